@@ -0,0 +1,16 @@
+// Initiates the single-node replica set `rs0` the first time the server is
+// up, then reports whether it has a primary. Run by the healthcheck of the
+// mongodb service: the init scripts of /docker-entrypoint-initdb.d run
+// against a temporary server started without --replSet, so they cannot.
+try {
+  rs.status();
+} catch (e) {
+  if (e.codeName !== "NotYetInitialized") {
+    throw e;
+  }
+  rs.initiate({ _id: "rs0", members: [{ _id: 0, host: "mongodb:27017" }] });
+}
+
+if (!db.hello().isWritablePrimary) {
+  quit(1);
+}
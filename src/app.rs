@@ -1,18 +1,42 @@
-use crate::config::Config;
+use crate::config::{CollectionsConfig, Config, IndexSyncMode};
+use crate::database::indexes::IndexRegistry;
 use crate::database::mongo_client::DatabaseClient;
-use crate::database::repositories::deployment_repository::DeploymentRepository;
-use crate::database::repositories::metrics_repository::MetricsRepository;
-use crate::database::repositories::predictors_repository::PredictorRepository;
-use crate::database::{ArticlePredictionsRepository, ArticleRepository};
+use crate::database::repositories::api_key_repository::MongoApiKeyRepository;
+use crate::database::repositories::deployment_repository::MongoDeploymentRepository;
+use crate::database::repositories::feed_source_repository::MongoFeedSourceRepository;
+use crate::database::repositories::health_repository::MongoHealthRepository;
+use crate::database::repositories::lease_repository::MongoLeaseRepository;
+use crate::database::repositories::metrics_repository::MongoMetricsRepository;
+use crate::database::repositories::predictors_repository::MongoPredictorRepository;
+use crate::database::repositories::rollout_repository::MongoRolloutRepository;
+use crate::database::{MongoArticlePredictionsRepository, MongoArticleRepository};
+use crate::lifecycle::{self, BackgroundTasks, Shutdown};
+use crate::services::api_key_service::ApiKeyService;
 use crate::services::article_service::ArticleService;
+use crate::services::deployment_service::DeploymentService;
+use crate::services::feed_service::FeedService;
+use crate::services::fetcher_service::FetcherService;
+use crate::services::health_service::{ExpectedCollection, HealthService};
 use crate::services::metrics_service::MetricsService;
 use crate::services::predictor_service::PredictorService;
+use crate::services::rollout_service::RolloutService;
+use crate::web::middleware::rate_limit::RateLimiter;
 use crate::web::routes::{self, AppState};
 use axum::Router;
-use log::{error, info};
+use log::{error, info, warn};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub struct App {
     pub router: Router,
+    db_client: DatabaseClient,
+    rollout_service: RolloutService,
+    rollout_scheduler_interval: Duration,
+    fetcher_service: Option<FetcherService>,
+    feed_service: Option<FeedService>,
+    listen_address: String,
+    shutdown_timeout: Duration,
 }
 
 impl App {
@@ -22,59 +46,289 @@ impl App {
             e
         })?;
 
-        let db_client = DatabaseClient::new(
-            &config.mongodb_connection_string,
-            &config.mongodb_database_name,
-        )
-        .await
-        .map_err(|e| {
+        let db_client = DatabaseClient::new(&config.mongodb).await.map_err(|e| {
             error!("Failed to connect to MongoDB: {}", e);
             e
         })?;
 
-        // Create all repositories
-        let articles_repository =
-            ArticleRepository::new(&db_client, &config.articles_collection_name);
+        let index_registry = index_registry(&config.collections);
+        match config.indexes.on_startup {
+            IndexSyncMode::Apply => index_registry.sync(&db_client, true).await.log(),
+            IndexSyncMode::Check => index_registry.sync(&db_client, false).await.log(),
+            IndexSyncMode::Skip => info!("Skipping index checks"),
+        }
 
-        let _article_predictions_repository = ArticlePredictionsRepository::new(
+        // Create all repositories
+        let articles_repository = MongoArticleRepository::new(
             &db_client,
-            &config.article_predictions_collection_name,
+            &config.collections.articles,
+            &config.collections.article_predictions,
         );
 
-        let _deployment_repository =
-            DeploymentRepository::new(&db_client, &config.deployment_collection_name);
+        let article_predictions_repository = Arc::new(MongoArticlePredictionsRepository::new(
+            &db_client,
+            &config.collections.article_predictions,
+        ));
 
-        let metrics_repository =
-            MetricsRepository::new(&db_client, &config.metrics_collection_name);
-        let predictor_repository =
-            PredictorRepository::new(&db_client, &config.predictor_collection_name);
+        let deployment_repository = Arc::new(MongoDeploymentRepository::new(
+            &db_client,
+            &config.collections.deployments,
+            &config.collections.deployment_history,
+            &config.collections.predictors,
+        ));
+
+        let metrics_repository = Arc::new(MongoMetricsRepository::new(
+            &db_client,
+            &config.collections.metrics,
+        ));
+        let predictor_repository = Arc::new(MongoPredictorRepository::new(
+            &db_client,
+            &config.collections.predictors,
+        ));
+        let rollout_repository = Arc::new(MongoRolloutRepository::new(
+            &db_client,
+            &config.collections.rollouts,
+        ));
+        let health_repository = Arc::new(MongoHealthRepository::new(&db_client));
+
+        let api_key_repository =
+            MongoApiKeyRepository::new(&db_client, &config.collections.api_keys);
+        let feed_source_repository = Arc::new(MongoFeedSourceRepository::new(
+            &db_client,
+            &config.collections.feed_sources,
+        ));
 
         // Create services
-        let article_service = ArticleService::new(articles_repository);
-        let metrics_service = MetricsService::new(metrics_repository);
-        let predictor_service = PredictorService::new(predictor_repository);
+        let api_key_service = ApiKeyService::new(
+            Arc::new(api_key_repository),
+            config.auth.admin_key.as_deref(),
+        );
+        let article_service = ArticleService::new(
+            Arc::new(articles_repository),
+            article_predictions_repository,
+        );
+        let metrics_service = MetricsService::new(metrics_repository.clone(), &config.cache);
+        let predictor_service = PredictorService::new(predictor_repository.clone(), &config.cache);
+        let deployment_service = DeploymentService::new(
+            deployment_repository,
+            predictor_repository.clone(),
+            predictor_service.clone(),
+        );
+        let rollout_service = RolloutService::new(
+            rollout_repository,
+            predictor_repository,
+            metrics_repository,
+            deployment_service.clone(),
+        );
+
+        let fetcher_service = if config.fetcher.enabled {
+            Some(
+                FetcherService::new(
+                    config.fetcher.clone(),
+                    Arc::new(MongoLeaseRepository::new(
+                        &db_client,
+                        &config.collections.leases,
+                    )),
+                    article_service.clone(),
+                    metrics_service.clone(),
+                )
+                .map_err(|e| {
+                    error!("Failed to create the news fetcher: {}", e);
+                    e
+                })?,
+            )
+        } else {
+            info!("News fetcher is disabled");
+            None
+        };
+
+        let feed_service = FeedService::new(
+            feed_source_repository,
+            article_service.clone(),
+            config.feeds.clone(),
+        )
+        .map_err(|e| {
+            error!("Failed to create the feed worker: {}", e);
+            e
+        })?;
+
+        // Every collection must carry the indexes its repository declares.
+        // History, rollouts, API keys, feeds and leases only exist once
+        // something has been written to them.
+        // Unless they are applied on startup, missing indexes only degrade
+        // readiness, since nothing but an operator will create them.
+        let indexes_required = config.indexes.on_startup == IndexSyncMode::Apply;
+        let expected_collection = |name: &str, required: bool| {
+            ExpectedCollection::new(name, required)
+                .with_indexes(index_registry.index_names(name))
+                .with_indexes_required(indexes_required)
+        };
+        let health_service = HealthService::new(
+            health_repository,
+            vec![
+                expected_collection(&config.collections.articles, true),
+                expected_collection(&config.collections.article_predictions, true),
+                expected_collection(&config.collections.deployments, true),
+                expected_collection(&config.collections.deployment_history, false),
+                expected_collection(&config.collections.metrics, true),
+                expected_collection(&config.collections.predictors, true),
+                expected_collection(&config.collections.rollouts, false),
+                expected_collection(&config.collections.api_keys, false),
+                expected_collection(&config.collections.feed_sources, false),
+                expected_collection(&config.collections.leases, false),
+            ],
+        );
 
-        // Create app state with both services
+        // Create app state with all services
         let app_state = AppState {
+            api_key_service,
             article_service,
+            deployment_service,
+            feed_service: feed_service.clone(),
+            health_service,
             metrics_service,
             predictor_service,
+            rollout_service: rollout_service.clone(),
+            limits: config.limits.clone(),
+            auth: config.auth.clone(),
+            rate_limiter: RateLimiter::new(config.rate_limits.clone()),
         };
 
-        let router = routes::create_router(app_state);
+        let router = routes::create_router(app_state, &config.server.cors_origins);
 
         info!("Application initialized successfully");
 
-        Ok(Self { router })
+        Ok(Self {
+            router,
+            db_client,
+            rollout_service,
+            rollout_scheduler_interval: Duration::from_secs(
+                config.rollouts.scheduler_interval_seconds,
+            ),
+            fetcher_service,
+            feed_service: Some(feed_service).filter(|_| config.feeds.enabled),
+            listen_address: config.listen_address(),
+            shutdown_timeout: Duration::from_secs(config.server.shutdown_timeout_seconds),
+        })
     }
 
+    /// Serves requests until SIGTERM or SIGINT, then stops accepting
+    /// connections, drains in-flight requests and background tasks within the
+    /// shutdown timeout, and closes the MongoDB client.
     pub async fn run(self) -> Result<(), Box<dyn std::error::Error>> {
-        let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await?;
+        let listener = tokio::net::TcpListener::bind(&self.listen_address).await?;
+
+        let shutdown = Shutdown::new();
+        let mut background_tasks = BackgroundTasks::new(shutdown.clone());
+
+        let rollout_service = self.rollout_service;
+        let rollout_scheduler_interval = self.rollout_scheduler_interval;
+        background_tasks.spawn("rollout_scheduler", move |shutdown| {
+            rollout_service
+                .clone()
+                .run_scheduler(rollout_scheduler_interval, shutdown)
+        });
+
+        if let Some(fetcher_service) = self.fetcher_service {
+            background_tasks.spawn("news_fetcher", move |shutdown| {
+                fetcher_service.clone().run_scheduler(shutdown)
+            });
+        }
 
-        info!("Server starting on http://0.0.0.0:8000");
+        if let Some(feed_service) = self.feed_service {
+            background_tasks.spawn("feed_worker", move |shutdown| {
+                feed_service.clone().run_scheduler(shutdown)
+            });
+        }
 
-        axum::serve(listener, self.router).await?;
+        let signal_shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            lifecycle::wait_for_signal().await;
+            signal_shutdown.trigger();
+        });
 
-        Ok(())
+        info!("Server starting on http://{}", self.listen_address);
+
+        // Peer addresses identify anonymous clients for rate limiting
+        let service = self
+            .router
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let server = axum::serve(listener, service).with_graceful_shutdown({
+            let shutdown = shutdown.clone();
+            async move { shutdown.wait().await }
+        });
+
+        let drain_deadline = async {
+            shutdown.wait().await;
+            info!(
+                "Shutting down, waiting up to {}s for in-flight requests",
+                self.shutdown_timeout.as_secs()
+            );
+            tokio::time::sleep(self.shutdown_timeout).await;
+        };
+
+        let result = tokio::select! {
+            result = server => result,
+            _ = drain_deadline => {
+                warn!("In-flight requests still running after the shutdown timeout, dropping them");
+                Ok(())
+            }
+        };
+
+        background_tasks.stop(self.shutdown_timeout).await;
+        self.db_client.shutdown(self.shutdown_timeout).await;
+
+        info!("Server stopped");
+
+        Ok(result?)
     }
 }
+
+/// Creates the indexes declared by the repositories, or only checks them when
+/// `check_only` is set, and prints the outcome. Returns whether every index is
+/// in place and as declared.
+pub async fn sync_indexes(check_only: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let config = Config::new()?;
+    let db_client = DatabaseClient::new(&config.mongodb).await?;
+
+    let report = index_registry(&config.collections)
+        .sync(&db_client, !check_only)
+        .await;
+
+    for outcome in &report.outcomes {
+        println!(
+            "{}.{}: {}",
+            outcome.collection, outcome.name, outcome.status
+        );
+    }
+    for (collection, name) in &report.unmanaged {
+        println!("{}.{}: not declared", collection, name);
+    }
+
+    db_client.shutdown(Duration::from_secs(5)).await;
+
+    Ok(!report.has_problems())
+}
+
+/// Indexes the repositories rely on, in the configured collections.
+fn index_registry(collections: &CollectionsConfig) -> IndexRegistry {
+    let mut registry = IndexRegistry::new();
+
+    MongoArticleRepository::register_indexes(&mut registry, &collections.articles);
+    MongoArticlePredictionsRepository::register_indexes(
+        &mut registry,
+        &collections.article_predictions,
+    );
+    MongoDeploymentRepository::register_indexes(
+        &mut registry,
+        &collections.deployments,
+        &collections.deployment_history,
+    );
+    MongoMetricsRepository::register_indexes(&mut registry, &collections.metrics);
+    MongoPredictorRepository::register_indexes(&mut registry, &collections.predictors);
+    MongoRolloutRepository::register_indexes(&mut registry, &collections.rollouts);
+    MongoApiKeyRepository::register_indexes(&mut registry, &collections.api_keys);
+    MongoFeedSourceRepository::register_indexes(&mut registry, &collections.feed_sources);
+
+    registry
+}
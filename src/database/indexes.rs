@@ -0,0 +1,521 @@
+use log::{error, info, warn};
+use mongodb::IndexModel;
+use mongodb::bson::{Bson, Document};
+use mongodb::options::IndexOptions;
+use std::fmt;
+
+use super::mongo_client::DatabaseClient;
+
+/// Index every collection has, which is never reported as unmanaged.
+const ID_INDEX_NAME: &str = "_id_";
+
+/// An index a repository relies on.
+#[derive(Debug, Clone)]
+pub struct IndexSpec {
+    pub collection: String,
+    pub name: String,
+    pub model: IndexModel,
+}
+
+/// Indexes declared by the repositories, created at startup or by the
+/// `indexes` command. Indexes are only ever created, never dropped or
+/// rebuilt: differences with existing ones are reported as drift.
+#[derive(Debug, Clone, Default)]
+pub struct IndexRegistry {
+    specs: Vec<IndexSpec>,
+}
+
+impl IndexRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, collection: &str, name: &str, keys: Document, mut options: IndexOptions) {
+        options.name = Some(name.to_string());
+
+        self.specs.push(IndexSpec {
+            collection: collection.to_string(),
+            name: name.to_string(),
+            model: IndexModel::builder().keys(keys).options(options).build(),
+        });
+    }
+
+    /// Names of the indexes declared on `collection`.
+    pub fn index_names(&self, collection: &str) -> Vec<&str> {
+        self.specs
+            .iter()
+            .filter(|spec| spec.collection == collection)
+            .map(|spec| spec.name.as_str())
+            .collect()
+    }
+
+    /// Managed collections, in the order they were first registered.
+    fn collections(&self) -> Vec<&str> {
+        let mut collections: Vec<&str> = Vec::new();
+        for spec in &self.specs {
+            if !collections.contains(&spec.collection.as_str()) {
+                collections.push(&spec.collection);
+            }
+        }
+        collections
+    }
+
+    /// Compares the declared indexes with the existing ones and, unless
+    /// `create_missing` is false, creates those that are missing.
+    pub async fn sync(&self, db_client: &DatabaseClient, create_missing: bool) -> IndexReport {
+        let mut report = IndexReport::default();
+
+        for collection in self.collections() {
+            let existing = match db_client.list_indexes(collection).await {
+                Ok(existing) => existing.unwrap_or_default(),
+                Err(e) => {
+                    for spec in self
+                        .specs
+                        .iter()
+                        .filter(|spec| spec.collection == collection)
+                    {
+                        report.push(spec, IndexStatus::Failed(e.to_string()));
+                    }
+                    continue;
+                }
+            };
+
+            for spec in self
+                .specs
+                .iter()
+                .filter(|spec| spec.collection == collection)
+            {
+                let status = match compare(spec, &existing) {
+                    Comparison::Matches => IndexStatus::Present,
+                    Comparison::Differs(details) => IndexStatus::Drifted(details),
+                    Comparison::Absent if !create_missing => IndexStatus::Missing,
+                    Comparison::Absent => match db_client
+                        .get_database()
+                        .collection::<Document>(collection)
+                        .create_index(spec.model.clone())
+                        .await
+                    {
+                        Ok(_) => IndexStatus::Created,
+                        Err(e) => IndexStatus::Failed(e.to_string()),
+                    },
+                };
+                report.push(spec, status);
+            }
+
+            for index in &existing {
+                let name = index_name(index).unwrap_or_default();
+                let is_declared = self
+                    .specs
+                    .iter()
+                    .any(|spec| spec.collection == collection && spec.name == name);
+                if name != ID_INDEX_NAME && !is_declared {
+                    report
+                        .unmanaged
+                        .push((collection.to_string(), name.to_string()));
+                }
+            }
+        }
+
+        report
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexStatus {
+    Present,
+    Created,
+    /// Absent, and left so because creation was not requested.
+    Missing,
+    /// An existing index differs from the declared one.
+    Drifted(String),
+    Failed(String),
+}
+
+impl fmt::Display for IndexStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexStatus::Present => write!(f, "present"),
+            IndexStatus::Created => write!(f, "created"),
+            IndexStatus::Missing => write!(f, "missing"),
+            IndexStatus::Drifted(details) => write!(f, "drifted: {}", details),
+            IndexStatus::Failed(message) => write!(f, "failed: {}", message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexOutcome {
+    pub collection: String,
+    pub name: String,
+    pub status: IndexStatus,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IndexReport {
+    pub outcomes: Vec<IndexOutcome>,
+    /// Existing indexes no repository declares, as `(collection, name)`.
+    pub unmanaged: Vec<(String, String)>,
+}
+
+impl IndexReport {
+    fn push(&mut self, spec: &IndexSpec, status: IndexStatus) {
+        self.outcomes.push(IndexOutcome {
+            collection: spec.collection.clone(),
+            name: spec.name.clone(),
+            status,
+        });
+    }
+
+    /// Whether any declared index is missing, drifted or failed to be created.
+    pub fn has_problems(&self) -> bool {
+        self.outcomes
+            .iter()
+            .any(|outcome| !matches!(outcome.status, IndexStatus::Present | IndexStatus::Created))
+    }
+
+    pub fn log(&self) {
+        for outcome in &self.outcomes {
+            match &outcome.status {
+                IndexStatus::Present => {}
+                IndexStatus::Created => {
+                    info!("Created index '{}' on {}", outcome.name, outcome.collection)
+                }
+                IndexStatus::Missing | IndexStatus::Drifted(_) => warn!(
+                    "Index '{}' on {} is {}",
+                    outcome.name, outcome.collection, outcome.status
+                ),
+                IndexStatus::Failed(message) => error!(
+                    "Failed to create index '{}' on {}: {}",
+                    outcome.name, outcome.collection, message
+                ),
+            }
+        }
+
+        for (collection, name) in &self.unmanaged {
+            info!(
+                "Index '{}' on {} is not declared by any repository",
+                name, collection
+            );
+        }
+
+        let count = |wanted: fn(&IndexStatus) -> bool| {
+            self.outcomes
+                .iter()
+                .filter(|outcome| wanted(&outcome.status))
+                .count()
+        };
+        info!(
+            "Successfully checked {} indexes: {} present, {} created, {} with problems",
+            self.outcomes.len(),
+            count(|status| *status == IndexStatus::Present),
+            count(|status| *status == IndexStatus::Created),
+            count(|status| !matches!(status, IndexStatus::Present | IndexStatus::Created)),
+        );
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Comparison {
+    Matches,
+    Differs(String),
+    Absent,
+}
+
+fn index_name(index: &IndexModel) -> Option<&str> {
+    index.options.as_ref()?.name.as_deref()
+}
+
+fn is_text_index(keys: &Document) -> bool {
+    keys.values().any(|value| value.as_str() == Some("text"))
+}
+
+/// Compares a declared index with the existing ones, matched by name or,
+/// failing that, by keys, since the server rejects a second index on the same
+/// keys.
+fn compare(spec: &IndexSpec, existing: &[IndexModel]) -> Comparison {
+    let by_name = existing
+        .iter()
+        .find(|index| index_name(index) == Some(spec.name.as_str()));
+
+    let Some(index) = by_name else {
+        if !is_text_index(&spec.model.keys) {
+            let same_keys = existing
+                .iter()
+                .find(|index| same_values(&index.keys, &spec.model.keys));
+            if let Some(index) = same_keys {
+                return Comparison::Differs(format!(
+                    "its keys are already indexed as '{}'",
+                    index_name(index).unwrap_or_default()
+                ));
+            }
+        }
+        return Comparison::Absent;
+    };
+
+    let mut differences = Vec::new();
+    let declared_options = spec.model.options.clone().unwrap_or_default();
+    let existing_options = index.options.clone().unwrap_or_default();
+
+    if is_text_index(&spec.model.keys) {
+        // The server stores text indexes under `_fts` and `_ftsx`, the indexed
+        // fields only show in the weights.
+        let default_weights: Document = spec
+            .model
+            .keys
+            .iter()
+            .filter(|(_, value)| value.as_str() == Some("text"))
+            .map(|(field, _)| (field.clone(), Bson::Int32(1)))
+            .collect();
+        let declared_weights = declared_options.weights.unwrap_or(default_weights);
+        let existing_weights = existing_options.weights.unwrap_or_default();
+
+        if !same_values_unordered(&existing_weights, &declared_weights) {
+            differences.push(format!(
+                "weights are {} instead of {}",
+                existing_weights, declared_weights
+            ));
+        }
+    } else if !same_values(&index.keys, &spec.model.keys) {
+        differences.push(format!(
+            "keys are {} instead of {}",
+            index.keys, spec.model.keys
+        ));
+    }
+
+    let declared_unique = declared_options.unique.unwrap_or(false);
+    if existing_options.unique.unwrap_or(false) != declared_unique {
+        differences.push(format!("unique should be {}", declared_unique));
+    }
+
+    let declared_sparse = declared_options.sparse.unwrap_or(false);
+    if existing_options.sparse.unwrap_or(false) != declared_sparse {
+        differences.push(format!("sparse should be {}", declared_sparse));
+    }
+
+    let declared_filter = declared_options
+        .partial_filter_expression
+        .unwrap_or_default();
+    let existing_filter = existing_options
+        .partial_filter_expression
+        .unwrap_or_default();
+    if !same_values(&existing_filter, &declared_filter) {
+        differences.push(format!(
+            "partial_filter_expression is {} instead of {}",
+            existing_filter, declared_filter
+        ));
+    }
+
+    if existing_options.expire_after != declared_options.expire_after {
+        differences.push(format!(
+            "expire_after is {:?} instead of {:?}",
+            existing_options.expire_after, declared_options.expire_after
+        ));
+    }
+
+    if differences.is_empty() {
+        Comparison::Matches
+    } else {
+        Comparison::Differs(differences.join(", "))
+    }
+}
+
+/// Equality of values, in order, that ignores the numeric type: the server may
+/// return `1` as a double or an int64.
+fn same_values(a: &Document, b: &Document) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b.iter())
+            .all(|((a_key, a_value), (b_key, b_value))| {
+                a_key == b_key && same_value(a_value, b_value)
+            })
+}
+
+fn same_values_unordered(a: &Document, b: &Document) -> bool {
+    a.len() == b.len()
+        && a.iter().all(|(key, a_value)| {
+            b.get(key)
+                .is_some_and(|b_value| same_value(a_value, b_value))
+        })
+}
+
+fn same_value(a: &Bson, b: &Bson) -> bool {
+    if let (Bson::Document(a), Bson::Document(b)) = (a, b) {
+        return same_values(a, b);
+    }
+
+    match (as_number(a), as_number(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+fn as_number(value: &Bson) -> Option<f64> {
+    match value {
+        Bson::Int32(value) => Some(f64::from(*value)),
+        Bson::Int64(value) => Some(*value as f64),
+        Bson::Double(value) => Some(*value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    fn spec(keys: Document, options: IndexOptions) -> IndexSpec {
+        let mut registry = IndexRegistry::new();
+        registry.add("articles", "declared", keys, options);
+        registry.specs.remove(0)
+    }
+
+    fn existing(name: &str, keys: Document, options: IndexOptions) -> IndexModel {
+        let mut options = options;
+        options.name = Some(name.to_string());
+        IndexModel::builder().keys(keys).options(options).build()
+    }
+
+    #[test]
+    fn matches_indexes_by_name_regardless_of_number_types() {
+        let spec = spec(
+            doc! { "published_at": -1, "_id": -1 },
+            IndexOptions::default(),
+        );
+        let indexes = [existing(
+            "declared",
+            doc! { "published_at": -1.0, "_id": -1_i64 },
+            IndexOptions::default(),
+        )];
+
+        assert_eq!(compare(&spec, &indexes), Comparison::Matches);
+        assert_eq!(compare(&spec, &[]), Comparison::Absent);
+    }
+
+    #[test]
+    fn reports_keys_and_options_that_differ() {
+        let spec = spec(
+            doc! { "key_hash": 1 },
+            IndexOptions::builder().unique(true).build(),
+        );
+
+        let indexes = [existing(
+            "declared",
+            doc! { "key_hash": -1 },
+            IndexOptions::default(),
+        )];
+        let Comparison::Differs(details) = compare(&spec, &indexes) else {
+            panic!("expected drift");
+        };
+        assert!(details.contains("keys are"), "{}", details);
+        assert!(details.contains("unique should be true"), "{}", details);
+
+        // Same keys under another name would make creation fail
+        let indexes = [existing(
+            "legacy",
+            doc! { "key_hash": 1 },
+            IndexOptions::default(),
+        )];
+        assert!(matches!(compare(&spec, &indexes), Comparison::Differs(_)));
+    }
+
+    #[test]
+    fn compares_partial_filter_expressions() {
+        let spec = spec(
+            doc! { "prediction_type": 1 },
+            IndexOptions::builder()
+                .unique(true)
+                .partial_filter_expression(doc! { "attempts": { "$gt": 0 } })
+                .build(),
+        );
+
+        let indexes = [existing(
+            "declared",
+            doc! { "prediction_type": 1 },
+            IndexOptions::builder()
+                .unique(true)
+                .partial_filter_expression(doc! { "attempts": { "$gt": 0.0 } })
+                .build(),
+        )];
+        assert_eq!(compare(&spec, &indexes), Comparison::Matches);
+
+        let indexes = [existing(
+            "declared",
+            doc! { "prediction_type": 1 },
+            IndexOptions::builder()
+                .unique(true)
+                .partial_filter_expression(doc! { "status": "active" })
+                .build(),
+        )];
+        let Comparison::Differs(details) = compare(&spec, &indexes) else {
+            panic!("expected drift");
+        };
+        assert!(
+            details.contains("partial_filter_expression is"),
+            "{}",
+            details
+        );
+
+        let indexes = [existing(
+            "declared",
+            doc! { "prediction_type": 1 },
+            IndexOptions::builder().unique(true).build(),
+        )];
+        assert!(matches!(compare(&spec, &indexes), Comparison::Differs(_)));
+    }
+
+    #[test]
+    fn lists_index_names_per_collection() {
+        let mut registry = IndexRegistry::new();
+        registry.add(
+            "articles",
+            "by_date",
+            doc! { "published_at": -1 },
+            IndexOptions::default(),
+        );
+        registry.add(
+            "api_keys",
+            "by_hash",
+            doc! { "key_hash": 1 },
+            IndexOptions::default(),
+        );
+        registry.add(
+            "articles",
+            "by_source",
+            doc! { "source": 1 },
+            IndexOptions::default(),
+        );
+
+        assert_eq!(registry.index_names("articles"), ["by_date", "by_source"]);
+        assert_eq!(registry.index_names("api_keys"), ["by_hash"]);
+        assert!(registry.index_names("metrics").is_empty());
+    }
+
+    #[test]
+    fn compares_text_indexes_by_weights() {
+        let spec = spec(
+            doc! { "title": "text", "content": "text" },
+            IndexOptions::builder()
+                .weights(doc! { "title": 10, "content": 1 })
+                .build(),
+        );
+        let stored_keys = doc! { "_fts": "text", "_ftsx": 1 };
+
+        let indexes = [existing(
+            "declared",
+            stored_keys.clone(),
+            IndexOptions::builder()
+                .weights(doc! { "content": 1, "title": 10 })
+                .build(),
+        )];
+        assert_eq!(compare(&spec, &indexes), Comparison::Matches);
+
+        let indexes = [existing(
+            "declared",
+            stored_keys,
+            IndexOptions::builder()
+                .weights(doc! { "title": 1, "content": 1 })
+                .build(),
+        )];
+        assert!(matches!(compare(&spec, &indexes), Comparison::Differs(_)));
+    }
+}
@@ -1,6 +1,9 @@
+pub mod indexes;
 pub mod mongo_client;
 pub mod repositories;
 
-pub use repositories::article_prediction_repository::ArticlePredictionsRepository;
-pub use repositories::article_repository::ArticleRepository;
+pub use repositories::article_prediction_repository::{
+    ArticlePredictionsRepository, MongoArticlePredictionsRepository,
+};
+pub use repositories::article_repository::{ArticleRepository, MongoArticleRepository};
 pub use repositories::models::article_repository_models::ArticleDocument;
@@ -1,19 +1,32 @@
-use log::info;
-use mongodb::{Client, Database};
+use log::{info, warn};
+use mongodb::error::{CommandError, ErrorKind};
+use mongodb::options::ClientOptions;
+use mongodb::{Client, Database, IndexModel};
+use std::time::Duration;
+
+use crate::config::MongoConfig;
+
+/// Server error code returned when a collection does not exist.
+const NAMESPACE_NOT_FOUND: i32 = 26;
 
 #[derive(Clone)]
 pub struct DatabaseClient {
+    client: Client,
     pub database: Database,
 }
 
 impl DatabaseClient {
-    pub async fn new(
-        connection_string: &str,
-        database_name: &str,
-    ) -> Result<Self, mongodb::error::Error> {
-        let client = Client::with_uri_str(connection_string).await?;
+    pub async fn new(config: &MongoConfig) -> Result<Self, mongodb::error::Error> {
+        let mut options = ClientOptions::parse(&config.connection_string).await?;
+        options.min_pool_size = Some(config.min_pool_size);
+        options.max_pool_size = Some(config.max_pool_size);
+        options.connect_timeout = Some(Duration::from_secs(config.connect_timeout_seconds));
+        options.server_selection_timeout =
+            Some(Duration::from_secs(config.server_selection_timeout_seconds));
+
+        let client = Client::with_options(options)?;
 
-        let database = client.database(database_name);
+        let database = client.database(&config.database_name);
 
         client
             .database("admin")
@@ -21,14 +34,83 @@ impl DatabaseClient {
             .await?;
 
         info!(
-            "Successfully connected to MongoDB database: {}",
-            database_name
+            "Successfully connected to MongoDB database: {} (pool size {}-{})",
+            config.database_name, config.min_pool_size, config.max_pool_size
         );
 
-        Ok(Self { database })
+        Ok(Self { client, database })
+    }
+
+    /// Closes the connection pools once the cursors and sessions still in use
+    /// are dropped, or after `timeout`.
+    pub async fn shutdown(self, timeout: Duration) {
+        match tokio::time::timeout(timeout, self.client.shutdown()).await {
+            Ok(()) => info!("Closed MongoDB client"),
+            Err(_) => warn!(
+                "MongoDB client still busy after {}s, closing it anyway",
+                timeout.as_secs()
+            ),
+        }
     }
 
     pub fn get_database(&self) -> Database {
         self.database.clone()
     }
+
+    pub async fn ping(&self) -> Result<(), mongodb::error::Error> {
+        self.database
+            .run_command(mongodb::bson::doc! {"ping": 1})
+            .await?;
+
+        Ok(())
+    }
+
+    /// Lists the index names of a collection, or `None` if it does not exist.
+    pub async fn list_index_names(
+        &self,
+        collection_name: &str,
+    ) -> Result<Option<Vec<String>>, mongodb::error::Error> {
+        let collection = self
+            .database
+            .collection::<mongodb::bson::Document>(collection_name);
+
+        match collection.list_index_names().await {
+            Ok(index_names) => Ok(Some(index_names)),
+            Err(e) if is_namespace_not_found(&e) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Lists the indexes of a collection, or `None` if it does not exist.
+    pub async fn list_indexes(
+        &self,
+        collection_name: &str,
+    ) -> Result<Option<Vec<IndexModel>>, mongodb::error::Error> {
+        let collection = self
+            .database
+            .collection::<mongodb::bson::Document>(collection_name);
+
+        let mut cursor = match collection.list_indexes().await {
+            Ok(cursor) => cursor,
+            Err(e) if is_namespace_not_found(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut indexes = Vec::new();
+        while cursor.advance().await? {
+            indexes.push(cursor.deserialize_current()?);
+        }
+
+        Ok(Some(indexes))
+    }
+}
+
+fn is_namespace_not_found(e: &mongodb::error::Error) -> bool {
+    matches!(
+        *e.kind,
+        ErrorKind::Command(CommandError {
+            code: NAMESPACE_NOT_FOUND,
+            ..
+        })
+    )
 }
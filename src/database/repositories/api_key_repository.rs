@@ -0,0 +1,148 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::info;
+use mongodb::Collection;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::{IndexOptions, ReturnDocument};
+
+use crate::database::indexes::IndexRegistry;
+use crate::database::mongo_client::DatabaseClient;
+
+use super::models::api_key_repository_models::ApiKeyDocument;
+
+/// Name of the unique index on `key_hash`, looked up on every authenticated request.
+pub const KEY_HASH_INDEX_NAME: &str = "api_keys_key_hash";
+
+#[async_trait]
+pub trait ApiKeyRepository: Send + Sync {
+    async fn insert(&self, api_key: &ApiKeyDocument) -> Result<ObjectId, mongodb::error::Error>;
+
+    async fn find_by_hash(
+        &self,
+        key_hash: &str,
+    ) -> Result<Option<ApiKeyDocument>, mongodb::error::Error>;
+
+    async fn find_by_id(
+        &self,
+        api_key_id: ObjectId,
+    ) -> Result<Option<ApiKeyDocument>, mongodb::error::Error>;
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKeyDocument>, mongodb::error::Error>;
+
+    /// Revokes a key that is not revoked yet, returning it as revoked.
+    async fn revoke(
+        &self,
+        api_key_id: ObjectId,
+        revoked_at: DateTime<Utc>,
+    ) -> Result<Option<ApiKeyDocument>, mongodb::error::Error>;
+}
+
+#[derive(Clone)]
+pub struct MongoApiKeyRepository {
+    collection: Collection<ApiKeyDocument>,
+}
+
+impl MongoApiKeyRepository {
+    pub fn new(db_client: &DatabaseClient, collection_name: &str) -> Self {
+        let collection: Collection<ApiKeyDocument> =
+            db_client.get_database().collection(collection_name);
+
+        info!(
+            "Created MongoApiKeyRepository for collection: {}",
+            collection_name
+        );
+
+        Self { collection }
+    }
+
+    pub fn register_indexes(registry: &mut IndexRegistry, collection_name: &str) {
+        registry.add(
+            collection_name,
+            KEY_HASH_INDEX_NAME,
+            doc! { "key_hash": 1 },
+            IndexOptions::builder().unique(true).build(),
+        );
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for MongoApiKeyRepository {
+    async fn insert(&self, api_key: &ApiKeyDocument) -> Result<ObjectId, mongodb::error::Error> {
+        let result = self.collection.insert_one(api_key).await?;
+
+        let api_key_id = result.inserted_id.as_object_id().unwrap_or_default();
+
+        info!(
+            "Created API key {} '{}' with role {}",
+            api_key_id,
+            api_key.name,
+            api_key.role.as_str()
+        );
+
+        Ok(api_key_id)
+    }
+
+    async fn find_by_hash(
+        &self,
+        key_hash: &str,
+    ) -> Result<Option<ApiKeyDocument>, mongodb::error::Error> {
+        self.collection
+            .find_one(doc! { "key_hash": key_hash })
+            .await
+    }
+
+    async fn find_by_id(
+        &self,
+        api_key_id: ObjectId,
+    ) -> Result<Option<ApiKeyDocument>, mongodb::error::Error> {
+        self.collection.find_one(doc! { "_id": api_key_id }).await
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKeyDocument>, mongodb::error::Error> {
+        let mut options = mongodb::options::FindOptions::default();
+        options.sort = Some(doc! { "created_at": -1, "_id": -1 });
+
+        let mut cursor = self
+            .collection
+            .find(doc! {})
+            .with_options(Some(options))
+            .await?;
+
+        let mut api_keys = Vec::new();
+
+        while cursor.advance().await? {
+            match cursor.deserialize_current() {
+                Ok(api_key) => api_keys.push(api_key),
+                Err(e) => {
+                    log::error!("Failed to deserialize API key: {}", e);
+                    return Err(e);
+                }
+            }
+        }
+
+        info!("Retrieved {} API keys from database", api_keys.len());
+
+        Ok(api_keys)
+    }
+
+    async fn revoke(
+        &self,
+        api_key_id: ObjectId,
+        revoked_at: DateTime<Utc>,
+    ) -> Result<Option<ApiKeyDocument>, mongodb::error::Error> {
+        let api_key = self
+            .collection
+            .find_one_and_update(
+                doc! { "_id": api_key_id, "revoked_at": null },
+                doc! { "$set": { "revoked_at": revoked_at } },
+            )
+            .return_document(ReturnDocument::After)
+            .await?;
+
+        if api_key.is_some() {
+            info!("Revoked API key {}", api_key_id);
+        }
+
+        Ok(api_key)
+    }
+}
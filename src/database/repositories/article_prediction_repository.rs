@@ -1,30 +1,59 @@
+use async_trait::async_trait;
 use log::info;
 use mongodb::Collection;
 use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::IndexOptions;
 
+use crate::database::indexes::IndexRegistry;
 use crate::database::mongo_client::DatabaseClient;
 
 use super::models::article_prediction_repository_models::ArticlePredictionsDocument;
 
+#[async_trait]
+pub trait ArticlePredictionsRepository: Send + Sync {
+    async fn find_by_article_id_and_prediction_type(
+        &self,
+        article_id: ObjectId,
+        prediction_type: &str,
+    ) -> Result<Option<ArticlePredictionsDocument>, mongodb::error::Error>;
+
+    async fn find_by_article_id(
+        &self,
+        article_id: ObjectId,
+    ) -> Result<Vec<ArticlePredictionsDocument>, mongodb::error::Error>;
+}
+
 #[derive(Clone)]
-pub struct ArticlePredictionsRepository {
+pub struct MongoArticlePredictionsRepository {
     collection: Collection<ArticlePredictionsDocument>,
 }
 
-impl ArticlePredictionsRepository {
+impl MongoArticlePredictionsRepository {
     pub fn new(db_client: &DatabaseClient, collection_name: &str) -> Self {
         let collection: Collection<ArticlePredictionsDocument> =
             db_client.get_database().collection(collection_name);
 
         info!(
-            "Created ArticlePredictionsRepository for collection: {}",
+            "Created MongoArticlePredictionsRepository for collection: {}",
             collection_name
         );
 
         Self { collection }
     }
 
-    pub async fn find_by_article_id_and_prediction_type(
+    pub fn register_indexes(registry: &mut IndexRegistry, collection_name: &str) {
+        registry.add(
+            collection_name,
+            "article_predictions_article_id_prediction_type",
+            doc! { "article_id": 1, "prediction_type": 1 },
+            IndexOptions::default(),
+        );
+    }
+}
+
+#[async_trait]
+impl ArticlePredictionsRepository for MongoArticlePredictionsRepository {
+    async fn find_by_article_id_and_prediction_type(
         &self,
         article_id: ObjectId,
         prediction_type: &str,
@@ -51,4 +80,40 @@ impl ArticlePredictionsRepository {
             }
         }
     }
+
+    async fn find_by_article_id(
+        &self,
+        article_id: ObjectId,
+    ) -> Result<Vec<ArticlePredictionsDocument>, mongodb::error::Error> {
+        let filter = doc! { "article_id": article_id };
+
+        let mut options = mongodb::options::FindOptions::default();
+        options.sort = Some(doc! { "prediction_type": 1 });
+
+        let mut cursor = self
+            .collection
+            .find(filter)
+            .with_options(Some(options))
+            .await?;
+
+        let mut predictions = Vec::new();
+
+        while cursor.advance().await? {
+            match cursor.deserialize_current() {
+                Ok(prediction) => predictions.push(prediction),
+                Err(e) => {
+                    log::error!("Failed to deserialize article prediction: {}", e);
+                    return Err(e);
+                }
+            }
+        }
+
+        info!(
+            "Found {} predictions for article {}",
+            predictions.len(),
+            article_id
+        );
+
+        Ok(predictions)
+    }
 }
@@ -1,16 +1,54 @@
+use async_trait::async_trait;
 use bson::Document;
-use log::{error, info};
+use log::info;
 use mongodb::Collection;
-use mongodb::bson::doc;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::{IndexOptions, ReturnDocument};
 use serde::Deserialize;
 
+use crate::database::indexes::IndexRegistry;
 use crate::database::mongo_client::DatabaseClient;
 
-use super::models::article_repository_models::{ArticleDocument, PaginatedArticles};
+use super::models::article_repository_models::{
+    ArticleDocument, ArticleUpsert, PaginatedArticles, PredictionFilter,
+};
+use super::models::pagination_models::{CursorDirection, PageCursor, page_cursors};
+
+pub const TEXT_INDEX_NAME: &str = "articles_text_search";
+
+#[async_trait]
+pub trait ArticleRepository: Send + Sync {
+    async fn find_by_id(
+        &self,
+        article_id: ObjectId,
+    ) -> Result<Option<ArticleDocument>, mongodb::error::Error>;
+
+    async fn list_articles_with_all_predictions(
+        &self,
+        limit: Option<i64>,
+        skip: Option<u64>,
+        page_cursor: Option<&PageCursor>,
+        search_query: Option<&str>,
+        source: Option<&str>,
+        prediction_filters: &[PredictionFilter],
+    ) -> Result<PaginatedArticles, mongodb::error::Error>;
+
+    /// Inserts `article`, or updates the article with the same `url_hash`
+    /// while keeping its id, `created_at` and predictions. An estimated
+    /// `published_at` is only set on insert.
+    async fn upsert_by_url_hash(
+        &self,
+        url_hash: &str,
+        article: &ArticleDocument,
+        published_at_estimated: bool,
+    ) -> Result<ArticleUpsert, mongodb::error::Error>;
+}
 
 #[derive(Clone)]
-pub struct ArticleRepository {
+pub struct MongoArticleRepository {
     collection: Collection<ArticleDocument>,
+    /// Joined by `$lookup` when listing articles with their predictions
+    predictions_collection_name: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -18,6 +56,8 @@ struct FacetResult {
     data: Vec<ArticleDocument>,
     #[serde(rename = "totalCount")]
     total_count: Vec<CountResult>,
+    #[serde(rename = "precedingCount", default)]
+    preceding_count: Vec<CountResult>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -25,86 +65,97 @@ struct CountResult {
     count: u64,
 }
 
-impl ArticleRepository {
-    pub fn new(db_client: &DatabaseClient, collection_name: &str) -> Self {
+impl MongoArticleRepository {
+    pub fn new(
+        db_client: &DatabaseClient,
+        collection_name: &str,
+        predictions_collection_name: &str,
+    ) -> Self {
         let collection: Collection<ArticleDocument> =
             db_client.get_database().collection(collection_name);
 
         info!(
-            "Created ArticleRepository for collection: {}",
-            collection_name
+            "Created MongoArticleRepository for collections: {}, {}",
+            collection_name, predictions_collection_name
         );
 
-        Self { collection }
+        Self {
+            collection,
+            predictions_collection_name: predictions_collection_name.to_string(),
+        }
     }
 
-    pub async fn list_articles(
-        &self,
-        limit: Option<i64>,
-        skip: Option<u64>,
-    ) -> Result<PaginatedArticles, mongodb::error::Error> {
-        let filter = doc! {};
-
-        let total_count = self.collection.count_documents(filter.clone()).await?;
-
-        let mut options = mongodb::options::FindOptions::default();
-        let skip_count = skip.unwrap_or(0);
-        let limit_count = limit.unwrap_or(20);
-
-        options.skip = Some(skip_count);
-        options.limit = Some(limit_count);
-        options.sort = Some(doc! { "published_at": -1 });
+    /// Text index of `/articles/search`, the `published_at` order of
+    /// listings, and the URL hash ingested articles are deduplicated on.
+    pub fn register_indexes(registry: &mut IndexRegistry, collection_name: &str) {
+        registry.add(
+            collection_name,
+            TEXT_INDEX_NAME,
+            doc! {
+                "title": "text",
+                "description": "text",
+                "content": "text"
+            },
+            IndexOptions::builder()
+                .weights(doc! {
+                    "title": 10,
+                    "description": 5,
+                    "content": 1
+                })
+                .build(),
+        );
+        registry.add(
+            collection_name,
+            "articles_published_at",
+            doc! { "published_at": -1, "_id": -1 },
+            IndexOptions::default(),
+        );
+        // Sparse, since articles imported before ingestion have no hash
+        registry.add(
+            collection_name,
+            "articles_url_hash",
+            doc! { "url_hash": 1 },
+            IndexOptions::builder().unique(true).sparse(true).build(),
+        );
+    }
 
-        let mut cursor = self
-            .collection
-            .find(filter)
-            .with_options(Some(options))
-            .await?;
+    fn build_prediction_filters_match(prediction_filters: &[PredictionFilter]) -> Document {
+        let mut match_doc = doc! {};
 
-        let mut articles = Vec::new();
+        for filter in prediction_filters {
+            let field_prefix = format!("predictions.{}", filter.prediction_type);
 
-        while cursor.advance().await? {
-            match cursor.deserialize_current() {
-                Ok(article) => articles.push(article),
-                Err(e) => {
-                    error!("Failed to deserialize article: {}", e);
-                    return Err(e);
+            match filter.values.as_slice() {
+                [] => {}
+                [value] => {
+                    match_doc.insert(format!("{}.prediction_value", field_prefix), value);
+                }
+                values => {
+                    match_doc.insert(
+                        format!("{}.prediction_value", field_prefix),
+                        doc! { "$in": values },
+                    );
                 }
             }
-        }
 
-        let current_page_count = articles.len();
-        let page = (skip_count / limit_count as u64) + 1;
-        let total_pages = total_count.div_ceil(limit_count as u64);
-
-        info!(
-            "Retrieved {} articles from database (page {} of {})",
-            current_page_count, page, total_pages
-        );
+            if let Some(min_confidence) = filter.min_confidence {
+                match_doc.insert(
+                    format!("{}.prediction_confidence", field_prefix),
+                    doc! { "$gte": min_confidence },
+                );
+            }
+        }
 
-        Ok(PaginatedArticles {
-            articles,
-            total_count,
-            current_page_count,
-            page,
-            per_page: limit_count,
-            total_pages,
-        })
+        match_doc
     }
 
-    pub async fn list_articles_with_all_predictions(
-        &self,
-        limit: Option<i64>,
-        skip: Option<u64>,
-        sentiment: Option<&str>,
-    ) -> Result<PaginatedArticles, mongodb::error::Error> {
-        let skip_count = skip.unwrap_or(0);
-        let limit_count = limit.unwrap_or(20);
-
-        let mut pipeline = vec![
+    /// Stages adding the selected prediction of every type as `predictions`,
+    /// and the sentiment as `sentiment_analysis`.
+    fn prediction_lookup_stages(&self) -> Vec<Document> {
+        vec![
             doc! {
                 "$lookup": {
-                    "from": "article_predictions",
+                    "from": &self.predictions_collection_name,
                     "let": { "articleId": "$_id" },
                     "pipeline": [
                         {
@@ -167,89 +218,349 @@ impl ArticleRepository {
                     "all_predictions": 0
                 }
             },
-        ];
+        ]
+    }
 
-        if let Some(sentiment_value) = sentiment {
-            pipeline.push(doc! {
-                "$match": {
-                    "predictions.sentiment_analysis.prediction_value": sentiment_value
+    /// Page without prediction filters: the page is sorted and limited
+    /// before predictions are looked up, so that cursor pages are read from
+    /// `articles_published_at`, and counts never look predictions up.
+    ///
+    /// Returns one extra article when another page follows, the total count
+    /// and the number of articles preceding the cursor.
+    async fn find_page(
+        &self,
+        filter: Document,
+        search_query: Option<&str>,
+        page_cursor: Option<&PageCursor>,
+        skip_count: u64,
+        limit_count: i64,
+    ) -> Result<(Vec<ArticleDocument>, u64, u64), mongodb::error::Error> {
+        let total_count = self.collection.count_documents(filter.clone()).await?;
+
+        let mut pipeline = Vec::new();
+        let preceding_count = match page_cursor {
+            Some(page_cursor) => {
+                let sort_order = page_cursor.sort_order();
+                pipeline.push(doc! {
+                    "$match": and_filters(filter.clone(), page_cursor.range_filter("published_at"))
+                });
+                pipeline.push(doc! { "$sort": { "published_at": sort_order, "_id": sort_order } });
+
+                self.collection
+                    .count_documents(and_filters(
+                        filter,
+                        page_cursor.preceding_filter("published_at"),
+                    ))
+                    .await?
+            }
+            _none => {
+                if !filter.is_empty() {
+                    pipeline.push(doc! { "$match": filter });
                 }
-            });
+                let sort = match search_query {
+                    Some(_) => {
+                        pipeline.push(
+                            doc! { "$addFields": { "search_score": { "$meta": "textScore" } } },
+                        );
+                        doc! { "search_score": -1, "published_at": -1, "_id": -1 }
+                    }
+                    _none => doc! { "published_at": -1, "_id": -1 },
+                };
+                pipeline.push(doc! { "$sort": sort });
+                pipeline.push(doc! { "$skip": skip_count as i64 });
+                skip_count
+            }
+        };
+        // One extra document is fetched to know whether another page follows
+        pipeline.push(doc! { "$limit": limit_count + 1 });
+        pipeline.extend(self.prediction_lookup_stages());
+
+        let mut cursor = self
+            .collection
+            .aggregate(pipeline)
+            .with_type::<ArticleDocument>()
+            .await?;
+
+        let mut articles = Vec::new();
+        while cursor.advance().await? {
+            articles.push(cursor.deserialize_current()?);
         }
 
+        Ok((articles, total_count, preceding_count))
+    }
+
+    /// Page with prediction filters, which need the predictions of every
+    /// matching article before the page can be cut.
+    async fn find_filtered_page(
+        &self,
+        filter: Document,
+        search_query: Option<&str>,
+        page_cursor: Option<&PageCursor>,
+        prediction_filters: &[PredictionFilter],
+        skip_count: u64,
+        limit_count: i64,
+    ) -> Result<(Vec<ArticleDocument>, u64, u64), mongodb::error::Error> {
+        let mut pipeline = Vec::new();
+        if !filter.is_empty() {
+            pipeline.push(doc! { "$match": filter });
+        }
+        if search_query.is_some() {
+            pipeline.push(doc! { "$addFields": { "search_score": { "$meta": "textScore" } } });
+        }
+        pipeline.extend(self.prediction_lookup_stages());
         pipeline.push(doc! {
-            "$sort": { "published_at": -1 }
+            "$match": Self::build_prediction_filters_match(prediction_filters)
         });
 
-        pipeline.push(doc! {
-            "$facet": {
-                "data": [
-                    { "$skip": skip_count as i32 },
-                    { "$limit": limit_count as i32 }
-                ],
-                "totalCount": [
-                    { "$group": { "_id": null, "count": { "$sum": 1 } } }
-                ]
+        let mut data_pipeline = Vec::new();
+        let mut facet = doc! {
+            "totalCount": [
+                { "$group": { "_id": null, "count": { "$sum": 1 } } }
+            ]
+        };
+
+        match page_cursor {
+            Some(page_cursor) => {
+                let sort_order = page_cursor.sort_order();
+                data_pipeline.push(doc! { "$match": page_cursor.range_filter("published_at") });
+                data_pipeline
+                    .push(doc! { "$sort": { "published_at": sort_order, "_id": sort_order } });
+
+                facet.insert(
+                    "precedingCount",
+                    vec![
+                        doc! { "$match": page_cursor.preceding_filter("published_at") },
+                        doc! { "$count": "count" },
+                    ],
+                );
             }
-        });
+            _none => {
+                let sort = match search_query {
+                    Some(_) => doc! { "search_score": -1, "published_at": -1, "_id": -1 },
+                    _none => doc! { "published_at": -1, "_id": -1 },
+                };
+                data_pipeline.push(doc! { "$sort": sort });
+                data_pipeline.push(doc! { "$skip": skip_count as i64 });
+            }
+        }
+        // One extra document is fetched to know whether another page follows
+        data_pipeline.push(doc! { "$limit": limit_count + 1 });
+        facet.insert("data", data_pipeline);
+
+        pipeline.push(doc! { "$facet": facet });
 
         let mut cursor = self.collection.aggregate(pipeline).await?;
 
-        if cursor.advance().await? {
-            let doc = cursor.current();
-            let document: Document = doc.try_into()?;
-            let facet_result: FacetResult = mongodb::bson::from_document(document)?;
+        if !cursor.advance().await? {
+            return Ok((Vec::new(), 0, skip_count));
+        }
 
-            let total_count = facet_result
-                .total_count
+        let document: Document = cursor.current().try_into()?;
+        let facet_result: FacetResult = mongodb::bson::from_document(document)?;
+
+        let total_count = facet_result
+            .total_count
+            .first()
+            .map(|c| c.count)
+            .unwrap_or(0);
+        let preceding_count = match page_cursor {
+            Some(_) => facet_result
+                .preceding_count
                 .first()
                 .map(|c| c.count)
-                .unwrap_or(0);
-
-            let current_page_count = facet_result.data.len();
-            let page = (skip_count / limit_count as u64) + 1;
-            let total_pages = total_count.div_ceil(limit_count as u64);
-
-            let log_message = if let Some(sentiment_value) = sentiment {
-                format!(
-                    "Retrieved {} articles with sentiment '{}' and all predictions from database (page {} of {})",
-                    current_page_count, sentiment_value, page, total_pages
-                )
-            } else {
-                format!(
-                    "Retrieved {} articles with all predictions from database (page {} of {})",
-                    current_page_count, page, total_pages
-                )
-            };
-            info!("{}", log_message);
-
-            Ok(PaginatedArticles {
-                articles: facet_result.data,
-                total_count,
-                current_page_count,
-                page,
-                per_page: limit_count,
-                total_pages,
-            })
-        } else {
-            Ok(PaginatedArticles {
-                articles: vec![],
-                total_count: 0,
-                current_page_count: 0,
-                page: 1,
-                per_page: limit_count,
-                total_pages: 0,
-            })
+                .unwrap_or(0),
+            _none => skip_count,
+        };
+
+        Ok((facet_result.data, total_count, preceding_count))
+    }
+}
+
+/// Matches the documents matching both filters.
+fn and_filters(filter: Document, other: Document) -> Document {
+    if filter.is_empty() {
+        other
+    } else {
+        doc! { "$and": [filter, other] }
+    }
+}
+
+#[async_trait]
+impl ArticleRepository for MongoArticleRepository {
+    async fn find_by_id(
+        &self,
+        article_id: ObjectId,
+    ) -> Result<Option<ArticleDocument>, mongodb::error::Error> {
+        let filter = doc! { "_id": article_id };
+
+        match self.collection.find_one(filter).await? {
+            Some(article) => {
+                info!("Found article {}", article_id);
+                Ok(Some(article))
+            }
+            _none => {
+                info!("No article found with id {}", article_id);
+                Ok(None)
+            }
         }
     }
 
-    pub async fn list_articles_with_sentiment(
+    async fn list_articles_with_all_predictions(
         &self,
         limit: Option<i64>,
         skip: Option<u64>,
-        sentiment: Option<&str>,
+        page_cursor: Option<&PageCursor>,
+        search_query: Option<&str>,
+        source: Option<&str>,
+        prediction_filters: &[PredictionFilter],
     ) -> Result<PaginatedArticles, mongodb::error::Error> {
-        self.list_articles_with_all_predictions(limit, skip, sentiment)
-            .await
+        let skip_count = skip.unwrap_or(0);
+        let limit_count = limit.unwrap_or(20);
+
+        // `$text` is only allowed in the first stage of a pipeline, and the
+        // source is matched there too, before predictions are looked up
+        let mut initial_match = doc! {};
+        if let Some(search_query) = search_query {
+            initial_match.insert("$text", doc! { "$search": search_query });
+        }
+        if let Some(source) = source {
+            initial_match.insert(
+                "$or",
+                vec![doc! { "source.id": source }, doc! { "source.name": source }],
+            );
+        }
+
+        let (mut articles, total_count, preceding_count) = if prediction_filters.is_empty() {
+            self.find_page(
+                initial_match,
+                search_query,
+                page_cursor,
+                skip_count,
+                limit_count,
+            )
+            .await?
+        } else {
+            self.find_filtered_page(
+                initial_match,
+                search_query,
+                page_cursor,
+                prediction_filters,
+                skip_count,
+                limit_count,
+            )
+            .await?
+        };
+
+        let has_more = articles.len() > limit_count as usize;
+        articles.truncate(limit_count as usize);
+
+        let (has_previous, has_next, preceding_count) = match page_cursor {
+            Some(page_cursor) => match page_cursor.direction {
+                CursorDirection::Next => (true, has_more, preceding_count),
+                CursorDirection::Prev => {
+                    articles.reverse();
+                    (
+                        has_more,
+                        true,
+                        preceding_count.saturating_sub(articles.len() as u64),
+                    )
+                }
+            },
+            _none => (skip_count > 0, has_more, skip_count),
+        };
+
+        // Cursors follow the `published_at` order, which relevance-ranked results do not
+        let (prev_cursor, next_cursor) = match search_query {
+            Some(_) => (None, None),
+            _none => page_cursors(
+                &articles,
+                |article| article.id.map(|id| (article.published_at, id)),
+                has_previous,
+                has_next,
+            ),
+        };
+
+        let current_page_count = articles.len();
+        let page = (preceding_count / limit_count as u64) + 1;
+        let total_pages = total_count.div_ceil(limit_count as u64);
+
+        let log_message = if !prediction_filters.is_empty() {
+            format!(
+                "Retrieved {} articles matching {} prediction filters and all predictions from database (page {} of {})",
+                current_page_count,
+                prediction_filters.len(),
+                page,
+                total_pages
+            )
+        } else {
+            format!(
+                "Retrieved {} articles with all predictions from database (page {} of {})",
+                current_page_count, page, total_pages
+            )
+        };
+        info!("{}", log_message);
+
+        Ok(PaginatedArticles {
+            articles,
+            total_count,
+            current_page_count,
+            page,
+            per_page: limit_count,
+            total_pages,
+            next_cursor,
+            prev_cursor,
+        })
+    }
+
+    async fn upsert_by_url_hash(
+        &self,
+        url_hash: &str,
+        article: &ArticleDocument,
+        published_at_estimated: bool,
+    ) -> Result<ArticleUpsert, mongodb::error::Error> {
+        let mut fields = mongodb::bson::to_document(article)?;
+        fields.remove("_id");
+        fields.remove("created_at");
+        fields.insert("url_hash", url_hash);
+
+        let new_id = ObjectId::new();
+        let mut on_insert = doc! {
+            "_id": new_id,
+            "created_at": mongodb::bson::DateTime::from_chrono(article.created_at)
+        };
+        if published_at_estimated {
+            fields.remove("published_at");
+            on_insert.insert(
+                "published_at",
+                mongodb::bson::DateTime::from_chrono(article.published_at),
+            );
+        }
+
+        // The server retries an upsert that loses a race on the unique index,
+        // so concurrent ingestions of one URL still end up as one article
+        let upserted = self
+            .collection
+            .find_one_and_update(
+                doc! { "url_hash": url_hash },
+                doc! {
+                    "$set": fields,
+                    "$setOnInsert": on_insert,
+                },
+            )
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .await?;
+
+        let id = upserted.and_then(|article| article.id).unwrap_or(new_id);
+        let created = id == new_id;
+
+        info!(
+            "{} article {} for URL hash {}",
+            if created { "Inserted" } else { "Updated" },
+            id,
+            url_hash
+        );
+
+        Ok(ArticleUpsert { id, created })
     }
 }
@@ -1,25 +1,332 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use log::info;
-use mongodb::Collection;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::{IndexOptions, ReturnDocument};
+use mongodb::{ClientSession, Collection};
 
+use crate::database::indexes::IndexRegistry;
 use crate::database::mongo_client::DatabaseClient;
 
-use super::models::deployment_repository_models::DeploymentDocument;
+use super::models::deployment_repository_models::{
+    ActiveDeploymentDocument, DeploymentDocument, DeploymentHistoryDocument, TrafficSplitUpdate,
+};
+use super::models::predictor_repository_models::PredictorDocument;
+
+#[async_trait]
+pub trait DeploymentRepository: Send + Sync {
+    async fn list_deployments(&self) -> Result<Vec<DeploymentDocument>, mongodb::error::Error>;
+
+    async fn find_by_prediction_type(
+        &self,
+        prediction_type: &str,
+    ) -> Result<Option<DeploymentDocument>, mongodb::error::Error>;
+
+    async fn list_history(
+        &self,
+        prediction_type: &str,
+        limit: Option<i64>,
+        skip: Option<u64>,
+    ) -> Result<Vec<DeploymentHistoryDocument>, mongodb::error::Error>;
+
+    /// Atomically replaces the active deployments of a prediction type,
+    /// creating the deployment if needed, sets the traffic of its predictors
+    /// (resetting the others to 0%) and records the change in the history.
+    /// Runs in a transaction, so MongoDB must run as a replica set.
+    async fn apply_traffic_split(
+        &self,
+        update: &TrafficSplitUpdate,
+    ) -> Result<(), mongodb::error::Error>;
+}
 
 #[derive(Clone)]
-pub struct DeploymentRepository {
+pub struct MongoDeploymentRepository {
     collection: Collection<DeploymentDocument>,
+    history_collection: Collection<DeploymentHistoryDocument>,
+    predictor_collection: Collection<PredictorDocument>,
 }
 
-impl DeploymentRepository {
-    pub fn new(db_client: &DatabaseClient, collection_name: &str) -> Self {
+impl MongoDeploymentRepository {
+    pub fn new(
+        db_client: &DatabaseClient,
+        collection_name: &str,
+        history_collection_name: &str,
+        predictor_collection_name: &str,
+    ) -> Self {
         let collection: Collection<DeploymentDocument> =
             db_client.get_database().collection(collection_name);
+        let history_collection: Collection<DeploymentHistoryDocument> =
+            db_client.get_database().collection(history_collection_name);
+        let predictor_collection: Collection<PredictorDocument> = db_client
+            .get_database()
+            .collection(predictor_collection_name);
 
         info!(
-            "Created DeploymentRepository for collection: {}",
-            collection_name
+            "Created MongoDeploymentRepository for collections: {}, {}, {}",
+            collection_name, history_collection_name, predictor_collection_name
         );
 
-        Self { collection }
+        Self {
+            collection,
+            history_collection,
+            predictor_collection,
+        }
+    }
+
+    /// Deployments are upserted by prediction type, which must be unique.
+    /// Predictor indexes are declared by `MongoPredictorRepository`.
+    pub fn register_indexes(
+        registry: &mut IndexRegistry,
+        collection_name: &str,
+        history_collection_name: &str,
+    ) {
+        registry.add(
+            collection_name,
+            "deployments_prediction_type",
+            doc! { "prediction_type": 1 },
+            IndexOptions::builder().unique(true).build(),
+        );
+        registry.add(
+            history_collection_name,
+            "deployment_history_prediction_type_created_at",
+            doc! { "prediction_type": 1, "created_at": -1, "_id": -1 },
+            IndexOptions::default(),
+        );
+    }
+
+    /// Body of the `apply_traffic_split` transaction.
+    async fn apply_in_transaction(
+        &self,
+        session: &mut ClientSession,
+        update: &TrafficSplitUpdate,
+    ) -> Result<(), mongodb::error::Error> {
+        let previous = self
+            .replace_active_deployments(
+                session,
+                &update.prediction_type,
+                &update.active_deployments,
+                update.updated_at,
+            )
+            .await?;
+
+        self.update_traffic_percentages(
+            session,
+            &update.prediction_type,
+            &update.traffic_percentages,
+            update.updated_at,
+        )
+        .await?;
+
+        let history_entry = DeploymentHistoryDocument {
+            id: None,
+            prediction_type: update.prediction_type.clone(),
+            previous_deployments: previous
+                .map(|deployment| deployment.active_deployments)
+                .unwrap_or_default(),
+            active_deployments: update.active_deployments.clone(),
+            changed_by: update.changed_by.clone(),
+            reason: update.reason.clone(),
+            created_at: update.updated_at,
+        };
+
+        self.insert_history(session, &history_entry).await
+    }
+
+    /// Replaces the active deployments of a prediction type, creating the
+    /// deployment if needed, and returns the deployment as it was before.
+    async fn replace_active_deployments(
+        &self,
+        session: &mut ClientSession,
+        prediction_type: &str,
+        active_deployments: &[ActiveDeploymentDocument],
+        now: DateTime<Utc>,
+    ) -> Result<Option<DeploymentDocument>, mongodb::error::Error> {
+        let active_deployments = mongodb::bson::to_bson(active_deployments)?;
+
+        let previous = self
+            .collection
+            .find_one_and_update(
+                doc! { "prediction_type": prediction_type },
+                doc! {
+                    "$set": {
+                        "active_deployments": active_deployments,
+                        "updated_at": now
+                    },
+                    "$setOnInsert": {
+                        "created_at": now
+                    }
+                },
+            )
+            .upsert(true)
+            .return_document(ReturnDocument::Before)
+            .session(&mut *session)
+            .await?;
+
+        info!(
+            "Replaced active deployments for prediction type '{}'",
+            prediction_type
+        );
+
+        Ok(previous)
+    }
+
+    /// Sets the traffic of the given predictors and resets every other
+    /// predictor of the same prediction type to 0%.
+    async fn update_traffic_percentages(
+        &self,
+        session: &mut ClientSession,
+        prediction_type: &str,
+        traffic_percentages: &[(ObjectId, i32)],
+        now: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error> {
+        self.predictor_collection
+            .update_many(
+                doc! { "prediction_type": prediction_type },
+                doc! { "$set": { "traffic_percentage": 0, "updated_at": now } },
+            )
+            .session(&mut *session)
+            .await?;
+
+        for (predictor_id, traffic_percentage) in traffic_percentages {
+            self.predictor_collection
+                .update_one(
+                    doc! { "_id": predictor_id, "prediction_type": prediction_type },
+                    doc! { "$set": { "traffic_percentage": traffic_percentage, "updated_at": now } },
+                )
+                .session(&mut *session)
+                .await?;
+        }
+
+        info!(
+            "Updated traffic of {} predictors for prediction type '{}'",
+            traffic_percentages.len(),
+            prediction_type
+        );
+
+        Ok(())
+    }
+
+    async fn insert_history(
+        &self,
+        session: &mut ClientSession,
+        entry: &DeploymentHistoryDocument,
+    ) -> Result<(), mongodb::error::Error> {
+        self.history_collection
+            .insert_one(entry)
+            .session(&mut *session)
+            .await?;
+
+        info!(
+            "Recorded deployment change for prediction type '{}' by '{}'",
+            entry.prediction_type, entry.changed_by
+        );
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl DeploymentRepository for MongoDeploymentRepository {
+    async fn list_deployments(&self) -> Result<Vec<DeploymentDocument>, mongodb::error::Error> {
+        let mut options = mongodb::options::FindOptions::default();
+        options.sort = Some(doc! { "prediction_type": 1 });
+
+        let mut cursor = self
+            .collection
+            .find(doc! {})
+            .with_options(Some(options))
+            .await?;
+
+        let mut deployments = Vec::new();
+
+        while cursor.advance().await? {
+            match cursor.deserialize_current() {
+                Ok(deployment) => deployments.push(deployment),
+                Err(e) => {
+                    log::error!("Failed to deserialize deployment: {}", e);
+                    return Err(e);
+                }
+            }
+        }
+
+        info!("Retrieved {} deployments from database", deployments.len());
+
+        Ok(deployments)
+    }
+
+    async fn find_by_prediction_type(
+        &self,
+        prediction_type: &str,
+    ) -> Result<Option<DeploymentDocument>, mongodb::error::Error> {
+        let filter = doc! { "prediction_type": prediction_type };
+
+        match self.collection.find_one(filter).await? {
+            Some(deployment) => {
+                info!("Found deployment for prediction type '{}'", prediction_type);
+                Ok(Some(deployment))
+            }
+            _none => {
+                info!(
+                    "No deployment found for prediction type '{}'",
+                    prediction_type
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    async fn list_history(
+        &self,
+        prediction_type: &str,
+        limit: Option<i64>,
+        skip: Option<u64>,
+    ) -> Result<Vec<DeploymentHistoryDocument>, mongodb::error::Error> {
+        let mut options = mongodb::options::FindOptions::default();
+
+        options.skip = Some(skip.unwrap_or(0));
+        options.limit = Some(limit.unwrap_or(20));
+        options.sort = Some(doc! { "created_at": -1, "_id": -1 });
+
+        let mut cursor = self
+            .history_collection
+            .find(doc! { "prediction_type": prediction_type })
+            .with_options(Some(options))
+            .await?;
+
+        let mut history = Vec::new();
+
+        while cursor.advance().await? {
+            match cursor.deserialize_current() {
+                Ok(entry) => history.push(entry),
+                Err(e) => {
+                    log::error!("Failed to deserialize deployment history entry: {}", e);
+                    return Err(e);
+                }
+            }
+        }
+
+        info!(
+            "Retrieved {} deployment history entries for prediction type '{}'",
+            history.len(),
+            prediction_type
+        );
+
+        Ok(history)
+    }
+
+    async fn apply_traffic_split(
+        &self,
+        update: &TrafficSplitUpdate,
+    ) -> Result<(), mongodb::error::Error> {
+        // The driver retries the whole transaction on transient errors, such
+        // as write conflicts between concurrent changes, and the commit when
+        // its outcome is unknown
+        let mut session = self.collection.client().start_session().await?;
+        session
+            .start_transaction()
+            .and_run((self, update), |session, (repository, update)| {
+                Box::pin(async move { repository.apply_in_transaction(session, update).await })
+            })
+            .await
     }
 }
@@ -0,0 +1,180 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::info;
+use mongodb::Collection;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::IndexOptions;
+
+use crate::database::indexes::IndexRegistry;
+use crate::database::mongo_client::DatabaseClient;
+
+use super::models::feed_source_repository_models::{FeedFetchOutcome, FeedSourceDocument};
+
+/// Name of the unique index on `url`, which rejects a feed registered twice.
+pub const URL_INDEX_NAME: &str = "feed_sources_url";
+
+#[async_trait]
+pub trait FeedSourceRepository: Send + Sync {
+    async fn insert(
+        &self,
+        feed_source: &FeedSourceDocument,
+    ) -> Result<ObjectId, mongodb::error::Error>;
+
+    async fn find_by_url(
+        &self,
+        url: &str,
+    ) -> Result<Option<FeedSourceDocument>, mongodb::error::Error>;
+
+    /// Every feed, oldest first, or only the enabled ones.
+    async fn list_feed_sources(
+        &self,
+        enabled_only: bool,
+    ) -> Result<Vec<FeedSourceDocument>, mongodb::error::Error>;
+
+    /// Records the outcome of a fetch. The cache validators are replaced when
+    /// the feed was downloaded, and kept otherwise.
+    async fn record_fetch(
+        &self,
+        feed_source_id: ObjectId,
+        outcome: &FeedFetchOutcome,
+        fetched_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error>;
+}
+
+#[derive(Clone)]
+pub struct MongoFeedSourceRepository {
+    collection: Collection<FeedSourceDocument>,
+}
+
+impl MongoFeedSourceRepository {
+    pub fn new(db_client: &DatabaseClient, collection_name: &str) -> Self {
+        let collection: Collection<FeedSourceDocument> =
+            db_client.get_database().collection(collection_name);
+
+        info!(
+            "Created MongoFeedSourceRepository for collection: {}",
+            collection_name
+        );
+
+        Self { collection }
+    }
+
+    pub fn register_indexes(registry: &mut IndexRegistry, collection_name: &str) {
+        registry.add(
+            collection_name,
+            URL_INDEX_NAME,
+            doc! { "url": 1 },
+            IndexOptions::builder().unique(true).build(),
+        );
+    }
+}
+
+#[async_trait]
+impl FeedSourceRepository for MongoFeedSourceRepository {
+    async fn insert(
+        &self,
+        feed_source: &FeedSourceDocument,
+    ) -> Result<ObjectId, mongodb::error::Error> {
+        let result = self.collection.insert_one(feed_source).await?;
+
+        let feed_source_id = result.inserted_id.as_object_id().unwrap_or_default();
+
+        info!(
+            "Created feed source {} for {}",
+            feed_source_id, feed_source.url
+        );
+
+        Ok(feed_source_id)
+    }
+
+    async fn find_by_url(
+        &self,
+        url: &str,
+    ) -> Result<Option<FeedSourceDocument>, mongodb::error::Error> {
+        self.collection.find_one(doc! { "url": url }).await
+    }
+
+    async fn list_feed_sources(
+        &self,
+        enabled_only: bool,
+    ) -> Result<Vec<FeedSourceDocument>, mongodb::error::Error> {
+        let filter = if enabled_only {
+            doc! { "enabled": true }
+        } else {
+            doc! {}
+        };
+
+        let mut options = mongodb::options::FindOptions::default();
+        options.sort = Some(doc! { "created_at": 1, "_id": 1 });
+
+        let mut cursor = self
+            .collection
+            .find(filter)
+            .with_options(Some(options))
+            .await?;
+
+        let mut feed_sources = Vec::new();
+
+        while cursor.advance().await? {
+            match cursor.deserialize_current() {
+                Ok(feed_source) => feed_sources.push(feed_source),
+                Err(e) => {
+                    log::error!("Failed to deserialize feed source: {}", e);
+                    return Err(e);
+                }
+            }
+        }
+
+        info!(
+            "Retrieved {} feed sources from database",
+            feed_sources.len()
+        );
+
+        Ok(feed_sources)
+    }
+
+    async fn record_fetch(
+        &self,
+        feed_source_id: ObjectId,
+        outcome: &FeedFetchOutcome,
+        fetched_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error> {
+        let fetched_at = mongodb::bson::DateTime::from_chrono(fetched_at);
+
+        let update = match outcome {
+            FeedFetchOutcome::Fetched {
+                etag,
+                last_modified,
+            } => doc! {
+                "$set": {
+                    "etag": etag.as_deref(),
+                    "last_modified": last_modified.as_deref(),
+                    "last_fetched_at": fetched_at,
+                    "last_success_at": fetched_at,
+                    "consecutive_failures": 0
+                }
+            },
+            FeedFetchOutcome::NotModified => doc! {
+                "$set": {
+                    "last_fetched_at": fetched_at,
+                    "last_success_at": fetched_at,
+                    "consecutive_failures": 0
+                }
+            },
+            FeedFetchOutcome::Failed(message) => doc! {
+                "$set": {
+                    "last_fetched_at": fetched_at,
+                    "last_error": message,
+                    "last_error_at": fetched_at
+                },
+                "$inc": { "consecutive_failures": 1 }
+            },
+        };
+
+        self.collection
+            .update_one(doc! { "_id": feed_source_id }, update)
+            .await?;
+
+        Ok(())
+    }
+}
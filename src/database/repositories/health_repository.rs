@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use log::info;
+
+use crate::database::mongo_client::DatabaseClient;
+
+#[async_trait]
+pub trait HealthRepository: Send + Sync {
+    async fn ping(&self) -> Result<(), mongodb::error::Error>;
+
+    /// Lists the index names of a collection, or `None` if it does not exist.
+    async fn list_index_names(
+        &self,
+        collection_name: &str,
+    ) -> Result<Option<Vec<String>>, mongodb::error::Error>;
+}
+
+#[derive(Clone)]
+pub struct MongoHealthRepository {
+    db_client: DatabaseClient,
+}
+
+impl MongoHealthRepository {
+    pub fn new(db_client: &DatabaseClient) -> Self {
+        info!("Created MongoHealthRepository");
+
+        Self {
+            db_client: db_client.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl HealthRepository for MongoHealthRepository {
+    async fn ping(&self) -> Result<(), mongodb::error::Error> {
+        self.db_client.ping().await
+    }
+
+    async fn list_index_names(
+        &self,
+        collection_name: &str,
+    ) -> Result<Option<Vec<String>>, mongodb::error::Error> {
+        self.db_client.list_index_names(collection_name).await
+    }
+}
@@ -0,0 +1,1325 @@
+//! In-memory implementation of every repository trait, so that services and
+//! handlers can be exercised without a MongoDB server. It follows the query
+//! semantics of the Mongo repositories closely enough for tests, not more.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::error::{ErrorKind, WriteError, WriteFailure};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::api_key_repository::ApiKeyRepository;
+use super::article_prediction_repository::ArticlePredictionsRepository;
+use super::article_repository::ArticleRepository;
+use super::deployment_repository::DeploymentRepository;
+use super::feed_source_repository::{FeedSourceRepository, URL_INDEX_NAME};
+use super::health_repository::HealthRepository;
+use super::lease_repository::LeaseRepository;
+use super::metrics_repository::{MetricsRepository, window_start};
+use super::models::api_key_repository_models::ApiKeyDocument;
+use super::models::article_prediction_repository_models::ArticlePredictionsDocument;
+use super::models::article_repository_models::{
+    ArticleDocument, ArticleUpsert, PaginatedArticles, PredictionDocument, PredictionFilter,
+};
+use super::models::deployment_repository_models::{
+    ActiveDeploymentDocument, DeploymentDocument, DeploymentHistoryDocument, TrafficSplitUpdate,
+};
+use super::models::feed_source_repository_models::{FeedFetchOutcome, FeedSourceDocument};
+use super::models::metrics_repository_models::{
+    MetricBinsAggregation, MetricInterval, MetricIntervalUnit, MetricQuantile,
+    MetricSummaryAggregation, MetricTimeseries, MetricTimeseriesBucket, MetricsDocument,
+    PaginatedMetrics,
+};
+use super::models::pagination_models::{CursorDirection, PageCursor, page_cursors};
+use super::models::predictor_repository_models::PredictorDocument;
+use super::models::rollout_repository_models::{RolloutDocument, RolloutStatus};
+use super::predictors_repository::PredictorRepository;
+use super::rollout_repository::{ACTIVE_ROLLOUT_INDEX_NAME, RolloutRepository};
+use crate::error::DUPLICATE_KEY_CODE;
+
+#[derive(Default)]
+struct Tables {
+    api_keys: Vec<ApiKeyDocument>,
+    articles: Vec<ArticleDocument>,
+    article_predictions: Vec<ArticlePredictionsDocument>,
+    deployments: Vec<DeploymentDocument>,
+    deployment_history: Vec<DeploymentHistoryDocument>,
+    feed_sources: Vec<FeedSourceDocument>,
+    /// Expiry of each lease by name.
+    leases: HashMap<String, DateTime<Utc>>,
+    metrics: Vec<MetricsDocument>,
+    predictors: Vec<PredictorDocument>,
+    rollouts: Vec<RolloutDocument>,
+    /// Index names of each existing collection, as seen by health checks.
+    collections: HashMap<String, Vec<String>>,
+}
+
+/// Shared state behind all in-memory repositories. Clones share the same
+/// tables, so a test can seed data through one handle and read it back
+/// through the services.
+#[derive(Clone, Default)]
+pub struct InMemoryDatabase {
+    tables: Arc<RwLock<Tables>>,
+    unavailable: Arc<AtomicBool>,
+    api_key_lookups: Arc<AtomicUsize>,
+}
+
+impl InMemoryDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes health checks and traffic split changes fail as if the server
+    /// could not be reached.
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.unavailable.store(unavailable, Ordering::SeqCst);
+    }
+
+    pub fn create_collection(&self, name: &str, index_names: &[&str]) {
+        self.write().collections.insert(
+            name.to_string(),
+            index_names.iter().map(|name| name.to_string()).collect(),
+        );
+    }
+
+    pub fn insert_article(&self, mut article: ArticleDocument) -> ObjectId {
+        let id = *article.id.get_or_insert_with(ObjectId::new);
+        self.write().articles.push(article);
+        id
+    }
+
+    pub fn insert_article_prediction(&self, mut prediction: ArticlePredictionsDocument) {
+        prediction.id.get_or_insert_with(ObjectId::new);
+        self.write().article_predictions.push(prediction);
+    }
+
+    pub fn insert_predictor(&self, mut predictor: PredictorDocument) -> ObjectId {
+        let id = *predictor.id.get_or_insert_with(ObjectId::new);
+        self.write().predictors.push(predictor);
+        id
+    }
+
+    pub fn insert_deployment(&self, mut deployment: DeploymentDocument) {
+        deployment.id.get_or_insert_with(ObjectId::new);
+        self.write().deployments.push(deployment);
+    }
+
+    pub fn insert_metric(&self, mut metric: MetricsDocument) -> ObjectId {
+        let id = *metric.id.get_or_insert_with(ObjectId::new);
+        self.write().metrics.push(metric);
+        id
+    }
+
+    /// Removes a predictor, returning it so that it can be inserted back.
+    pub fn remove_predictor(&self, predictor_id: ObjectId) -> Option<PredictorDocument> {
+        let mut tables = self.write();
+        let index = tables
+            .predictors
+            .iter()
+            .position(|predictor| predictor.id == Some(predictor_id))?;
+        Some(tables.predictors.remove(index))
+    }
+
+    /// Makes the next step of every rollout due now.
+    pub fn make_rollouts_due(&self) {
+        for rollout in self.write().rollouts.iter_mut() {
+            rollout.next_step_at = Utc::now();
+        }
+    }
+
+    /// API keys looked up by hash so far.
+    pub fn api_key_lookups(&self) -> usize {
+        self.api_key_lookups.load(Ordering::SeqCst)
+    }
+
+    pub fn predictors(&self) -> Vec<PredictorDocument> {
+        self.read().predictors.clone()
+    }
+
+    pub fn metrics(&self) -> Vec<MetricsDocument> {
+        self.read().metrics.clone()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Tables> {
+        self.tables.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Tables> {
+        self.tables.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn matching_metrics(
+        &self,
+        metric_name: &str,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        start_time: DateTime<Utc>,
+    ) -> Vec<MetricsDocument> {
+        self.read()
+            .metrics
+            .iter()
+            .filter(|metric| metric.created_at >= start_time)
+            .filter(|metric| {
+                matches_metric(metric, metric_name, prediction_type, predictor_version)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+fn matches_metric(
+    metric: &MetricsDocument,
+    metric_name: &str,
+    prediction_type: Option<&str>,
+    predictor_version: Option<&str>,
+) -> bool {
+    let tag_matches = |tag: &str, expected: Option<&str>| {
+        expected.is_none_or(|expected| metric.tags.get(tag).map(String::as_str) == Some(expected))
+    };
+
+    metric.metric_name == metric_name
+        && tag_matches("prediction_type", prediction_type)
+        && tag_matches("predictor_version", predictor_version)
+}
+
+/// The error MongoDB returns for a write rejected by the unique index `index_name`.
+fn duplicate_key_error(index_name: &str) -> mongodb::error::Error {
+    let write_error: WriteError = mongodb::bson::from_document(doc! {
+        "code": DUPLICATE_KEY_CODE,
+        "errmsg": format!("E11000 duplicate key error index: {}", index_name),
+    })
+    .unwrap();
+
+    ErrorKind::Write(WriteFailure::WriteError(write_error)).into()
+}
+
+/// Sort key of cursor-paginated listings. BSON dates only keep milliseconds.
+fn page_key(sort_value: DateTime<Utc>, id: Option<ObjectId>) -> (i64, ObjectId) {
+    (sort_value.timestamp_millis(), id.unwrap_or_default())
+}
+
+fn cursor_key(page_cursor: &PageCursor) -> (i64, ObjectId) {
+    page_key(page_cursor.sort_value, Some(page_cursor.id))
+}
+
+struct Page<T> {
+    items: Vec<T>,
+    has_previous: bool,
+    has_next: bool,
+    preceding_count: u64,
+}
+
+/// Pages through `items`, which must be sorted by descending `key`, the way
+/// the Mongo repositories do with `skip` or a `PageCursor`.
+fn paginate<T>(
+    items: Vec<T>,
+    key: impl Fn(&T) -> (i64, ObjectId),
+    limit: i64,
+    skip: u64,
+    page_cursor: Option<&PageCursor>,
+) -> Page<T> {
+    let limit = limit as usize;
+
+    match page_cursor {
+        Some(page_cursor) => {
+            let position = cursor_key(page_cursor);
+            match page_cursor.direction {
+                CursorDirection::Next => {
+                    let preceding_count =
+                        items.iter().filter(|item| key(item) >= position).count() as u64;
+                    let mut page: Vec<T> = items
+                        .into_iter()
+                        .filter(|item| key(item) < position)
+                        .take(limit + 1)
+                        .collect();
+                    let has_next = page.len() > limit;
+                    page.truncate(limit);
+
+                    Page {
+                        items: page,
+                        has_previous: true,
+                        has_next,
+                        preceding_count,
+                    }
+                }
+                CursorDirection::Prev => {
+                    let mut preceding: Vec<T> = items
+                        .into_iter()
+                        .filter(|item| key(item) > position)
+                        .collect();
+                    let preceding_count = preceding.len() as u64;
+                    preceding.reverse();
+                    let mut page: Vec<T> = preceding.into_iter().take(limit + 1).collect();
+                    let has_previous = page.len() > limit;
+                    page.truncate(limit);
+                    page.reverse();
+
+                    Page {
+                        preceding_count: preceding_count.saturating_sub(page.len() as u64),
+                        items: page,
+                        has_previous,
+                        has_next: true,
+                    }
+                }
+            }
+        }
+        _none => {
+            let mut page: Vec<T> = items
+                .into_iter()
+                .skip(skip as usize)
+                .take(limit + 1)
+                .collect();
+            let has_next = page.len() > limit;
+            page.truncate(limit);
+
+            Page {
+                items: page,
+                has_previous: skip > 0,
+                has_next,
+                preceding_count: skip,
+            }
+        }
+    }
+}
+
+/// Weighted term frequency over the fields of the articles text index.
+fn text_score(article: &ArticleDocument, search_query: &str) -> f64 {
+    let fields = [
+        (article.title.as_deref(), 10.0),
+        (article.description.as_deref(), 5.0),
+        (article.content.as_deref(), 1.0),
+    ];
+
+    search_query
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .map(|term| {
+            fields
+                .iter()
+                .filter_map(|(text, weight)| text.map(|text| (text.to_lowercase(), weight)))
+                .map(|(text, weight)| text.matches(term.as_str()).count() as f64 * weight)
+                .sum::<f64>()
+        })
+        .sum()
+}
+
+fn matches_prediction_filters(
+    predictions: Option<&HashMap<String, PredictionDocument>>,
+    prediction_filters: &[PredictionFilter],
+) -> bool {
+    prediction_filters.iter().all(|filter| {
+        let prediction =
+            predictions.and_then(|predictions| predictions.get(&filter.prediction_type));
+
+        let value_matches = filter.values.is_empty()
+            || prediction.is_some_and(|prediction| {
+                prediction
+                    .prediction_value
+                    .as_str()
+                    .is_some_and(|value| filter.values.iter().any(|v| v == value))
+            });
+        let confidence_matches = filter.min_confidence.is_none_or(|min_confidence| {
+            prediction
+                .and_then(|prediction| prediction.prediction_confidence)
+                .is_some_and(|confidence| confidence >= min_confidence)
+        });
+
+        value_matches && confidence_matches
+    })
+}
+
+/// Nearest-rank percentile of values sorted in ascending order.
+fn percentile(sorted_values: &[f64], quantile: f64) -> f64 {
+    let rank = (quantile * sorted_values.len() as f64).ceil() as usize;
+    sorted_values[rank.clamp(1, sorted_values.len()) - 1]
+}
+
+/// Start of the `$dateTrunc` bucket holding `date`, whose bins are counted
+/// from 2000-01-01 (the first Sunday after it for weeks).
+fn bucket_start(date: DateTime<Utc>, interval: MetricInterval) -> DateTime<Utc> {
+    let reference = match interval.unit {
+        MetricIntervalUnit::Week => Utc.with_ymd_and_hms(2000, 1, 2, 0, 0, 0),
+        _ => Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0),
+    }
+    .unwrap();
+
+    let bin_millis = interval.duration().num_milliseconds();
+    let offset = (date - reference).num_milliseconds().div_euclid(bin_millis);
+
+    reference + Duration::milliseconds(offset * bin_millis)
+}
+
+#[async_trait]
+impl FeedSourceRepository for InMemoryDatabase {
+    async fn insert(
+        &self,
+        feed_source: &FeedSourceDocument,
+    ) -> Result<ObjectId, mongodb::error::Error> {
+        let mut tables = self.write();
+
+        if tables
+            .feed_sources
+            .iter()
+            .any(|existing| existing.url == feed_source.url)
+        {
+            return Err(duplicate_key_error(URL_INDEX_NAME));
+        }
+
+        let mut feed_source = feed_source.clone();
+        let feed_source_id = *feed_source.id.get_or_insert_with(ObjectId::new);
+        tables.feed_sources.push(feed_source);
+
+        Ok(feed_source_id)
+    }
+
+    async fn find_by_url(
+        &self,
+        url: &str,
+    ) -> Result<Option<FeedSourceDocument>, mongodb::error::Error> {
+        Ok(self
+            .read()
+            .feed_sources
+            .iter()
+            .find(|feed_source| feed_source.url == url)
+            .cloned())
+    }
+
+    async fn list_feed_sources(
+        &self,
+        enabled_only: bool,
+    ) -> Result<Vec<FeedSourceDocument>, mongodb::error::Error> {
+        let mut feed_sources: Vec<FeedSourceDocument> = self
+            .read()
+            .feed_sources
+            .iter()
+            .filter(|feed_source| feed_source.enabled || !enabled_only)
+            .cloned()
+            .collect();
+        feed_sources.sort_by_key(|feed_source| page_key(feed_source.created_at, feed_source.id));
+
+        Ok(feed_sources)
+    }
+
+    async fn record_fetch(
+        &self,
+        feed_source_id: ObjectId,
+        outcome: &FeedFetchOutcome,
+        fetched_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error> {
+        let mut tables = self.write();
+        let Some(feed_source) = tables
+            .feed_sources
+            .iter_mut()
+            .find(|feed_source| feed_source.id == Some(feed_source_id))
+        else {
+            return Ok(());
+        };
+
+        feed_source.last_fetched_at = Some(fetched_at);
+        match outcome {
+            FeedFetchOutcome::Fetched {
+                etag,
+                last_modified,
+            } => {
+                feed_source.etag = etag.clone();
+                feed_source.last_modified = last_modified.clone();
+                feed_source.last_success_at = Some(fetched_at);
+                feed_source.consecutive_failures = 0;
+            }
+            FeedFetchOutcome::NotModified => {
+                feed_source.last_success_at = Some(fetched_at);
+                feed_source.consecutive_failures = 0;
+            }
+            FeedFetchOutcome::Failed(message) => {
+                feed_source.last_error = Some(message.clone());
+                feed_source.last_error_at = Some(fetched_at);
+                feed_source.consecutive_failures += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for InMemoryDatabase {
+    async fn insert(&self, api_key: &ApiKeyDocument) -> Result<ObjectId, mongodb::error::Error> {
+        let mut api_key = api_key.clone();
+        let api_key_id = *api_key.id.get_or_insert_with(ObjectId::new);
+        self.write().api_keys.push(api_key);
+
+        Ok(api_key_id)
+    }
+
+    async fn find_by_hash(
+        &self,
+        key_hash: &str,
+    ) -> Result<Option<ApiKeyDocument>, mongodb::error::Error> {
+        self.api_key_lookups.fetch_add(1, Ordering::SeqCst);
+        Ok(self
+            .read()
+            .api_keys
+            .iter()
+            .find(|api_key| api_key.key_hash == key_hash)
+            .cloned())
+    }
+
+    async fn find_by_id(
+        &self,
+        api_key_id: ObjectId,
+    ) -> Result<Option<ApiKeyDocument>, mongodb::error::Error> {
+        Ok(self
+            .read()
+            .api_keys
+            .iter()
+            .find(|api_key| api_key.id == Some(api_key_id))
+            .cloned())
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<ApiKeyDocument>, mongodb::error::Error> {
+        let mut api_keys = self.read().api_keys.clone();
+        api_keys.sort_by_key(|api_key| std::cmp::Reverse(page_key(api_key.created_at, api_key.id)));
+
+        Ok(api_keys)
+    }
+
+    async fn revoke(
+        &self,
+        api_key_id: ObjectId,
+        revoked_at: DateTime<Utc>,
+    ) -> Result<Option<ApiKeyDocument>, mongodb::error::Error> {
+        let mut tables = self.write();
+
+        Ok(tables
+            .api_keys
+            .iter_mut()
+            .find(|api_key| api_key.id == Some(api_key_id) && api_key.revoked_at.is_none())
+            .map(|api_key| {
+                api_key.revoked_at = Some(revoked_at);
+                api_key.clone()
+            }))
+    }
+}
+
+#[async_trait]
+impl ArticleRepository for InMemoryDatabase {
+    async fn find_by_id(
+        &self,
+        article_id: ObjectId,
+    ) -> Result<Option<ArticleDocument>, mongodb::error::Error> {
+        Ok(self
+            .read()
+            .articles
+            .iter()
+            .find(|article| article.id == Some(article_id))
+            .cloned())
+    }
+
+    async fn list_articles_with_all_predictions(
+        &self,
+        limit: Option<i64>,
+        skip: Option<u64>,
+        page_cursor: Option<&PageCursor>,
+        search_query: Option<&str>,
+        source: Option<&str>,
+        prediction_filters: &[PredictionFilter],
+    ) -> Result<PaginatedArticles, mongodb::error::Error> {
+        let limit_count = limit.unwrap_or(20);
+        let tables = self.read();
+
+        let mut articles: Vec<ArticleDocument> = tables
+            .articles
+            .iter()
+            .filter(|article| {
+                source.is_none_or(|source| {
+                    article.source.id.as_deref() == Some(source) || article.source.name == source
+                })
+            })
+            .cloned()
+            .filter_map(|mut article| {
+                if let Some(search_query) = search_query {
+                    let score = text_score(&article, search_query);
+                    if score == 0.0 {
+                        return None;
+                    }
+                    article.search_score = Some(score);
+                }
+
+                let predictions: HashMap<String, PredictionDocument> = tables
+                    .article_predictions
+                    .iter()
+                    .filter(|prediction| Some(prediction.article_id) == article.id)
+                    .map(|prediction| {
+                        (
+                            prediction.prediction_type.clone(),
+                            PredictionDocument {
+                                prediction_confidence: prediction
+                                    .selected_prediction
+                                    .prediction_confidence,
+                                prediction_value: prediction
+                                    .selected_prediction
+                                    .prediction_value
+                                    .clone(),
+                            },
+                        )
+                    })
+                    .collect();
+                article.sentiment_analysis = predictions.get("sentiment_analysis").cloned();
+                article.predictions = Some(predictions).filter(|p| !p.is_empty());
+
+                matches_prediction_filters(article.predictions.as_ref(), prediction_filters)
+                    .then_some(article)
+            })
+            .collect();
+        drop(tables);
+
+        let key = |article: &ArticleDocument| page_key(article.published_at, article.id);
+        articles.sort_by_key(|article| std::cmp::Reverse(key(article)));
+        if search_query.is_some() && page_cursor.is_none() {
+            articles.sort_by(|a, b| b.search_score.partial_cmp(&a.search_score).unwrap());
+        }
+
+        let total_count = articles.len() as u64;
+        let page = paginate(articles, key, limit_count, skip.unwrap_or(0), page_cursor);
+
+        let (prev_cursor, next_cursor) = match search_query {
+            Some(_) => (None, None),
+            _none => page_cursors(
+                &page.items,
+                |article| article.id.map(|id| (article.published_at, id)),
+                page.has_previous,
+                page.has_next,
+            ),
+        };
+
+        Ok(PaginatedArticles {
+            current_page_count: page.items.len(),
+            articles: page.items,
+            total_count,
+            page: (page.preceding_count / limit_count as u64) + 1,
+            per_page: limit_count,
+            total_pages: total_count.div_ceil(limit_count as u64),
+            next_cursor,
+            prev_cursor,
+        })
+    }
+
+    async fn upsert_by_url_hash(
+        &self,
+        url_hash: &str,
+        article: &ArticleDocument,
+        published_at_estimated: bool,
+    ) -> Result<ArticleUpsert, mongodb::error::Error> {
+        let mut tables = self.write();
+
+        match tables
+            .articles
+            .iter_mut()
+            .find(|existing| existing.url_hash.as_deref() == Some(url_hash))
+        {
+            Some(existing) => {
+                let id = existing.id.unwrap_or_default();
+                *existing = ArticleDocument {
+                    id: existing.id,
+                    url_hash: Some(url_hash.to_string()),
+                    created_at: existing.created_at,
+                    published_at: if published_at_estimated {
+                        existing.published_at
+                    } else {
+                        article.published_at
+                    },
+                    predictions: existing.predictions.take(),
+                    sentiment_analysis: existing.sentiment_analysis.take(),
+                    ..article.clone()
+                };
+                Ok(ArticleUpsert { id, created: false })
+            }
+            _none => {
+                let id = ObjectId::new();
+                tables.articles.push(ArticleDocument {
+                    id: Some(id),
+                    url_hash: Some(url_hash.to_string()),
+                    ..article.clone()
+                });
+                Ok(ArticleUpsert { id, created: true })
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ArticlePredictionsRepository for InMemoryDatabase {
+    async fn find_by_article_id_and_prediction_type(
+        &self,
+        article_id: ObjectId,
+        prediction_type: &str,
+    ) -> Result<Option<ArticlePredictionsDocument>, mongodb::error::Error> {
+        Ok(self
+            .read()
+            .article_predictions
+            .iter()
+            .find(|prediction| {
+                prediction.article_id == article_id && prediction.prediction_type == prediction_type
+            })
+            .cloned())
+    }
+
+    async fn find_by_article_id(
+        &self,
+        article_id: ObjectId,
+    ) -> Result<Vec<ArticlePredictionsDocument>, mongodb::error::Error> {
+        let mut predictions: Vec<ArticlePredictionsDocument> = self
+            .read()
+            .article_predictions
+            .iter()
+            .filter(|prediction| prediction.article_id == article_id)
+            .cloned()
+            .collect();
+        predictions.sort_by(|a, b| a.prediction_type.cmp(&b.prediction_type));
+
+        Ok(predictions)
+    }
+}
+
+#[async_trait]
+impl DeploymentRepository for InMemoryDatabase {
+    async fn list_deployments(&self) -> Result<Vec<DeploymentDocument>, mongodb::error::Error> {
+        let mut deployments = self.read().deployments.clone();
+        deployments.sort_by(|a, b| a.prediction_type.cmp(&b.prediction_type));
+
+        Ok(deployments)
+    }
+
+    async fn find_by_prediction_type(
+        &self,
+        prediction_type: &str,
+    ) -> Result<Option<DeploymentDocument>, mongodb::error::Error> {
+        Ok(self
+            .read()
+            .deployments
+            .iter()
+            .find(|deployment| deployment.prediction_type == prediction_type)
+            .cloned())
+    }
+
+    async fn list_history(
+        &self,
+        prediction_type: &str,
+        limit: Option<i64>,
+        skip: Option<u64>,
+    ) -> Result<Vec<DeploymentHistoryDocument>, mongodb::error::Error> {
+        let mut history: Vec<DeploymentHistoryDocument> = self
+            .read()
+            .deployment_history
+            .iter()
+            .filter(|entry| entry.prediction_type == prediction_type)
+            .cloned()
+            .collect();
+        history.sort_by_key(|entry| std::cmp::Reverse(page_key(entry.created_at, entry.id)));
+
+        Ok(history
+            .into_iter()
+            .skip(skip.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(20) as usize)
+            .collect())
+    }
+
+    async fn apply_traffic_split(
+        &self,
+        update: &TrafficSplitUpdate,
+    ) -> Result<(), mongodb::error::Error> {
+        if self.unavailable.load(Ordering::SeqCst) {
+            return Err(std::io::ErrorKind::ConnectionRefused.into());
+        }
+
+        let mut tables = self.write();
+
+        let previous_deployments: Vec<ActiveDeploymentDocument> = match tables
+            .deployments
+            .iter_mut()
+            .find(|deployment| deployment.prediction_type == update.prediction_type)
+        {
+            Some(deployment) => {
+                deployment.updated_at = update.updated_at;
+                std::mem::replace(
+                    &mut deployment.active_deployments,
+                    update.active_deployments.clone(),
+                )
+            }
+            _none => {
+                tables.deployments.push(DeploymentDocument {
+                    id: Some(ObjectId::new()),
+                    prediction_type: update.prediction_type.clone(),
+                    active_deployments: update.active_deployments.clone(),
+                    created_at: update.updated_at,
+                    updated_at: update.updated_at,
+                });
+                Vec::new()
+            }
+        };
+
+        let traffic_percentages: HashMap<ObjectId, i32> =
+            update.traffic_percentages.iter().copied().collect();
+        for predictor in tables
+            .predictors
+            .iter_mut()
+            .filter(|predictor| predictor.prediction_type == update.prediction_type)
+        {
+            predictor.traffic_percentage = predictor
+                .id
+                .and_then(|id| traffic_percentages.get(&id).copied())
+                .unwrap_or(0);
+            predictor.updated_at = update.updated_at;
+        }
+
+        tables.deployment_history.push(DeploymentHistoryDocument {
+            id: Some(ObjectId::new()),
+            prediction_type: update.prediction_type.clone(),
+            previous_deployments,
+            active_deployments: update.active_deployments.clone(),
+            changed_by: update.changed_by.clone(),
+            reason: update.reason.clone(),
+            created_at: update.updated_at,
+        });
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HealthRepository for InMemoryDatabase {
+    async fn ping(&self) -> Result<(), mongodb::error::Error> {
+        if self.unavailable.load(Ordering::SeqCst) {
+            return Err(std::io::ErrorKind::ConnectionRefused.into());
+        }
+
+        Ok(())
+    }
+
+    async fn list_index_names(
+        &self,
+        collection_name: &str,
+    ) -> Result<Option<Vec<String>>, mongodb::error::Error> {
+        Ok(self.read().collections.get(collection_name).cloned())
+    }
+}
+
+#[async_trait]
+impl LeaseRepository for InMemoryDatabase {
+    async fn try_acquire(
+        &self,
+        name: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<bool, mongodb::error::Error> {
+        let mut tables = self.write();
+
+        if tables
+            .leases
+            .get(name)
+            .is_some_and(|expires_at| *expires_at > now)
+        {
+            return Ok(false);
+        }
+
+        tables.leases.insert(name.to_string(), now + lease);
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl MetricsRepository for InMemoryDatabase {
+    async fn list_metrics(
+        &self,
+        metric_name: &str,
+        limit: Option<i64>,
+        skip: Option<u64>,
+        page_cursor: Option<&PageCursor>,
+        prediction_type: Option<String>,
+        predictor_version: Option<String>,
+    ) -> Result<PaginatedMetrics, mongodb::error::Error> {
+        let mut metrics: Vec<MetricsDocument> = self
+            .read()
+            .metrics
+            .iter()
+            .filter(|metric| {
+                matches_metric(
+                    metric,
+                    metric_name,
+                    prediction_type.as_deref(),
+                    predictor_version.as_deref(),
+                )
+            })
+            .cloned()
+            .collect();
+
+        let key = |metric: &MetricsDocument| page_key(metric.created_at, metric.id);
+        metrics.sort_by_key(|metric| std::cmp::Reverse(key(metric)));
+
+        let page = paginate(
+            metrics,
+            key,
+            limit.unwrap_or(20),
+            skip.unwrap_or(0),
+            page_cursor,
+        );
+        let (prev_cursor, next_cursor) = page_cursors(
+            &page.items,
+            |metric| metric.id.map(|id| (metric.created_at, id)),
+            page.has_previous,
+            page.has_next,
+        );
+
+        Ok(PaginatedMetrics {
+            metrics: page.items,
+            next_cursor,
+            prev_cursor,
+        })
+    }
+
+    async fn insert_metrics(
+        &self,
+        metrics: &[MetricsDocument],
+    ) -> Result<Vec<(usize, String)>, mongodb::error::Error> {
+        let mut tables = self.write();
+        tables
+            .metrics
+            .extend(metrics.iter().cloned().map(|mut metric| {
+                metric.id.get_or_insert_with(ObjectId::new);
+                metric
+            }));
+
+        Ok(Vec::new())
+    }
+
+    async fn list_metric_values(
+        &self,
+        metric_name: &str,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        num_days: Option<i32>,
+        max_values: i64,
+    ) -> Result<Vec<f64>, mongodb::error::Error> {
+        let mut metrics = self.matching_metrics(
+            metric_name,
+            prediction_type,
+            predictor_version,
+            window_start(num_days),
+        );
+        metrics.sort_by_key(|metric| std::cmp::Reverse(metric.created_at));
+
+        Ok(metrics
+            .iter()
+            .take(max_values as usize)
+            .map(|metric| metric.metric_value)
+            .collect())
+    }
+
+    async fn get_metric_summary_aggregation(
+        &self,
+        metric_name: &str,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        start_time: DateTime<Utc>,
+        quantiles: &[f64],
+    ) -> Result<Option<MetricSummaryAggregation>, mongodb::error::Error> {
+        let mut values: Vec<f64> = self
+            .matching_metrics(metric_name, prediction_type, predictor_version, start_time)
+            .iter()
+            .map(|metric| metric.metric_value)
+            .collect();
+
+        if values.is_empty() {
+            return Ok(None);
+        }
+        values.sort_by(f64::total_cmp);
+
+        let count = values.len() as f64;
+        let sum_value: f64 = values.iter().sum();
+        let avg_value = sum_value / count;
+        let variance = values
+            .iter()
+            .map(|value| (value - avg_value).powi(2))
+            .sum::<f64>()
+            / count;
+
+        Ok(Some(MetricSummaryAggregation {
+            avg_value,
+            sum_value,
+            count: values.len() as i64,
+            min_value: values[0],
+            max_value: values[values.len() - 1],
+            std_dev_value: variance.sqrt(),
+            p50_value: percentile(&values, 0.5),
+            p90_value: percentile(&values, 0.9),
+            p95_value: percentile(&values, 0.95),
+            p99_value: percentile(&values, 0.99),
+            quantiles: quantiles
+                .iter()
+                .map(|&quantile| MetricQuantile {
+                    quantile,
+                    value: percentile(&values, quantile),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn get_metric_timeseries_aggregation(
+        &self,
+        metric_name: &str,
+        interval: MetricInterval,
+        group_by: Option<&str>,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        num_days: Option<i32>,
+    ) -> Result<Vec<MetricTimeseries>, mongodb::error::Error> {
+        let mut buckets: Vec<((Option<String>, DateTime<Utc>), Vec<f64>)> = Vec::new();
+
+        for metric in self.matching_metrics(
+            metric_name,
+            prediction_type,
+            predictor_version,
+            window_start(num_days),
+        ) {
+            let key = (
+                group_by.and_then(|tag| metric.tags.get(tag).cloned()),
+                bucket_start(metric.created_at, interval),
+            );
+            match buckets
+                .iter_mut()
+                .find(|(bucket_key, _)| *bucket_key == key)
+            {
+                Some((_, values)) => values.push(metric.metric_value),
+                _none => buckets.push((key, vec![metric.metric_value])),
+            }
+        }
+        buckets.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut series: Vec<MetricTimeseries> = Vec::new();
+        for ((group, bucket_start), values) in buckets {
+            let sum_value: f64 = values.iter().sum();
+            let bucket = MetricTimeseriesBucket {
+                bucket_start,
+                avg_value: sum_value / values.len() as f64,
+                sum_value,
+                count: values.len() as i64,
+                min_value: values.iter().copied().fold(f64::INFINITY, f64::min),
+                max_value: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            };
+
+            match series.last_mut().filter(|last| last.group == group) {
+                Some(last) => last.buckets.push(bucket),
+                _none => series.push(MetricTimeseries {
+                    group,
+                    buckets: vec![bucket],
+                }),
+            }
+        }
+
+        Ok(series)
+    }
+
+    async fn get_metric_bins_aggregation(
+        &self,
+        metric_name: &str,
+        num_bins: i32,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        num_days: Option<i32>,
+    ) -> Result<Vec<MetricBinsAggregation>, mongodb::error::Error> {
+        let values: Vec<f64> = self
+            .matching_metrics(
+                metric_name,
+                prediction_type,
+                predictor_version,
+                window_start(num_days),
+            )
+            .iter()
+            .map(|metric| metric.metric_value)
+            .collect();
+
+        if values.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let min_value = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max_value = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let degenerate = values.len() == 1 || min_value == max_value;
+        let bin_size = (max_value - min_value) / num_bins as f64;
+
+        let mut counts = vec![0i64; num_bins as usize];
+        for value in &values {
+            let bin_index = if degenerate {
+                0
+            } else {
+                (((value - min_value) / bin_size).floor() as usize).min(num_bins as usize - 1)
+            };
+            counts[bin_index] += 1;
+        }
+
+        Ok(counts
+            .into_iter()
+            .enumerate()
+            .map(|(bin_index, count)| {
+                let (bin_start, bin_end) = if degenerate {
+                    (min_value, max_value)
+                } else {
+                    (
+                        min_value + bin_index as f64 * bin_size,
+                        min_value + (bin_index + 1) as f64 * bin_size,
+                    )
+                };
+
+                MetricBinsAggregation {
+                    bin_index: bin_index as i32,
+                    bin_start,
+                    bin_end,
+                    count,
+                }
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl PredictorRepository for InMemoryDatabase {
+    async fn get_prediction_types(&self) -> Result<HashSet<String>, mongodb::error::Error> {
+        Ok(self
+            .read()
+            .predictors
+            .iter()
+            .map(|predictor| predictor.prediction_type.clone())
+            .collect())
+    }
+
+    async fn get_predictor_versions(
+        &self,
+        prediction_type: &str,
+    ) -> Result<HashSet<i32>, mongodb::error::Error> {
+        Ok(self
+            .read()
+            .predictors
+            .iter()
+            .filter(|predictor| predictor.prediction_type == prediction_type)
+            .map(|predictor| predictor.predictor_version)
+            .collect())
+    }
+
+    async fn get_predictors_by_type(
+        &self,
+        prediction_type: &str,
+        min_traffic: Option<i32>,
+    ) -> Result<Vec<PredictorDocument>, mongodb::error::Error> {
+        let mut predictors: Vec<PredictorDocument> = self
+            .read()
+            .predictors
+            .iter()
+            .filter(|predictor| predictor.prediction_type == prediction_type)
+            .filter(|predictor| {
+                min_traffic.is_none_or(|min_traffic| predictor.traffic_percentage >= min_traffic)
+            })
+            .cloned()
+            .collect();
+        predictors.sort_by_key(|predictor| predictor.predictor_version);
+
+        Ok(predictors)
+    }
+
+    async fn find_by_ids(
+        &self,
+        predictor_ids: &[ObjectId],
+    ) -> Result<Vec<PredictorDocument>, mongodb::error::Error> {
+        Ok(self
+            .read()
+            .predictors
+            .iter()
+            .filter(|predictor| predictor.id.is_some_and(|id| predictor_ids.contains(&id)))
+            .cloned()
+            .collect())
+    }
+}
+
+#[async_trait]
+impl RolloutRepository for InMemoryDatabase {
+    async fn insert(&self, rollout: &RolloutDocument) -> Result<ObjectId, mongodb::error::Error> {
+        let mut tables = self.write();
+
+        if rollout.status == RolloutStatus::Active
+            && tables.rollouts.iter().any(|existing| {
+                existing.prediction_type == rollout.prediction_type
+                    && existing.status == RolloutStatus::Active
+            })
+        {
+            return Err(duplicate_key_error(ACTIVE_ROLLOUT_INDEX_NAME));
+        }
+
+        let mut rollout = rollout.clone();
+        let rollout_id = *rollout.id.get_or_insert_with(ObjectId::new);
+        tables.rollouts.push(rollout);
+
+        Ok(rollout_id)
+    }
+
+    async fn find_by_id(
+        &self,
+        rollout_id: ObjectId,
+    ) -> Result<Option<RolloutDocument>, mongodb::error::Error> {
+        Ok(self
+            .read()
+            .rollouts
+            .iter()
+            .find(|rollout| rollout.id == Some(rollout_id))
+            .cloned())
+    }
+
+    async fn find_active_by_prediction_type(
+        &self,
+        prediction_type: &str,
+    ) -> Result<Option<RolloutDocument>, mongodb::error::Error> {
+        Ok(self
+            .read()
+            .rollouts
+            .iter()
+            .find(|rollout| {
+                rollout.prediction_type == prediction_type
+                    && rollout.status == RolloutStatus::Active
+            })
+            .cloned())
+    }
+
+    async fn list_rollouts(
+        &self,
+        prediction_type: Option<&str>,
+        status: Option<RolloutStatus>,
+        limit: Option<i64>,
+        skip: Option<u64>,
+    ) -> Result<Vec<RolloutDocument>, mongodb::error::Error> {
+        let mut rollouts: Vec<RolloutDocument> = self
+            .read()
+            .rollouts
+            .iter()
+            .filter(|rollout| prediction_type.is_none_or(|p| rollout.prediction_type == p))
+            .filter(|rollout| status.is_none_or(|s| rollout.status == s))
+            .cloned()
+            .collect();
+        rollouts.sort_by_key(|rollout| std::cmp::Reverse(page_key(rollout.created_at, rollout.id)));
+
+        Ok(rollouts
+            .into_iter()
+            .skip(skip.unwrap_or(0) as usize)
+            .take(limit.unwrap_or(20) as usize)
+            .collect())
+    }
+
+    async fn claim_due_rollout(
+        &self,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<Option<RolloutDocument>, mongodb::error::Error> {
+        let mut tables = self.write();
+
+        let rollout = tables
+            .rollouts
+            .iter_mut()
+            .filter(|rollout| {
+                rollout.status == RolloutStatus::Active && rollout.next_step_at <= now
+            })
+            .min_by_key(|rollout| rollout.next_step_at);
+
+        Ok(rollout.map(|rollout| {
+            rollout.next_step_at = now + lease;
+            rollout.clone()
+        }))
+    }
+
+    async fn record_step(
+        &self,
+        rollout_id: ObjectId,
+        current_step: usize,
+        baseline_deployments: &[ActiveDeploymentDocument],
+        step_started_at: DateTime<Utc>,
+        next_step_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error> {
+        let mut tables = self.write();
+
+        if let Some(rollout) = tables
+            .rollouts
+            .iter_mut()
+            .find(|rollout| rollout.id == Some(rollout_id))
+        {
+            rollout.current_step = Some(current_step);
+            rollout.baseline_deployments = baseline_deployments.to_vec();
+            rollout.step_started_at = Some(step_started_at);
+            rollout.next_step_at = next_step_at;
+            rollout.updated_at = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn reschedule(
+        &self,
+        rollout_id: ObjectId,
+        retry_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error> {
+        let mut tables = self.write();
+
+        if let Some(rollout) = tables
+            .rollouts
+            .iter_mut()
+            .find(|rollout| rollout.id == Some(rollout_id))
+        {
+            rollout.next_step_at = retry_at;
+            rollout.updated_at = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn record_rollback(
+        &self,
+        rollout_id: ObjectId,
+        rollback_reason: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error> {
+        let mut tables = self.write();
+
+        if let Some(rollout) = tables
+            .rollouts
+            .iter_mut()
+            .find(|rollout| rollout.id == Some(rollout_id))
+        {
+            rollout.rollback_reason = Some(rollback_reason.to_string());
+            rollout.next_step_at = retry_at;
+            rollout.updated_at = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn update_status(
+        &self,
+        rollout_id: ObjectId,
+        expected_status: RolloutStatus,
+        status: RolloutStatus,
+        status_reason: Option<&str>,
+    ) -> Result<bool, mongodb::error::Error> {
+        let mut tables = self.write();
+
+        match tables
+            .rollouts
+            .iter_mut()
+            .find(|rollout| rollout.id == Some(rollout_id) && rollout.status == expected_status)
+        {
+            Some(rollout) => {
+                rollout.status = status;
+                rollout.status_reason = status_reason.map(str::to_string);
+                rollout.updated_at = Utc::now();
+                Ok(true)
+            }
+            _none => Ok(false),
+        }
+    }
+}
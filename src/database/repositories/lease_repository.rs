@@ -0,0 +1,68 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use mongodb::Collection;
+use mongodb::bson::doc;
+
+use crate::database::mongo_client::DatabaseClient;
+use crate::error::is_duplicate_key;
+
+use super::models::lease_repository_models::LeaseDocument;
+
+#[async_trait]
+pub trait LeaseRepository: Send + Sync {
+    /// Takes the lease named `name` until `now + lease` if nobody holds it,
+    /// returning whether it was taken.
+    async fn try_acquire(
+        &self,
+        name: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<bool, mongodb::error::Error>;
+}
+
+#[derive(Clone)]
+pub struct MongoLeaseRepository {
+    collection: Collection<LeaseDocument>,
+}
+
+impl MongoLeaseRepository {
+    pub fn new(db_client: &DatabaseClient, collection_name: &str) -> Self {
+        let collection: Collection<LeaseDocument> =
+            db_client.get_database().collection(collection_name);
+
+        info!(
+            "Created MongoLeaseRepository for collection: {}",
+            collection_name
+        );
+
+        Self { collection }
+    }
+}
+
+#[async_trait]
+impl LeaseRepository for MongoLeaseRepository {
+    async fn try_acquire(
+        &self,
+        name: &str,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<bool, mongodb::error::Error> {
+        // A lease still held does not match, so the upsert inserts a second
+        // document with the same `_id`, which the server rejects
+        let result = self
+            .collection
+            .update_one(
+                doc! { "_id": name, "expires_at": { "$lte": now } },
+                doc! { "$set": { "expires_at": now + lease } },
+            )
+            .upsert(true)
+            .await;
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(e) if is_duplicate_key(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -1,45 +1,150 @@
-use chrono::Utc;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use log::info;
 use mongodb::Collection;
-use mongodb::bson::doc;
+use mongodb::bson::{Document, doc};
+use mongodb::error::{ErrorKind, InsertManyError};
+use mongodb::options::IndexOptions;
 
+use crate::database::indexes::IndexRegistry;
 use crate::database::mongo_client::DatabaseClient;
 use crate::database::repositories::models::metrics_repository_models::MetricBinsAggregation;
 
-use super::models::metrics_repository_models::{MetricSummaryAggregation, MetricsDocument};
+use super::models::metrics_repository_models::{
+    MetricInterval, MetricQuantile, MetricSummaryAggregation, MetricTimeseries,
+    MetricTimeseriesBucket, MetricsDocument, PaginatedMetrics,
+};
+use super::models::pagination_models::{CursorDirection, PageCursor, page_cursors};
+
+/// Days covered by the metric queries when `num_days` is not given.
+pub const DEFAULT_NUM_DAYS: i32 = 7;
+
+/// Start of the window of `num_days` days, or of the default window, ending now.
+pub fn window_start(num_days: Option<i32>) -> DateTime<Utc> {
+    Utc::now() - chrono::Duration::days(num_days.unwrap_or(DEFAULT_NUM_DAYS) as i64)
+}
+
+/// Percentiles always reported by the summary, as p50, p90, p95 and p99.
+const SUMMARY_PERCENTILES: [f64; 4] = [0.5, 0.9, 0.95, 0.99];
+
+#[async_trait]
+pub trait MetricsRepository: Send + Sync {
+    async fn list_metrics(
+        &self,
+        metric_name: &str,
+        limit: Option<i64>,
+        skip: Option<u64>,
+        page_cursor: Option<&PageCursor>,
+        prediction_type: Option<String>,
+        predictor_version: Option<String>,
+    ) -> Result<PaginatedMetrics, mongodb::error::Error>;
+
+    /// Inserts metrics without stopping at the first failure and returns the
+    /// index and message of every document the server rejected.
+    async fn insert_metrics(
+        &self,
+        metrics: &[MetricsDocument],
+    ) -> Result<Vec<(usize, String)>, mongodb::error::Error>;
+
+    /// Returns the raw values of a metric, most recent first, for statistics
+    /// that cannot be computed by the aggregation pipeline.
+    async fn list_metric_values(
+        &self,
+        metric_name: &str,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        num_days: Option<i32>,
+        max_values: i64,
+    ) -> Result<Vec<f64>, mongodb::error::Error>;
+
+    async fn get_metric_summary_aggregation(
+        &self,
+        metric_name: &str,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        start_time: DateTime<Utc>,
+        quantiles: &[f64],
+    ) -> Result<Option<MetricSummaryAggregation>, mongodb::error::Error>;
+
+    async fn get_metric_timeseries_aggregation(
+        &self,
+        metric_name: &str,
+        interval: MetricInterval,
+        group_by: Option<&str>,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        num_days: Option<i32>,
+    ) -> Result<Vec<MetricTimeseries>, mongodb::error::Error>;
+
+    async fn get_metric_bins_aggregation(
+        &self,
+        metric_name: &str,
+        num_bins: i32,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        num_days: Option<i32>,
+    ) -> Result<Vec<MetricBinsAggregation>, mongodb::error::Error>;
+}
 
 #[derive(Clone)]
-pub struct MetricsRepository {
+pub struct MongoMetricsRepository {
     collection: Collection<MetricsDocument>,
 }
 
-impl MetricsRepository {
+impl MongoMetricsRepository {
     pub fn new(db_client: &DatabaseClient, collection_name: &str) -> Self {
         let collection: Collection<MetricsDocument> =
             db_client.get_database().collection(collection_name);
 
         info!(
-            "Created MetricsRepository for collection: {}",
+            "Created MongoMetricsRepository for collection: {}",
             collection_name
         );
 
         Self { collection }
     }
 
-    pub async fn list_metrics(
+    /// Listings and aggregations filter on the name, optionally on the
+    /// prediction type and predictor version tags, and go by `created_at`.
+    pub fn register_indexes(registry: &mut IndexRegistry, collection_name: &str) {
+        registry.add(
+            collection_name,
+            "metrics_name_tags_created_at",
+            doc! {
+                "metric_name": 1,
+                "tags.prediction_type": 1,
+                "tags.predictor_version": 1,
+                "created_at": -1
+            },
+            IndexOptions::default(),
+        );
+        registry.add(
+            collection_name,
+            "metrics_name_created_at",
+            doc! { "metric_name": 1, "created_at": -1, "_id": -1 },
+            IndexOptions::default(),
+        );
+    }
+}
+
+#[async_trait]
+impl MetricsRepository for MongoMetricsRepository {
+    async fn list_metrics(
         &self,
         metric_name: &str,
         limit: Option<i64>,
         skip: Option<u64>,
+        page_cursor: Option<&PageCursor>,
         prediction_type: Option<String>,
         predictor_version: Option<String>,
-    ) -> Result<Vec<MetricsDocument>, mongodb::error::Error> {
-        let mut options = mongodb::options::FindOptions::default();
+    ) -> Result<PaginatedMetrics, mongodb::error::Error> {
+        let skip_count = skip.unwrap_or(0);
+        let limit_count = limit.unwrap_or(20);
 
-        options.skip = Some(skip.unwrap_or(0));
-        options.limit = Some(limit.unwrap_or(20));
+        let mut options = mongodb::options::FindOptions::default();
 
-        options.sort = Some(doc! { "created_at": -1 });
+        // One extra document is fetched to know whether another page follows
+        options.limit = Some(limit_count + 1);
 
         let mut filter = doc! {};
         filter.insert("metric_name", metric_name);
@@ -52,6 +157,18 @@ impl MetricsRepository {
             filter.insert("tags.predictor_version", pred_version);
         }
 
+        match page_cursor {
+            Some(page_cursor) => {
+                let sort_order = page_cursor.sort_order();
+                options.sort = Some(doc! { "created_at": sort_order, "_id": sort_order });
+                filter.insert("$and", vec![page_cursor.range_filter("created_at")]);
+            }
+            _none => {
+                options.skip = Some(skip_count);
+                options.sort = Some(doc! { "created_at": -1, "_id": -1 });
+            }
+        }
+
         let mut cursor = self
             .collection
             .find(filter)
@@ -70,20 +187,135 @@ impl MetricsRepository {
             }
         }
 
+        let has_more = metrics.len() > limit_count as usize;
+        metrics.truncate(limit_count as usize);
+
+        let (has_previous, has_next) = match page_cursor.map(|c| c.direction) {
+            Some(CursorDirection::Next) => (true, has_more),
+            Some(CursorDirection::Prev) => {
+                metrics.reverse();
+                (has_more, true)
+            }
+            _none => (skip_count > 0, has_more),
+        };
+
+        let (prev_cursor, next_cursor) = page_cursors(
+            &metrics,
+            |metric| metric.id.map(|id| (metric.created_at, id)),
+            has_previous,
+            has_next,
+        );
+
         info!("Retrieved {} metrics from database", metrics.len());
 
-        Ok(metrics)
+        Ok(PaginatedMetrics {
+            metrics,
+            next_cursor,
+            prev_cursor,
+        })
     }
 
-    pub async fn get_metric_summary_aggregation(
+    async fn insert_metrics(
+        &self,
+        metrics: &[MetricsDocument],
+    ) -> Result<Vec<(usize, String)>, mongodb::error::Error> {
+        if metrics.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        match self.collection.insert_many(metrics).ordered(false).await {
+            Ok(result) => {
+                info!("Inserted {} metrics", result.inserted_ids.len());
+                Ok(Vec::new())
+            }
+            Err(e) => match *e.kind {
+                ErrorKind::InsertMany(InsertManyError {
+                    write_errors: Some(ref write_errors),
+                    write_concern_error: None,
+                    ..
+                }) => {
+                    let failures: Vec<(usize, String)> = write_errors
+                        .iter()
+                        .map(|write_error| (write_error.index, write_error.message.clone()))
+                        .collect();
+
+                    info!(
+                        "Inserted {} metrics, {} rejected by the server",
+                        metrics.len() - failures.len(),
+                        failures.len()
+                    );
+
+                    Ok(failures)
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    async fn list_metric_values(
         &self,
         metric_name: &str,
         prediction_type: Option<&str>,
         predictor_version: Option<&str>,
         num_days: Option<i32>,
-    ) -> Result<Option<MetricSummaryAggregation>, mongodb::error::Error> {
-        let start_time = Utc::now() - chrono::Duration::days(num_days.unwrap_or(7) as i64);
+        max_values: i64,
+    ) -> Result<Vec<f64>, mongodb::error::Error> {
+        let start_time = window_start(num_days);
+
+        let mut filter = doc! {
+            "created_at": {
+                "$gte": start_time
+            },
+            "metric_name": metric_name
+        };
+
+        if let Some(name) = prediction_type {
+            filter.insert("tags.prediction_type", name);
+        }
+
+        if let Some(name) = predictor_version {
+            filter.insert("tags.predictor_version", name);
+        }
+
+        let mut options = mongodb::options::FindOptions::default();
+        options.sort = Some(doc! { "created_at": -1 });
+        options.limit = Some(max_values);
+        options.projection = Some(doc! { "_id": 0, "metric_value": 1 });
+
+        let mut cursor = self
+            .collection
+            .clone_with_type::<Document>()
+            .find(filter)
+            .with_options(Some(options))
+            .await?;
+
+        let mut values = Vec::new();
+
+        while cursor.advance().await? {
+            match cursor.current().get_f64("metric_value") {
+                Ok(value) => values.push(value),
+                Err(e) => log::error!("Failed to read metric value: {}", e),
+            }
+        }
+
+        info!(
+            "Retrieved {} values for metric '{}' and predictor_version '{}'",
+            values.len(),
+            metric_name,
+            predictor_version.unwrap_or("")
+        );
+
+        Ok(values)
+    }
 
+    async fn get_metric_summary_aggregation(
+        &self,
+        metric_name: &str,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        start_time: DateTime<Utc>,
+        quantiles: &[f64],
+    ) -> Result<Option<MetricSummaryAggregation>, mongodb::error::Error> {
         let mut match_doc = doc! {
             "created_at": {
                 "$gte": start_time
@@ -99,6 +331,12 @@ impl MetricsRepository {
             match_doc.insert("tags.predictor_version", name);
         }
 
+        let percentiles: Vec<f64> = SUMMARY_PERCENTILES
+            .iter()
+            .chain(quantiles)
+            .copied()
+            .collect();
+
         let pipeline = vec![
             doc! { "$match": match_doc },
             doc! {
@@ -108,7 +346,15 @@ impl MetricsRepository {
                     "sum_value": { "$sum": "$metric_value" },
                     "count": { "$sum": 1 },
                     "min_value": { "$min": "$metric_value" },
-                    "max_value": { "$max": "$metric_value" }
+                    "max_value": { "$max": "$metric_value" },
+                    "std_dev_value": { "$stdDevPop": "$metric_value" },
+                    "percentiles": {
+                        "$percentile": {
+                            "input": "$metric_value",
+                            "p": &percentiles,
+                            "method": "approximate"
+                        }
+                    }
                 }
             },
         ];
@@ -117,6 +363,18 @@ impl MetricsRepository {
 
         if cursor.advance().await? {
             let doc = cursor.current();
+
+            // `$percentile` returns one value per requested `p`, in the same order
+            let percentile_values: Vec<f64> = match doc.get_array("percentiles") {
+                Ok(values) => values
+                    .into_iter()
+                    .map(|value| value.ok().and_then(|value| value.as_f64()).unwrap_or(0.0))
+                    .collect(),
+                Err(_) => Vec::new(),
+            };
+            let percentile_at =
+                |position: usize| percentile_values.get(position).copied().unwrap_or(0.0);
+
             let aggregation = MetricSummaryAggregation {
                 avg_value: doc.get_f64("avg_value").unwrap_or(0.0),
                 sum_value: doc.get_f64("sum_value").unwrap_or(0.0),
@@ -126,6 +384,19 @@ impl MetricsRepository {
                     .unwrap_or_else(|_| doc.get_i64("count").unwrap_or(0)),
                 min_value: doc.get_f64("min_value").unwrap_or(0.0),
                 max_value: doc.get_f64("max_value").unwrap_or(0.0),
+                std_dev_value: doc.get_f64("std_dev_value").unwrap_or(0.0),
+                p50_value: percentile_at(0),
+                p90_value: percentile_at(1),
+                p95_value: percentile_at(2),
+                p99_value: percentile_at(3),
+                quantiles: quantiles
+                    .iter()
+                    .enumerate()
+                    .map(|(position, quantile)| MetricQuantile {
+                        quantile: *quantile,
+                        value: percentile_at(SUMMARY_PERCENTILES.len() + position),
+                    })
+                    .collect(),
             };
 
             info!("Calculated aggregation for metric '{}'", metric_name);
@@ -136,7 +407,119 @@ impl MetricsRepository {
         }
     }
 
-    pub async fn get_metric_bins_aggregation(
+    async fn get_metric_timeseries_aggregation(
+        &self,
+        metric_name: &str,
+        interval: MetricInterval,
+        group_by: Option<&str>,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        num_days: Option<i32>,
+    ) -> Result<Vec<MetricTimeseries>, mongodb::error::Error> {
+        let start_time = window_start(num_days);
+
+        let mut match_doc = doc! {
+            "created_at": {
+                "$gte": start_time
+            },
+            "metric_name": metric_name
+        };
+
+        if let Some(name) = prediction_type {
+            match_doc.insert("tags.prediction_type", name);
+        }
+
+        if let Some(name) = predictor_version {
+            match_doc.insert("tags.predictor_version", name);
+        }
+
+        let mut group_id = doc! {
+            "bucket_start": {
+                "$dateTrunc": {
+                    "date": "$created_at",
+                    "unit": interval.unit.as_str(),
+                    "binSize": interval.bin_size
+                }
+            }
+        };
+
+        if let Some(tag) = group_by {
+            group_id.insert("group", format!("$tags.{}", tag));
+        }
+
+        let pipeline = vec![
+            doc! { "$match": match_doc },
+            doc! {
+                "$group": {
+                    "_id": group_id,
+                    "avg_value": { "$avg": "$metric_value" },
+                    "sum_value": { "$sum": "$metric_value" },
+                    "count": { "$sum": 1 },
+                    "min_value": { "$min": "$metric_value" },
+                    "max_value": { "$max": "$metric_value" }
+                }
+            },
+            doc! {
+                "$sort": { "_id.group": 1, "_id.bucket_start": 1 }
+            },
+            doc! {
+                "$project": {
+                    "_id": 0,
+                    "group": { "$ifNull": ["$_id.group", null] },
+                    "bucket_start": "$_id.bucket_start",
+                    "avg_value": 1,
+                    "sum_value": 1,
+                    "count": 1,
+                    "min_value": 1,
+                    "max_value": 1
+                }
+            },
+        ];
+
+        let mut cursor = self.collection.aggregate(pipeline).await?;
+        let mut series: Vec<MetricTimeseries> = Vec::new();
+
+        while cursor.advance().await? {
+            let doc = cursor.current();
+            let group = doc.get_str("group").ok().map(str::to_string);
+            let bucket = MetricTimeseriesBucket {
+                bucket_start: doc
+                    .get_datetime("bucket_start")
+                    .map(|date| date.to_chrono())
+                    .unwrap_or_default(),
+                avg_value: doc.get_f64("avg_value").unwrap_or(0.0),
+                sum_value: doc.get_f64("sum_value").unwrap_or(0.0),
+                count: doc
+                    .get_i32("count")
+                    .map(|v| v as i64)
+                    .unwrap_or_else(|_| doc.get_i64("count").unwrap_or(0)),
+                min_value: doc.get_f64("min_value").unwrap_or(0.0),
+                max_value: doc.get_f64("max_value").unwrap_or(0.0),
+            };
+
+            // Buckets arrive sorted by group, so each series is contiguous
+            match series.last_mut().filter(|last| last.group == group) {
+                Some(last) => last.buckets.push(bucket),
+                _none => series.push(MetricTimeseries {
+                    group,
+                    buckets: vec![bucket],
+                }),
+            }
+        }
+
+        info!(
+            "Generated {} time series for metric '{}' with interval {}{} over {} days",
+            series.len(),
+            metric_name,
+            interval.bin_size,
+            interval.unit.as_str(),
+            num_days.unwrap_or(DEFAULT_NUM_DAYS)
+        );
+
+        Ok(series)
+    }
+
+    async fn get_metric_bins_aggregation(
         &self,
         metric_name: &str,
         num_bins: i32,
@@ -144,7 +527,7 @@ impl MetricsRepository {
         predictor_version: Option<&str>,
         num_days: Option<i32>,
     ) -> Result<Vec<MetricBinsAggregation>, mongodb::error::Error> {
-        let start_time = Utc::now() - chrono::Duration::days(num_days.unwrap_or(7) as i64);
+        let start_time = window_start(num_days);
 
         let mut match_doc = doc! {
             "created_at": {
@@ -386,7 +769,7 @@ impl MetricsRepository {
             histogram_bins.len(),
             metric_name,
             prediction_type.unwrap_or(""),
-            num_days.unwrap_or(7)
+            num_days.unwrap_or(DEFAULT_NUM_DAYS)
         );
 
         Ok(histogram_bins)
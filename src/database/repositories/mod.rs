@@ -1,6 +1,13 @@
+pub mod api_key_repository;
 pub mod article_prediction_repository;
 pub mod article_repository;
 pub mod deployment_repository;
+pub mod feed_source_repository;
+pub mod health_repository;
+#[cfg(test)]
+pub mod in_memory;
+pub mod lease_repository;
 pub mod metrics_repository;
 pub mod models;
 pub mod predictors_repository;
+pub mod rollout_repository;
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// What a key may do. Roles are ordered: each one is granted everything the
+/// previous ones are.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyRole {
+    /// Reads, when they are not public.
+    Reader,
+    /// Records metrics and articles.
+    Ingest,
+    /// Changes deployments and rollouts.
+    Operator,
+    /// Issues and revokes keys.
+    Admin,
+}
+
+impl ApiKeyRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyRole::Reader => "reader",
+            ApiKeyRole::Ingest => "ingest",
+            ApiKeyRole::Operator => "operator",
+            ApiKeyRole::Admin => "admin",
+        }
+    }
+}
+
+/// A stored key. Only the SHA-256 hash of the key is kept, so a key cannot
+/// be recovered once issued; `key_prefix` helps telling keys apart.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ApiKeyDocument {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+
+    pub name: String,
+    pub role: ApiKeyRole,
+    pub key_prefix: String,
+    pub key_hash: String,
+
+    pub created_by: String,
+
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(
+        default,
+        with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
@@ -2,22 +2,26 @@ use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+use super::pagination_models::PageCursor;
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct SourceDocument {
     pub id: Option<String>,
     pub name: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct PredictionDocument {
     pub prediction_confidence: Option<f64>,
     pub prediction_value: serde_json::Value,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct ArticleDocument {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<crate::web::openapi::ObjectIdSchema>)]
     pub id: Option<ObjectId>,
 
     pub source: SourceDocument,
@@ -27,14 +31,21 @@ pub struct ArticleDocument {
     pub url: Option<String>,
     pub url_to_image: Option<String>,
 
+    /// Hex SHA-256 of the canonical form of `url`, set on ingested articles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url_hash: Option<String>,
+
     #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
     pub published_at: DateTime<Utc>,
 
     pub content: Option<String>,
 
     #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
     pub created_at: DateTime<Utc>,
     #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
     pub updated_at: DateTime<Utc>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -42,6 +53,19 @@ pub struct ArticleDocument {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sentiment_analysis: Option<PredictionDocument>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_score: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_highlights: Option<HashMap<String, String>>,
+}
+
+/// Outcome of upserting an article on its URL hash.
+#[derive(Debug, Clone, Copy)]
+pub struct ArticleUpsert {
+    pub id: ObjectId,
+    pub created: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -52,4 +76,23 @@ pub struct PaginatedArticles {
     pub page: u64,
     pub per_page: i64,
     pub total_pages: u64,
+    pub next_cursor: Option<PageCursor>,
+    pub prev_cursor: Option<PageCursor>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredictionFilter {
+    pub prediction_type: String,
+    pub values: Vec<String>,
+    pub min_confidence: Option<f64>,
+}
+
+impl PredictionFilter {
+    pub fn new(prediction_type: &str) -> Self {
+        Self {
+            prediction_type: prediction_type.to_string(),
+            values: Vec::new(),
+            min_confidence: None,
+        }
+    }
 }
@@ -1,16 +1,19 @@
 use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct ActiveDeploymentDocument {
+    #[schema(value_type = crate::web::openapi::ObjectIdSchema)]
     pub predictor_id: ObjectId,
     pub traffic_percentage: f64,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct DeploymentDocument {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<crate::web::openapi::ObjectIdSchema>)]
     pub id: Option<ObjectId>,
 
     pub prediction_type: String,
@@ -18,7 +21,41 @@ pub struct DeploymentDocument {
     pub active_deployments: Vec<ActiveDeploymentDocument>,
 
     #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
     pub created_at: DateTime<Utc>,
     #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct DeploymentHistoryDocument {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<crate::web::openapi::ObjectIdSchema>)]
+    pub id: Option<ObjectId>,
+
+    pub prediction_type: String,
+
+    pub previous_deployments: Vec<ActiveDeploymentDocument>,
+    pub active_deployments: Vec<ActiveDeploymentDocument>,
+
+    pub changed_by: String,
+    pub reason: Option<String>,
+
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub created_at: DateTime<Utc>,
+}
+
+/// A complete traffic split to apply to a prediction type in one step.
+#[derive(Debug, Clone)]
+pub struct TrafficSplitUpdate {
+    pub prediction_type: String,
+    /// Predictors receiving traffic, as stored on the deployment.
+    pub active_deployments: Vec<ActiveDeploymentDocument>,
+    /// Traffic of each listed predictor; unlisted predictors drop to 0%.
+    pub traffic_percentages: Vec<(ObjectId, i32)>,
+    pub changed_by: String,
+    pub reason: Option<String>,
     pub updated_at: DateTime<Utc>,
 }
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// An RSS or Atom feed polled by the feed worker. The cache validators and
+/// the fetch outcome are maintained by the worker.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct FeedSourceDocument {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<crate::web::openapi::ObjectIdSchema>)]
+    pub id: Option<ObjectId>,
+
+    pub url: String,
+    /// `source.name` of the articles, instead of the title of the feed.
+    pub name: Option<String>,
+    /// `source.id` of the articles.
+    pub source_id: Option<String>,
+    pub enabled: bool,
+
+    /// `ETag` and `Last-Modified` of the last successful fetch, sent back so
+    /// that unchanged feeds are not downloaded again.
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+
+    #[serde(
+        default,
+        with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    #[schema(value_type = Option<crate::web::openapi::BsonDateTimeSchema>)]
+    pub last_fetched_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    #[schema(value_type = Option<crate::web::openapi::BsonDateTimeSchema>)]
+    pub last_success_at: Option<DateTime<Utc>>,
+
+    /// Latest error, kept after the feed recovers.
+    pub last_error: Option<String>,
+    #[serde(
+        default,
+        with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    #[schema(value_type = Option<crate::web::openapi::BsonDateTimeSchema>)]
+    pub last_error_at: Option<DateTime<Utc>>,
+    /// Failed fetches since the last successful one.
+    #[serde(default)]
+    pub consecutive_failures: i32,
+
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Result of polling a feed once.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedFetchOutcome {
+    /// The feed was downloaded and its articles stored.
+    Fetched {
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+    /// The server answered `304 Not Modified`.
+    NotModified,
+    Failed(String),
+}
@@ -0,0 +1,13 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Lease on a background task that only one instance may run at a time.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LeaseDocument {
+    /// Name of the task, e.g. `news_fetcher`.
+    #[serde(rename = "_id")]
+    pub name: String,
+
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
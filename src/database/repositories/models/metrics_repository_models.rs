@@ -2,10 +2,14 @@ use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+use super::pagination_models::PageCursor;
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct MetricsDocument {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<crate::web::openapi::ObjectIdSchema>)]
     pub id: Option<ObjectId>,
 
     pub metric_name: String,
@@ -15,24 +19,123 @@ pub struct MetricsDocument {
     pub tags: HashMap<String, String>,
 
     #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
     pub created_at: DateTime<Utc>,
     #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+pub struct PaginatedMetrics {
+    pub metrics: Vec<MetricsDocument>,
+    pub next_cursor: Option<PageCursor>,
+    pub prev_cursor: Option<PageCursor>,
+}
+
+#[derive(Debug, Clone)]
 pub struct MetricSummaryAggregation {
     pub avg_value: f64,
     pub sum_value: f64,
     pub count: i64,
     pub min_value: f64,
     pub max_value: f64,
+    pub std_dev_value: f64,
+    pub p50_value: f64,
+    pub p90_value: f64,
+    pub p95_value: f64,
+    pub p99_value: f64,
+    /// Caller-requested quantiles, in the order they were asked for.
+    pub quantiles: Vec<MetricQuantile>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MetricQuantile {
+    pub quantile: f64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct MetricBinsAggregation {
     pub bin_index: i32,
     pub bin_start: f64,
     pub bin_end: f64,
     pub count: i64,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricIntervalUnit {
+    Minute,
+    Hour,
+    Day,
+    Week,
+}
+
+impl MetricIntervalUnit {
+    /// Unit name understood by MongoDB's `$dateTrunc`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricIntervalUnit::Minute => "minute",
+            MetricIntervalUnit::Hour => "hour",
+            MetricIntervalUnit::Day => "day",
+            MetricIntervalUnit::Week => "week",
+        }
+    }
+}
+
+/// Width of a time-series bucket, written as `<count><unit>` with unit
+/// `m`, `h`, `d` or `w` (e.g. `15m`, `1h`, `1d`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricInterval {
+    pub bin_size: i64,
+    pub unit: MetricIntervalUnit,
+}
+
+impl MetricInterval {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let unit = match value.chars().last()? {
+            'm' => MetricIntervalUnit::Minute,
+            'h' => MetricIntervalUnit::Hour,
+            'd' => MetricIntervalUnit::Day,
+            'w' => MetricIntervalUnit::Week,
+            _ => return None,
+        };
+
+        let bin_size: i64 = value[..value.len() - 1].parse().ok()?;
+        if bin_size <= 0 {
+            return None;
+        }
+
+        Some(Self { bin_size, unit })
+    }
+
+    pub fn duration(&self) -> chrono::Duration {
+        match self.unit {
+            MetricIntervalUnit::Minute => chrono::Duration::minutes(self.bin_size),
+            MetricIntervalUnit::Hour => chrono::Duration::hours(self.bin_size),
+            MetricIntervalUnit::Day => chrono::Duration::days(self.bin_size),
+            MetricIntervalUnit::Week => chrono::Duration::weeks(self.bin_size),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MetricTimeseriesBucket {
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub bucket_start: DateTime<Utc>,
+    pub avg_value: f64,
+    pub sum_value: f64,
+    pub count: i64,
+    pub min_value: f64,
+    pub max_value: f64,
+}
+
+/// Buckets of one series, ordered by time. `group` holds the value of the
+/// grouping tag, or `None` when the series is ungrouped or the tag is missing.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MetricTimeseries {
+    pub group: Option<String>,
+    pub buckets: Vec<MetricTimeseriesBucket>,
+}
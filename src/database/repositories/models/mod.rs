@@ -1,5 +1,10 @@
+pub mod api_key_repository_models;
 pub mod article_prediction_repository_models;
 pub mod article_repository_models;
 pub mod deployment_repository_models;
+pub mod feed_source_repository_models;
+pub mod lease_repository_models;
 pub mod metrics_repository_models;
+pub mod pagination_models;
 pub mod predictor_repository_models;
+pub mod rollout_repository_models;
@@ -0,0 +1,116 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use mongodb::bson::{Document, doc, oid::ObjectId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorDirection {
+    Next,
+    Prev,
+}
+
+/// Position in a listing sorted by `(<timestamp>, _id)` descending.
+///
+/// Cursors are handed to clients as opaque tokens, so the encoding can change
+/// without breaking anything but in-flight pagination.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageCursor {
+    pub direction: CursorDirection,
+    pub sort_value: DateTime<Utc>,
+    pub id: ObjectId,
+}
+
+impl PageCursor {
+    pub fn new(direction: CursorDirection, sort_value: DateTime<Utc>, id: ObjectId) -> Self {
+        Self {
+            direction,
+            sort_value,
+            id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let direction = match self.direction {
+            CursorDirection::Next => "n",
+            CursorDirection::Prev => "p",
+        };
+
+        URL_SAFE_NO_PAD.encode(format!(
+            "{}:{}:{}",
+            direction,
+            self.sort_value.timestamp_millis(),
+            self.id.to_hex()
+        ))
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(token).ok()?).ok()?;
+        let mut parts = raw.splitn(3, ':');
+
+        let direction = match parts.next()? {
+            "n" => CursorDirection::Next,
+            "p" => CursorDirection::Prev,
+            _ => return None,
+        };
+        let sort_value = DateTime::from_timestamp_millis(parts.next()?.parse().ok()?)?;
+        let id = ObjectId::parse_str(parts.next()?).ok()?;
+
+        Some(Self::new(direction, sort_value, id))
+    }
+
+    /// Sort order (`1` or `-1`) to apply on `(<sort_field>, _id)` when reading from this cursor.
+    pub fn sort_order(&self) -> i32 {
+        match self.direction {
+            CursorDirection::Next => -1,
+            CursorDirection::Prev => 1,
+        }
+    }
+
+    /// Matches the documents located after the cursor in its direction.
+    pub fn range_filter(&self, sort_field: &str) -> Document {
+        match self.direction {
+            CursorDirection::Next => self.key_filter(sort_field, "$lt", "$lt"),
+            CursorDirection::Prev => self.key_filter(sort_field, "$gt", "$gt"),
+        }
+    }
+
+    /// Matches the documents sorted before the cursor in the default descending order,
+    /// including the cursor document itself for `Next` cursors.
+    pub fn preceding_filter(&self, sort_field: &str) -> Document {
+        match self.direction {
+            CursorDirection::Next => self.key_filter(sort_field, "$gt", "$gte"),
+            CursorDirection::Prev => self.key_filter(sort_field, "$gt", "$gt"),
+        }
+    }
+
+    fn key_filter(&self, sort_field: &str, sort_op: &str, id_op: &str) -> Document {
+        doc! {
+            "$or": [
+                { sort_field: { sort_op: self.sort_value } },
+                { sort_field: self.sort_value, "_id": { id_op: self.id } }
+            ]
+        }
+    }
+}
+
+/// Computes the `(prev, next)` cursors surrounding a page already in descending order.
+pub fn page_cursors<T>(
+    items: &[T],
+    key: impl Fn(&T) -> Option<(DateTime<Utc>, ObjectId)>,
+    has_previous: bool,
+    has_next: bool,
+) -> (Option<PageCursor>, Option<PageCursor>) {
+    let prev_cursor = items
+        .first()
+        .filter(|_| has_previous)
+        .and_then(&key)
+        .map(|(sort_value, id)| PageCursor::new(CursorDirection::Prev, sort_value, id));
+
+    let next_cursor = items
+        .last()
+        .filter(|_| has_next)
+        .and_then(&key)
+        .map(|(sort_value, id)| PageCursor::new(CursorDirection::Next, sort_value, id));
+
+    (prev_cursor, next_cursor)
+}
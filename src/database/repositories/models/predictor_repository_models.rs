@@ -1,10 +1,12 @@
 use chrono::{DateTime, Utc};
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct PredictorDocument {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<crate::web::openapi::ObjectIdSchema>)]
     pub id: Option<ObjectId>,
 
     pub prediction_type: String,
@@ -13,7 +15,9 @@ pub struct PredictorDocument {
     pub traffic_percentage: i32,
 
     #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
     pub created_at: DateTime<Utc>,
     #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
     pub updated_at: DateTime<Utc>,
 }
@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::deployment_repository_models::ActiveDeploymentDocument;
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RolloutStatus {
+    Active,
+    Completed,
+    RolledBack,
+    Cancelled,
+    Failed,
+}
+
+impl RolloutStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RolloutStatus::Active => "active",
+            RolloutStatus::Completed => "completed",
+            RolloutStatus::RolledBack => "rolled_back",
+            RolloutStatus::Cancelled => "cancelled",
+            RolloutStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Condition on the summary of a metric of the rolled out predictor over the
+/// current step, checked before moving to the next step.
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct RolloutGateDocument {
+    pub metric_name: String,
+    pub max_avg_value: Option<f64>,
+    pub min_avg_value: Option<f64>,
+    /// Values the current step must have collected to be judged, 30 by default.
+    pub min_samples: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct RolloutDocument {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<crate::web::openapi::ObjectIdSchema>)]
+    pub id: Option<ObjectId>,
+
+    pub prediction_type: String,
+    #[schema(value_type = crate::web::openapi::ObjectIdSchema)]
+    pub predictor_id: ObjectId,
+
+    pub steps: Vec<i32>,
+    pub step_interval_hours: i64,
+    pub gate: Option<RolloutGateDocument>,
+
+    pub status: RolloutStatus,
+    pub status_reason: Option<String>,
+
+    /// Index in `steps` of the last applied step, `None` until the first one is applied.
+    pub current_step: Option<usize>,
+    /// When the current step was applied; the gate only looks at metrics recorded since.
+    #[serde(
+        default,
+        with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    #[schema(value_type = Option<crate::web::openapi::BsonDateTimeSchema>)]
+    pub step_started_at: Option<DateTime<Utc>>,
+    /// Split of the other predictors when the rollout started, scaled down at
+    /// each step and restored on rollback.
+    pub baseline_deployments: Vec<ActiveDeploymentDocument>,
+    /// Why the rollout is being rolled back, set while the baseline split
+    /// could not be restored yet; the rollback is retried until it is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback_reason: Option<String>,
+
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub next_step_at: DateTime<Utc>,
+
+    pub created_by: String,
+
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub updated_at: DateTime<Utc>,
+}
@@ -1,31 +1,67 @@
+use async_trait::async_trait;
 use log::info;
 use mongodb::Collection;
-use mongodb::bson::doc;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::IndexOptions;
 use std::collections::HashSet;
 
+use crate::database::indexes::IndexRegistry;
 use crate::database::mongo_client::DatabaseClient;
 
 use super::models::predictor_repository_models::PredictorDocument;
 
+#[async_trait]
+pub trait PredictorRepository: Send + Sync {
+    async fn get_prediction_types(&self) -> Result<HashSet<String>, mongodb::error::Error>;
+
+    async fn get_predictor_versions(
+        &self,
+        prediction_type: &str,
+    ) -> Result<HashSet<i32>, mongodb::error::Error>;
+
+    async fn get_predictors_by_type(
+        &self,
+        prediction_type: &str,
+        min_traffic: Option<i32>,
+    ) -> Result<Vec<PredictorDocument>, mongodb::error::Error>;
+
+    async fn find_by_ids(
+        &self,
+        predictor_ids: &[ObjectId],
+    ) -> Result<Vec<PredictorDocument>, mongodb::error::Error>;
+}
+
 #[derive(Clone)]
-pub struct PredictorRepository {
+pub struct MongoPredictorRepository {
     collection: Collection<PredictorDocument>,
 }
 
-impl PredictorRepository {
+impl MongoPredictorRepository {
     pub fn new(db_client: &DatabaseClient, collection_name: &str) -> Self {
         let collection: Collection<PredictorDocument> =
             db_client.get_database().collection(collection_name);
 
         info!(
-            "Created PredictorRepository for collection: {}",
+            "Created MongoPredictorRepository for collection: {}",
             collection_name
         );
 
         Self { collection }
     }
 
-    pub async fn get_prediction_types(&self) -> Result<HashSet<String>, mongodb::error::Error> {
+    pub fn register_indexes(registry: &mut IndexRegistry, collection_name: &str) {
+        registry.add(
+            collection_name,
+            "predictors_prediction_type_version",
+            doc! { "prediction_type": 1, "predictor_version": 1 },
+            IndexOptions::default(),
+        );
+    }
+}
+
+#[async_trait]
+impl PredictorRepository for MongoPredictorRepository {
+    async fn get_prediction_types(&self) -> Result<HashSet<String>, mongodb::error::Error> {
         let pipeline = vec![
             doc! {
                 "$group": {
@@ -52,7 +88,7 @@ impl PredictorRepository {
         Ok(prediction_types)
     }
 
-    pub async fn get_predictor_versions(
+    async fn get_predictor_versions(
         &self,
         prediction_type: &str,
     ) -> Result<HashSet<i32>, mongodb::error::Error> {
@@ -91,7 +127,7 @@ impl PredictorRepository {
         Ok(predictor_versions)
     }
 
-    pub async fn get_predictors_by_type(
+    async fn get_predictors_by_type(
         &self,
         prediction_type: &str,
         min_traffic: Option<i32>,
@@ -131,4 +167,33 @@ impl PredictorRepository {
 
         Ok(predictors)
     }
+
+    async fn find_by_ids(
+        &self,
+        predictor_ids: &[ObjectId],
+    ) -> Result<Vec<PredictorDocument>, mongodb::error::Error> {
+        let filter = doc! { "_id": { "$in": predictor_ids } };
+
+        let mut cursor = self.collection.find(filter).await?;
+
+        let mut predictors = Vec::new();
+
+        while cursor.advance().await? {
+            match cursor.deserialize_current() {
+                Ok(predictor) => predictors.push(predictor),
+                Err(e) => {
+                    log::error!("Failed to deserialize predictor: {}", e);
+                    return Err(e);
+                }
+            }
+        }
+
+        info!(
+            "Found {} of {} requested predictors",
+            predictors.len(),
+            predictor_ids.len()
+        );
+
+        Ok(predictors)
+    }
 }
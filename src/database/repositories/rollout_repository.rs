@@ -0,0 +1,325 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use mongodb::Collection;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::{IndexOptions, ReturnDocument};
+
+use crate::database::indexes::IndexRegistry;
+use crate::database::mongo_client::DatabaseClient;
+
+use super::models::deployment_repository_models::ActiveDeploymentDocument;
+use super::models::rollout_repository_models::{RolloutDocument, RolloutStatus};
+
+pub const ACTIVE_ROLLOUT_INDEX_NAME: &str = "rollouts_active_prediction_type";
+
+#[async_trait]
+pub trait RolloutRepository: Send + Sync {
+    /// Fails with a duplicate key error if `rollout` is active while another
+    /// rollout of its prediction type already is.
+    async fn insert(&self, rollout: &RolloutDocument) -> Result<ObjectId, mongodb::error::Error>;
+
+    async fn find_by_id(
+        &self,
+        rollout_id: ObjectId,
+    ) -> Result<Option<RolloutDocument>, mongodb::error::Error>;
+
+    async fn find_active_by_prediction_type(
+        &self,
+        prediction_type: &str,
+    ) -> Result<Option<RolloutDocument>, mongodb::error::Error>;
+
+    async fn list_rollouts(
+        &self,
+        prediction_type: Option<&str>,
+        status: Option<RolloutStatus>,
+        limit: Option<i64>,
+        skip: Option<u64>,
+    ) -> Result<Vec<RolloutDocument>, mongodb::error::Error>;
+
+    /// Claims one active rollout whose next step is due by pushing its
+    /// `next_step_at` back by `lease`, so concurrent schedulers skip it.
+    async fn claim_due_rollout(
+        &self,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<Option<RolloutDocument>, mongodb::error::Error>;
+
+    async fn record_step(
+        &self,
+        rollout_id: ObjectId,
+        current_step: usize,
+        baseline_deployments: &[ActiveDeploymentDocument],
+        step_started_at: DateTime<Utc>,
+        next_step_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error>;
+
+    /// Moves the next attempt at the current step of a rollout to `retry_at`.
+    async fn reschedule(
+        &self,
+        rollout_id: ObjectId,
+        retry_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error>;
+
+    /// Marks a rollout as rolling back, to retry restoring its baseline at
+    /// `retry_at`.
+    async fn record_rollback(
+        &self,
+        rollout_id: ObjectId,
+        rollback_reason: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error>;
+
+    /// Moves a rollout out of `expected_status`, returning `false` if it was
+    /// no longer in that status.
+    async fn update_status(
+        &self,
+        rollout_id: ObjectId,
+        expected_status: RolloutStatus,
+        status: RolloutStatus,
+        status_reason: Option<&str>,
+    ) -> Result<bool, mongodb::error::Error>;
+}
+
+#[derive(Clone)]
+pub struct MongoRolloutRepository {
+    collection: Collection<RolloutDocument>,
+}
+
+impl MongoRolloutRepository {
+    pub fn new(db_client: &DatabaseClient, collection_name: &str) -> Self {
+        let collection: Collection<RolloutDocument> =
+            db_client.get_database().collection(collection_name);
+
+        info!(
+            "Created MongoRolloutRepository for collection: {}",
+            collection_name
+        );
+
+        Self { collection }
+    }
+
+    /// The scheduler claims active rollouts by `next_step_at`; listings and
+    /// the active rollout lookup filter by prediction type and status. At
+    /// most one rollout per prediction type may be active, which concurrent
+    /// creations cannot guarantee on their own.
+    pub fn register_indexes(registry: &mut IndexRegistry, collection_name: &str) {
+        registry.add(
+            collection_name,
+            "rollouts_status_next_step_at",
+            doc! { "status": 1, "next_step_at": 1 },
+            IndexOptions::default(),
+        );
+        registry.add(
+            collection_name,
+            "rollouts_prediction_type_status",
+            doc! { "prediction_type": 1, "status": 1, "created_at": -1 },
+            IndexOptions::default(),
+        );
+        registry.add(
+            collection_name,
+            ACTIVE_ROLLOUT_INDEX_NAME,
+            doc! { "prediction_type": 1 },
+            IndexOptions::builder()
+                .unique(true)
+                .partial_filter_expression(doc! { "status": RolloutStatus::Active.as_str() })
+                .build(),
+        );
+    }
+}
+
+#[async_trait]
+impl RolloutRepository for MongoRolloutRepository {
+    async fn insert(&self, rollout: &RolloutDocument) -> Result<ObjectId, mongodb::error::Error> {
+        let result = self.collection.insert_one(rollout).await?;
+
+        let rollout_id = result.inserted_id.as_object_id().unwrap_or_default();
+
+        info!(
+            "Created rollout {} for prediction type '{}'",
+            rollout_id, rollout.prediction_type
+        );
+
+        Ok(rollout_id)
+    }
+
+    async fn find_by_id(
+        &self,
+        rollout_id: ObjectId,
+    ) -> Result<Option<RolloutDocument>, mongodb::error::Error> {
+        self.collection.find_one(doc! { "_id": rollout_id }).await
+    }
+
+    async fn find_active_by_prediction_type(
+        &self,
+        prediction_type: &str,
+    ) -> Result<Option<RolloutDocument>, mongodb::error::Error> {
+        self.collection
+            .find_one(doc! {
+                "prediction_type": prediction_type,
+                "status": RolloutStatus::Active.as_str()
+            })
+            .await
+    }
+
+    async fn list_rollouts(
+        &self,
+        prediction_type: Option<&str>,
+        status: Option<RolloutStatus>,
+        limit: Option<i64>,
+        skip: Option<u64>,
+    ) -> Result<Vec<RolloutDocument>, mongodb::error::Error> {
+        let mut filter = doc! {};
+
+        if let Some(prediction_type) = prediction_type {
+            filter.insert("prediction_type", prediction_type);
+        }
+
+        if let Some(status) = status {
+            filter.insert("status", status.as_str());
+        }
+
+        let mut options = mongodb::options::FindOptions::default();
+        options.skip = Some(skip.unwrap_or(0));
+        options.limit = Some(limit.unwrap_or(20));
+        options.sort = Some(doc! { "created_at": -1, "_id": -1 });
+
+        let mut cursor = self
+            .collection
+            .find(filter)
+            .with_options(Some(options))
+            .await?;
+
+        let mut rollouts = Vec::new();
+
+        while cursor.advance().await? {
+            match cursor.deserialize_current() {
+                Ok(rollout) => rollouts.push(rollout),
+                Err(e) => {
+                    log::error!("Failed to deserialize rollout: {}", e);
+                    return Err(e);
+                }
+            }
+        }
+
+        info!("Retrieved {} rollouts from database", rollouts.len());
+
+        Ok(rollouts)
+    }
+
+    async fn claim_due_rollout(
+        &self,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<Option<RolloutDocument>, mongodb::error::Error> {
+        self.collection
+            .find_one_and_update(
+                doc! {
+                    "status": RolloutStatus::Active.as_str(),
+                    "next_step_at": { "$lte": now }
+                },
+                doc! { "$set": { "next_step_at": now + lease } },
+            )
+            .sort(doc! { "next_step_at": 1 })
+            .return_document(ReturnDocument::After)
+            .await
+    }
+
+    async fn record_step(
+        &self,
+        rollout_id: ObjectId,
+        current_step: usize,
+        baseline_deployments: &[ActiveDeploymentDocument],
+        step_started_at: DateTime<Utc>,
+        next_step_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error> {
+        self.collection
+            .update_one(
+                doc! { "_id": rollout_id },
+                doc! {
+                    "$set": {
+                        "current_step": current_step as i64,
+                        "baseline_deployments": mongodb::bson::to_bson(baseline_deployments)?,
+                        "step_started_at": step_started_at,
+                        "next_step_at": next_step_at,
+                        "updated_at": Utc::now()
+                    }
+                },
+            )
+            .await?;
+
+        info!("Rollout {} moved to step {}", rollout_id, current_step);
+
+        Ok(())
+    }
+
+    async fn reschedule(
+        &self,
+        rollout_id: ObjectId,
+        retry_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error> {
+        self.collection
+            .update_one(
+                doc! { "_id": rollout_id },
+                doc! { "$set": { "next_step_at": retry_at, "updated_at": Utc::now() } },
+            )
+            .await?;
+
+        info!("Rollout {} rescheduled for {}", rollout_id, retry_at);
+
+        Ok(())
+    }
+
+    async fn record_rollback(
+        &self,
+        rollout_id: ObjectId,
+        rollback_reason: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<(), mongodb::error::Error> {
+        self.collection
+            .update_one(
+                doc! { "_id": rollout_id },
+                doc! {
+                    "$set": {
+                        "rollback_reason": rollback_reason,
+                        "next_step_at": retry_at,
+                        "updated_at": Utc::now()
+                    }
+                },
+            )
+            .await?;
+
+        info!(
+            "Rollout {} is rolling back: {}",
+            rollout_id, rollback_reason
+        );
+
+        Ok(())
+    }
+
+    async fn update_status(
+        &self,
+        rollout_id: ObjectId,
+        expected_status: RolloutStatus,
+        status: RolloutStatus,
+        status_reason: Option<&str>,
+    ) -> Result<bool, mongodb::error::Error> {
+        let result = self
+            .collection
+            .update_one(
+                doc! { "_id": rollout_id, "status": expected_status.as_str() },
+                doc! {
+                    "$set": {
+                        "status": status.as_str(),
+                        "status_reason": status_reason,
+                        "updated_at": Utc::now()
+                    }
+                },
+            )
+            .await?;
+
+        info!("Rollout {} is now {}", rollout_id, status.as_str());
+
+        Ok(result.modified_count > 0)
+    }
+}
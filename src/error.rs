@@ -0,0 +1,316 @@
+use axum::{
+    Json,
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use log::{error, warn};
+use mongodb::error::{ErrorKind, WriteFailure};
+use serde::Serialize;
+use std::fmt;
+use utoipa::ToSchema;
+
+use crate::web::middleware::request_id;
+
+/// `IllegalOperation`, among others returned for transactions on a standalone server.
+const ILLEGAL_OPERATION_CODE: i32 = 20;
+
+/// `DuplicateKey`, returned for writes rejected by a unique index.
+pub const DUPLICATE_KEY_CODE: i32 = 11000;
+
+/// Error shared by services and handlers. Its response carries a JSON body
+/// with a machine-readable `code` so that clients do not have to rely on the
+/// status code alone.
+#[derive(Debug)]
+pub enum AppError {
+    /// The request is malformed or violates a constraint; `param` names the
+    /// offending parameter or field when there is a single one.
+    Validation {
+        message: String,
+        param: Option<String>,
+    },
+    /// No API key, or an unknown or revoked one, came with the request.
+    Unauthorized {
+        message: String,
+    },
+    /// The API key of the request lacks the role the route requires.
+    Forbidden {
+        message: String,
+    },
+    NotFound {
+        message: String,
+    },
+    /// The request is valid but conflicts with the current state of a resource.
+    Conflict {
+        message: String,
+    },
+    PayloadTooLarge {
+        message: String,
+    },
+    /// The body comes with a `Content-Type` the route does not read.
+    UnsupportedMediaType {
+        message: String,
+    },
+    /// The client used up its rate limit; it may retry after
+    /// `retry_after_seconds`, which is sent as `Retry-After`.
+    TooManyRequests {
+        message: String,
+        retry_after_seconds: u64,
+    },
+    Database(mongodb::error::Error),
+    Internal(String),
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorBody,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// One of `validation_error`, `unauthorized`, `forbidden`, `not_found`,
+    /// `conflict`, `payload_too_large`, `unsupported_media_type`,
+    /// `rate_limited`, `database_error` or `internal_error`.
+    #[schema(value_type = String)]
+    pub code: &'static str,
+    pub message: String,
+    pub param: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl AppError {
+    pub fn validation(message: impl Into<String>) -> Self {
+        AppError::Validation {
+            message: message.into(),
+            param: None,
+        }
+    }
+
+    pub fn invalid_param(param: impl Into<String>, message: impl Into<String>) -> Self {
+        AppError::Validation {
+            message: message.into(),
+            param: Some(param.into()),
+        }
+    }
+
+    pub fn missing_param(param: &str) -> Self {
+        Self::invalid_param(param, format!("{} is required", param))
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        AppError::Unauthorized {
+            message: message.into(),
+        }
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        AppError::Forbidden {
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        AppError::NotFound {
+            message: message.into(),
+        }
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        AppError::Conflict {
+            message: message.into(),
+        }
+    }
+
+    pub fn payload_too_large(message: impl Into<String>) -> Self {
+        AppError::PayloadTooLarge {
+            message: message.into(),
+        }
+    }
+
+    pub fn unsupported_media_type(message: impl Into<String>) -> Self {
+        AppError::UnsupportedMediaType {
+            message: message.into(),
+        }
+    }
+
+    pub fn too_many_requests(message: impl Into<String>, retry_after_seconds: u64) -> Self {
+        AppError::TooManyRequests {
+            message: message.into(),
+            retry_after_seconds,
+        }
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        AppError::Internal(message.into())
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Validation { .. } => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden { .. } => StatusCode::FORBIDDEN,
+            AppError::NotFound { .. } => StatusCode::NOT_FOUND,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::UnsupportedMediaType { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Database(e) if is_unavailable(e) || is_transactions_unsupported(e) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Validation { .. } => "validation_error",
+            AppError::Unauthorized { .. } => "unauthorized",
+            AppError::Forbidden { .. } => "forbidden",
+            AppError::NotFound { .. } => "not_found",
+            AppError::Conflict { .. } => "conflict",
+            AppError::PayloadTooLarge { .. } => "payload_too_large",
+            AppError::UnsupportedMediaType { .. } => "unsupported_media_type",
+            AppError::TooManyRequests { .. } => "rate_limited",
+            AppError::Database(_) => "database_error",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Validation { message, .. }
+            | AppError::Unauthorized { message }
+            | AppError::Forbidden { message }
+            | AppError::NotFound { message }
+            | AppError::Conflict { message }
+            | AppError::PayloadTooLarge { message }
+            | AppError::UnsupportedMediaType { message }
+            | AppError::TooManyRequests { message, .. } => write!(f, "{}", message),
+            AppError::Database(e) => write!(f, "database error: {}", e),
+            AppError::Internal(message) => write!(f, "internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Database(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+fn is_unavailable(e: &mongodb::error::Error) -> bool {
+    matches!(
+        *e.kind,
+        ErrorKind::ServerSelection { .. }
+            | ErrorKind::Io(_)
+            | ErrorKind::ConnectionPoolCleared { .. }
+    )
+}
+
+/// Whether a write was rejected by a unique index.
+pub fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    match &*e.kind {
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => {
+            write_error.code == DUPLICATE_KEY_CODE
+        }
+        ErrorKind::Command(command_error) => command_error.code == DUPLICATE_KEY_CODE,
+        _ => false,
+    }
+}
+
+/// Traffic splits are applied in transactions, which a standalone server
+/// rejects: MongoDB has to run as a replica set, even a single-node one.
+fn is_transactions_unsupported(e: &mongodb::error::Error) -> bool {
+    match &*e.kind {
+        ErrorKind::Command(command_error) => {
+            command_error.code == ILLEGAL_OPERATION_CODE
+                && command_error
+                    .message
+                    .starts_with("Transaction numbers are only allowed")
+        }
+        _ => false,
+    }
+}
+
+impl From<mongodb::error::Error> for AppError {
+    fn from(e: mongodb::error::Error) -> Self {
+        AppError::Database(e)
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let request_id = request_id::current();
+        let status = self.status_code();
+        let code = self.code();
+        let retry_after_seconds = match &self {
+            AppError::TooManyRequests {
+                retry_after_seconds,
+                ..
+            } => Some(*retry_after_seconds),
+            _ => None,
+        };
+
+        let (message, param) = match self {
+            AppError::Validation { message, param } => (message, param),
+            AppError::Unauthorized { message }
+            | AppError::Forbidden { message }
+            | AppError::NotFound { message }
+            | AppError::Conflict { message }
+            | AppError::PayloadTooLarge { message }
+            | AppError::UnsupportedMediaType { message }
+            | AppError::TooManyRequests { message, .. } => (message, None),
+            // Server-side details are logged rather than handed to the client
+            AppError::Database(e) => {
+                error!(
+                    "Database error (request {}): {}",
+                    request_id.as_deref().unwrap_or("-"),
+                    e
+                );
+                let message = if is_transactions_unsupported(&e) {
+                    "the database does not support transactions, MongoDB must run as a replica set"
+                } else if status == StatusCode::SERVICE_UNAVAILABLE {
+                    "the database is unavailable"
+                } else {
+                    "the database failed to process the request"
+                };
+                (message.to_string(), None)
+            }
+            AppError::Internal(message) => {
+                error!(
+                    "Internal error (request {}): {}",
+                    request_id.as_deref().unwrap_or("-"),
+                    message
+                );
+                ("internal server error".to_string(), None)
+            }
+        };
+
+        if status.is_client_error() {
+            warn!("Rejected request with {}: {}", code, message);
+        }
+
+        let body = ErrorResponse {
+            error: ErrorBody {
+                code,
+                message,
+                param,
+                request_id,
+            },
+        };
+
+        let mut response = (status, Json(body)).into_response();
+        if let Some(seconds) = retry_after_seconds {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+
+        response
+    }
+}
@@ -0,0 +1,181 @@
+use log::{error, info, warn};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+/// Delay before restarting a background task that stopped on its own.
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
+/// Tells the server and the background tasks that the application is
+/// stopping. Clones share the same state.
+#[derive(Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolves once the shutdown is triggered, immediately if it already was.
+    pub async fn wait(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as `self`, so this cannot fail
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+}
+
+/// Resolves on SIGINT (Ctrl+C) or, on Unix, SIGTERM.
+pub async fn wait_for_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => info!("Received SIGINT"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
+
+/// Long-running tasks owned by the application. Each task is restarted if
+/// it returns or panics before the shutdown, and is given a grace period to
+/// finish its current work once the shutdown is triggered.
+pub struct BackgroundTasks {
+    shutdown: Shutdown,
+    tasks: JoinSet<()>,
+}
+
+impl BackgroundTasks {
+    pub fn new(shutdown: Shutdown) -> Self {
+        Self {
+            shutdown,
+            tasks: JoinSet::new(),
+        }
+    }
+
+    /// Supervises `task`, which must return soon after its `Shutdown` is triggered.
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, task: F)
+    where
+        F: Fn(Shutdown) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let shutdown = self.shutdown.clone();
+
+        self.tasks.spawn(async move {
+            loop {
+                info!("Starting background task '{}'", name);
+
+                // A set rather than a bare handle, so that aborting the supervisor
+                // also aborts the run it is waiting on
+                let mut run = JoinSet::new();
+                run.spawn(task(shutdown.clone()));
+
+                match run.join_next().await {
+                    Some(Ok(())) if shutdown.is_triggered() => break,
+                    Some(Err(e)) => error!("Background task '{}' failed: {}", name, e),
+                    _ => warn!("Background task '{}' stopped unexpectedly", name),
+                }
+
+                tokio::select! {
+                    _ = shutdown.wait() => break,
+                    _ = tokio::time::sleep(RESTART_DELAY) => {}
+                }
+            }
+
+            info!("Background task '{}' stopped", name);
+        });
+    }
+
+    /// Triggers the shutdown and waits up to `timeout` for every task to
+    /// stop, aborting the ones still running after it.
+    pub async fn stop(mut self, timeout: Duration) {
+        self.shutdown.trigger();
+
+        let tasks = &mut self.tasks;
+        let all_stopped = async { while tasks.join_next().await.is_some() {} };
+
+        if tokio::time::timeout(timeout, all_stopped).await.is_err() {
+            warn!(
+                "Aborting {} background tasks still running after {}s",
+                self.tasks.len(),
+                timeout.as_secs()
+            );
+            self.tasks.shutdown().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_failed_tasks_until_shutdown() {
+        let shutdown = Shutdown::new();
+        let mut background_tasks = BackgroundTasks::new(shutdown.clone());
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let task_runs = runs.clone();
+        background_tasks.spawn("flaky", move |shutdown| {
+            let run = task_runs.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if run == 0 {
+                    panic!("first run fails");
+                }
+                shutdown.wait().await;
+            }
+        });
+
+        tokio::time::sleep(RESTART_DELAY * 2).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        background_tasks.stop(Duration::from_secs(1)).await;
+        assert!(shutdown.is_triggered());
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn aborts_tasks_that_ignore_the_shutdown() {
+        let mut background_tasks = BackgroundTasks::new(Shutdown::new());
+        background_tasks.spawn("stubborn", |_| std::future::pending::<()>());
+
+        let started_at = tokio::time::Instant::now();
+        background_tasks.stop(Duration::from_secs(3)).await;
+
+        assert_eq!(started_at.elapsed(), Duration::from_secs(3));
+    }
+}
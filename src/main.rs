@@ -1,6 +1,8 @@
 mod app;
 mod config;
 mod database;
+mod error;
+mod lifecycle;
 mod services;
 mod web;
 
@@ -11,6 +13,20 @@ use log::error;
 async fn main() {
     env_logger::init();
 
+    // `indexes [--check]` creates or checks the MongoDB indexes, then exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("indexes") {
+        let check_only = args.iter().any(|arg| arg == "--check");
+        match app::sync_indexes(check_only).await {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(2),
+            Err(e) => {
+                error!("Failed to sync indexes: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
     let app = match App::new().await {
         Ok(app) => app,
         Err(e) => {
@@ -0,0 +1,231 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::Utc;
+use log::{error, info};
+use mongodb::bson::oid::ObjectId;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::database::repositories::api_key_repository::ApiKeyRepository;
+use crate::database::repositories::models::api_key_repository_models::{
+    ApiKeyDocument, ApiKeyRole,
+};
+use crate::error::AppError;
+
+/// Marks issued keys, so that leaked ones are easy to recognize.
+const KEY_PREFIX: &str = "snk_";
+const KEY_RANDOM_BYTES: usize = 32;
+/// Characters of a key kept in clear as `key_prefix`.
+const DISPLAYED_KEY_LENGTH: usize = 12;
+const MAX_KEY_NAME_LENGTH: usize = 100;
+
+/// How long an authentication result is reused. Other instances may accept a
+/// revoked key for this long.
+const AUTHENTICATION_TTL: Duration = Duration::from_secs(30);
+/// Cached results, unknown keys included, before the cache is emptied.
+const MAX_CACHED_AUTHENTICATIONS: usize = 10_000;
+
+/// Name reported for requests made with the configured admin key.
+pub const ADMIN_KEY_CALLER: &str = "bootstrap-admin";
+
+/// Key holder a request was authenticated as.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// Id of the stored key, `None` for the configured admin key.
+    pub key_id: Option<ObjectId>,
+    pub name: String,
+    pub role: ApiKeyRole,
+}
+
+/// A newly issued key. `key` is only available at this point.
+#[derive(Debug, Clone)]
+pub struct IssuedApiKey {
+    pub api_key: ApiKeyDocument,
+    pub key: String,
+}
+
+/// Key hashes resolved recently, with when the result expires.
+type AuthenticationCache = HashMap<String, (Instant, Option<Caller>)>;
+
+#[derive(Clone)]
+pub struct ApiKeyService {
+    api_key_repository: Arc<dyn ApiKeyRepository>,
+    admin_key_hash: Option<String>,
+    authentications: Arc<Mutex<AuthenticationCache>>,
+}
+
+impl ApiKeyService {
+    pub fn new(api_key_repository: Arc<dyn ApiKeyRepository>, admin_key: Option<&str>) -> Self {
+        info!("Created ApiKeyService");
+        Self {
+            api_key_repository,
+            admin_key_hash: admin_key.map(hash_key),
+            authentications: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub async fn issue_api_key(
+        &self,
+        name: &str,
+        role: ApiKeyRole,
+        created_by: &str,
+    ) -> Result<IssuedApiKey, AppError> {
+        info!("Issuing {} API key '{}'", role.as_str(), name);
+
+        let name = name.trim();
+        if name.is_empty() || name.len() > MAX_KEY_NAME_LENGTH {
+            return Err(AppError::invalid_param(
+                "name",
+                format!(
+                    "name must be between 1 and {} characters",
+                    MAX_KEY_NAME_LENGTH
+                ),
+            ));
+        }
+
+        let random_bytes: [u8; KEY_RANDOM_BYTES] = rand::random();
+        let key = format!("{}{}", KEY_PREFIX, URL_SAFE_NO_PAD.encode(random_bytes));
+
+        let mut api_key = ApiKeyDocument {
+            id: None,
+            name: name.to_string(),
+            role,
+            key_prefix: key[..DISPLAYED_KEY_LENGTH].to_string(),
+            key_hash: hash_key(&key),
+            created_by: created_by.to_string(),
+            created_at: Utc::now(),
+            revoked_at: None,
+        };
+
+        let api_key_id = self
+            .api_key_repository
+            .insert(&api_key)
+            .await
+            .map_err(|e| {
+                error!("Failed to insert API key '{}': {}", name, e);
+                AppError::from(e)
+            })?;
+        api_key.id = Some(api_key_id);
+
+        info!("Successfully issued API key {}", api_key_id);
+
+        Ok(IssuedApiKey { api_key, key })
+    }
+
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKeyDocument>, AppError> {
+        info!("Getting all API keys");
+
+        let api_keys = self.api_key_repository.list_api_keys().await.map_err(|e| {
+            error!("Failed to get API keys: {}", e);
+            AppError::from(e)
+        })?;
+
+        info!("Successfully retrieved {} API keys", api_keys.len());
+
+        Ok(api_keys)
+    }
+
+    /// Revokes a key, returning `None` if it does not exist.
+    pub async fn revoke_api_key(
+        &self,
+        api_key_id: ObjectId,
+    ) -> Result<Option<ApiKeyDocument>, AppError> {
+        info!("Revoking API key {}", api_key_id);
+
+        let revoked = self
+            .api_key_repository
+            .revoke(api_key_id, Utc::now())
+            .await
+            .map_err(|e| {
+                error!("Failed to revoke API key {}: {}", api_key_id, e);
+                AppError::from(e)
+            })?;
+
+        if revoked.is_some() {
+            self.authentications().clear();
+            return Ok(revoked);
+        }
+
+        match self.api_key_repository.find_by_id(api_key_id).await? {
+            Some(_) => Err(AppError::conflict(format!(
+                "API key {} is already revoked",
+                api_key_id
+            ))),
+            _none => Ok(None),
+        }
+    }
+
+    /// Resolves the holder of `key`, or `None` if the key is unknown or revoked.
+    pub async fn authenticate(&self, key: &str) -> Result<Option<Caller>, AppError> {
+        if let Some(caller) = self.cached_authentication(key) {
+            return Ok(caller);
+        }
+
+        let key_hash = hash_key(key);
+        let api_key = self
+            .api_key_repository
+            .find_by_hash(&key_hash)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up API key: {}", e);
+                AppError::from(e)
+            })?;
+
+        let caller = api_key
+            .filter(|api_key| api_key.revoked_at.is_none())
+            .map(|api_key| Caller {
+                key_id: api_key.id,
+                name: api_key.name,
+                role: api_key.role,
+            });
+
+        let mut authentications = self.authentications();
+        if authentications.len() >= MAX_CACHED_AUTHENTICATIONS {
+            let now = Instant::now();
+            authentications.retain(|_, (expires_at, _)| *expires_at > now);
+            if authentications.len() >= MAX_CACHED_AUTHENTICATIONS {
+                authentications.clear();
+            }
+        }
+        authentications.insert(
+            key_hash,
+            (Instant::now() + AUTHENTICATION_TTL, caller.clone()),
+        );
+
+        Ok(caller)
+    }
+
+    /// Resolves `key` without a database lookup: the admin key, or a key
+    /// authenticated recently. `None` if the key has to be looked up.
+    pub fn cached_authentication(&self, key: &str) -> Option<Option<Caller>> {
+        let key_hash = hash_key(key);
+
+        if self.admin_key_hash.as_deref() == Some(key_hash.as_str()) {
+            return Some(Some(Caller {
+                key_id: None,
+                name: ADMIN_KEY_CALLER.to_string(),
+                role: ApiKeyRole::Admin,
+            }));
+        }
+
+        match self.authentications().get(&key_hash) {
+            Some((expires_at, caller)) if *expires_at > Instant::now() => Some(caller.clone()),
+            _ => None,
+        }
+    }
+
+    fn authentications(&self) -> std::sync::MutexGuard<'_, AuthenticationCache> {
+        self.authentications
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Hex-encoded SHA-256 of a key. Keys are long random strings, so a fast
+/// unsalted hash is enough to make the stored hashes useless to an attacker.
+fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
@@ -1,6 +1,29 @@
-use crate::database::ArticleRepository;
-use crate::database::repositories::models::article_repository_models::ArticleDocument;
+use crate::database::repositories::models::article_prediction_repository_models::ArticlePredictionsDocument;
+use crate::database::repositories::models::article_repository_models::{
+    ArticleDocument, PredictionFilter, SourceDocument,
+};
+use crate::database::repositories::models::pagination_models::PageCursor;
+use crate::database::{ArticlePredictionsRepository, ArticleRepository};
+use crate::error::AppError;
+use crate::services::canonical_url;
+use chrono::{DateTime, Duration, Utc};
 use log::{error, info};
+use mongodb::bson::oid::ObjectId;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Number of characters kept on each side of the first match in a highlight snippet.
+const SNIPPET_RADIUS: usize = 80;
+
+const MAX_TITLE_LENGTH: usize = 1000;
+const MAX_DESCRIPTION_LENGTH: usize = 10_000;
+const MAX_CONTENT_LENGTH: usize = 100_000;
+
+/// Placeholder NewsAPI returns for articles taken down by their publisher.
+const REMOVED_ARTICLE_TITLE: &str = "[Removed]";
+
+/// Tolerated clock skew of sources when checking `published_at`.
+const MAX_PUBLISHED_AT_SKEW_HOURS: i64 = 24;
 
 #[derive(Debug, Clone)]
 pub struct PaginatedArticlesWithSentiment {
@@ -10,68 +33,157 @@ pub struct PaginatedArticlesWithSentiment {
     pub page: u64,
     pub per_page: i64,
     pub total_pages: u64,
+    pub next_cursor: Option<PageCursor>,
+    pub prev_cursor: Option<PageCursor>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArticleWithPredictions {
+    pub article: ArticleDocument,
+    pub predictions: Vec<ArticlePredictionsDocument>,
+}
+
+/// An article as sources such as NewsAPI publish it.
+#[derive(Debug, Clone)]
+pub struct NewArticle {
+    pub source: SourceDocument,
+    pub author: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub url: String,
+    pub url_to_image: Option<String>,
+    pub published_at: DateTime<Utc>,
+    /// Set when the source gave no date and `published_at` is the time the
+    /// article was seen, which must not move on later updates.
+    pub published_at_estimated: bool,
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpsertedArticle {
+    pub index: usize,
+    pub id: ObjectId,
+    /// Whether the article was new, rather than an update of a known URL.
+    pub created: bool,
+    pub url: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArticleIngestionFailure {
+    pub index: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ArticlesIngestionResult {
+    pub upserted: Vec<UpsertedArticle>,
+    pub failures: Vec<ArticleIngestionFailure>,
 }
 
 #[derive(Clone)]
 pub struct ArticleService {
-    article_repository: ArticleRepository,
+    article_repository: Arc<dyn ArticleRepository>,
+    article_predictions_repository: Arc<dyn ArticlePredictionsRepository>,
 }
 
 impl ArticleService {
-    pub fn new(article_repository: ArticleRepository) -> Self {
+    pub fn new(
+        article_repository: Arc<dyn ArticleRepository>,
+        article_predictions_repository: Arc<dyn ArticlePredictionsRepository>,
+    ) -> Self {
         info!("Created ArticleService");
-        Self { article_repository }
+        Self {
+            article_repository,
+            article_predictions_repository,
+        }
     }
 
-    pub async fn get_articles_with_sentiment(
+    pub async fn get_article_with_predictions(
         &self,
-        limit: Option<i64>,
-        skip: Option<u64>,
-        sentiment: Option<&str>,
-    ) -> Result<PaginatedArticlesWithSentiment, Box<dyn std::error::Error>> {
-        info!("Getting articles with all predictions");
+        article_id: ObjectId,
+        prediction_type: Option<&str>,
+    ) -> Result<Option<ArticleWithPredictions>, AppError> {
+        info!("Getting article {} with predictions", article_id);
 
-        let paginated_articles = self
+        let article = match self
             .article_repository
-            .list_articles_with_all_predictions(limit, skip, sentiment)
+            .find_by_id(article_id)
             .await
             .map_err(|e| {
-                error!("Failed to get articles with all predictions: {}", e);
-                Box::new(e) as Box<dyn std::error::Error>
-            })?;
+                error!("Failed to get article {}: {}", article_id, e);
+                AppError::from(e)
+            })? {
+            Some(article) => article,
+            _none => return Ok(None),
+        };
+
+        let predictions = match prediction_type {
+            Some(prediction_type) => self
+                .article_predictions_repository
+                .find_by_article_id_and_prediction_type(article_id, prediction_type)
+                .await
+                .map(|prediction| prediction.into_iter().collect()),
+            _none => {
+                self.article_predictions_repository
+                    .find_by_article_id(article_id)
+                    .await
+            }
+        }
+        .map_err(|e| {
+            error!(
+                "Failed to get predictions for article {}: {}",
+                article_id, e
+            );
+            AppError::from(e)
+        })?;
 
         info!(
-            "Successfully retrieved {} articles with all predictions",
-            paginated_articles.articles.len()
+            "Successfully retrieved article {} with {} predictions",
+            article_id,
+            predictions.len()
         );
 
-        Ok(PaginatedArticlesWithSentiment {
-            articles: paginated_articles.articles,
-            total_count: paginated_articles.total_count,
-            current_page_count: paginated_articles.current_page_count,
-            page: paginated_articles.page,
-            per_page: paginated_articles.per_page,
-            total_pages: paginated_articles.total_pages,
-        })
+        Ok(Some(ArticleWithPredictions {
+            article,
+            predictions,
+        }))
     }
 
     pub async fn get_articles_with_all_predictions(
         &self,
         limit: Option<i64>,
         skip: Option<u64>,
-        sentiment: Option<&str>,
-    ) -> Result<PaginatedArticlesWithSentiment, Box<dyn std::error::Error>> {
+        page_cursor: Option<&PageCursor>,
+        search_query: Option<&str>,
+        source: Option<&str>,
+        prediction_filters: &[PredictionFilter],
+    ) -> Result<PaginatedArticlesWithSentiment, AppError> {
         info!("Getting articles with all predictions");
 
-        let paginated_articles = self
+        let mut paginated_articles = self
             .article_repository
-            .list_articles_with_all_predictions(limit, skip, sentiment)
+            .list_articles_with_all_predictions(
+                limit,
+                skip,
+                page_cursor,
+                search_query,
+                source,
+                prediction_filters,
+            )
             .await
             .map_err(|e| {
                 error!("Failed to get articles with all predictions: {}", e);
-                Box::new(e) as Box<dyn std::error::Error>
+                AppError::from(e)
             })?;
 
+        if let Some(search_query) = search_query {
+            let terms = search_terms(search_query);
+
+            for article in paginated_articles.articles.iter_mut() {
+                article.search_highlights = Some(highlight_article(article, &terms));
+            }
+        }
+
         info!(
             "Successfully retrieved {} articles with all predictions (including news_classification, sentiment_analysis, etc.)",
             paginated_articles.articles.len()
@@ -84,6 +196,224 @@ impl ArticleService {
             page: paginated_articles.page,
             per_page: paginated_articles.per_page,
             total_pages: paginated_articles.total_pages,
+            next_cursor: paginated_articles.next_cursor,
+            prev_cursor: paginated_articles.prev_cursor,
         })
     }
+
+    /// Stores articles under their canonical URL, updating the article
+    /// already known for a URL instead of adding a duplicate. Upserts are
+    /// idempotent, so a batch that failed midway can be resent as is.
+    pub async fn ingest_articles(
+        &self,
+        articles: Vec<(usize, NewArticle)>,
+    ) -> Result<ArticlesIngestionResult, AppError> {
+        info!("Ingesting {} articles", articles.len());
+
+        let now = Utc::now();
+        let mut result = ArticlesIngestionResult::default();
+
+        for (index, article) in articles {
+            let url = match validate_new_article(&article, now) {
+                Ok(url) => url,
+                Err(message) => {
+                    result
+                        .failures
+                        .push(ArticleIngestionFailure { index, message });
+                    continue;
+                }
+            };
+
+            let url_hash = canonical_url::url_hash(&url);
+            let document = ArticleDocument {
+                id: None,
+                source: article.source,
+                author: article.author,
+                title: Some(article.title.trim().to_string()),
+                description: article.description,
+                url: Some(url.clone()),
+                url_to_image: article.url_to_image,
+                url_hash: Some(url_hash.clone()),
+                published_at: article.published_at,
+                content: article.content,
+                created_at: now,
+                updated_at: now,
+                predictions: None,
+                sentiment_analysis: None,
+                search_score: None,
+                search_highlights: None,
+            };
+
+            let upsert = self
+                .article_repository
+                .upsert_by_url_hash(&url_hash, &document, article.published_at_estimated)
+                .await
+                .map_err(|e| {
+                    error!("Failed to upsert article {}: {}", url, e);
+                    AppError::from(e)
+                })?;
+
+            result.upserted.push(UpsertedArticle {
+                index,
+                id: upsert.id,
+                created: upsert.created,
+                url,
+            });
+        }
+
+        info!(
+            "Successfully ingested {} articles ({} new), {} rejected",
+            result.upserted.len(),
+            result
+                .upserted
+                .iter()
+                .filter(|article| article.created)
+                .count(),
+            result.failures.len()
+        );
+
+        Ok(result)
+    }
+}
+
+/// Checks an article and returns its cleaned URL.
+fn validate_new_article(article: &NewArticle, now: DateTime<Utc>) -> Result<String, String> {
+    let title = article.title.trim();
+
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LENGTH {
+        return Err(format!(
+            "title must contain between 1 and {} characters",
+            MAX_TITLE_LENGTH
+        ));
+    }
+
+    if title == REMOVED_ARTICLE_TITLE {
+        return Err("the article was removed by its publisher".to_string());
+    }
+
+    if article.source.name.trim().is_empty() {
+        return Err("source.name must not be empty".to_string());
+    }
+
+    if article
+        .description
+        .as_ref()
+        .is_some_and(|description| description.chars().count() > MAX_DESCRIPTION_LENGTH)
+    {
+        return Err(format!(
+            "description must not exceed {} characters",
+            MAX_DESCRIPTION_LENGTH
+        ));
+    }
+
+    if article
+        .content
+        .as_ref()
+        .is_some_and(|content| content.chars().count() > MAX_CONTENT_LENGTH)
+    {
+        return Err(format!(
+            "content must not exceed {} characters",
+            MAX_CONTENT_LENGTH
+        ));
+    }
+
+    if article.published_at > now + Duration::hours(MAX_PUBLISHED_AT_SKEW_HOURS) {
+        return Err("published_at must not be in the future".to_string());
+    }
+
+    canonical_url::clean(&article.url)
+}
+
+/// Extracts the positive terms of a MongoDB `$text` search string, dropping
+/// negations (`-term`) and quotes around phrases.
+fn search_terms(search_query: &str) -> Vec<Vec<char>> {
+    search_query
+        .split_whitespace()
+        .filter(|term| !term.starts_with('-'))
+        .map(|term| term.trim_matches('"'))
+        .filter(|term| !term.is_empty())
+        .map(|term| term.chars().map(lowercase_char).collect())
+        .collect()
+}
+
+fn highlight_article(article: &ArticleDocument, terms: &[Vec<char>]) -> HashMap<String, String> {
+    [
+        ("title", &article.title),
+        ("description", &article.description),
+        ("content", &article.content),
+    ]
+    .into_iter()
+    .filter_map(|(field, text)| {
+        let snippet = highlight_snippet(text.as_deref()?, terms)?;
+        Some((field.to_string(), snippet))
+    })
+    .collect()
+}
+
+/// Builds an HTML-escaped snippet around the first match, wrapping every match in `<mark>`.
+fn highlight_snippet(text: &str, terms: &[Vec<char>]) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let lowered: Vec<char> = chars.iter().map(|c| lowercase_char(*c)).collect();
+
+    // Matches are anchored at word starts so that stems like "crash" still hit "crashes"
+    let match_length_at = |position: usize| {
+        if position > 0 && lowered[position - 1].is_alphanumeric() {
+            return None;
+        }
+
+        terms
+            .iter()
+            .filter(|term| lowered[position..].starts_with(term))
+            .map(|term| term.len())
+            .max()
+    };
+
+    let first_match = (0..lowered.len()).find(|&position| match_length_at(position).is_some())?;
+
+    let start = first_match.saturating_sub(SNIPPET_RADIUS);
+    let end = (first_match + SNIPPET_RADIUS).min(chars.len());
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+
+    let mut position = start;
+    while position < end {
+        match match_length_at(position) {
+            Some(length) => {
+                let match_end = (position + length).min(chars.len());
+                snippet.push_str("<mark>");
+                push_escaped(&mut snippet, &chars[position..match_end]);
+                snippet.push_str("</mark>");
+                position = match_end;
+            }
+            _none => {
+                push_escaped(&mut snippet, &chars[position..position + 1]);
+                position += 1;
+            }
+        }
+    }
+
+    if position < chars.len() {
+        snippet.push('…');
+    }
+
+    Some(snippet)
+}
+
+fn lowercase_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn push_escaped(output: &mut String, chars: &[char]) {
+    for c in chars {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            _ => output.push(*c),
+        }
+    }
 }
@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::time::Instant;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CacheStats {
+    #[schema(value_type = String)]
+    pub name: &'static str,
+    /// 0 when caching is disabled.
+    pub ttl_seconds: u64,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+}
+
+struct Entries<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    /// Bumped by `invalidate`, so that values loaded before an invalidation
+    /// are not stored after it.
+    generation: u64,
+}
+
+struct Inner<K, V> {
+    name: &'static str,
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<Entries<K, V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// In-process cache of query results that expire after a fixed TTL. A zero
+/// TTL disables it. Clones share their entries.
+pub struct TtlCache<K, V> {
+    inner: Arc<Inner<K, V>>,
+}
+
+impl<K, V> Clone for TtlCache<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(name: &'static str, ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                name,
+                ttl,
+                max_entries,
+                entries: Mutex::new(Entries {
+                    entries: HashMap::new(),
+                    generation: 0,
+                }),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Returns the cached value of `key`, or loads and caches it. Errors are
+    /// not cached.
+    pub async fn get_or_try_load<E>(
+        &self,
+        key: K,
+        load: impl Future<Output = Result<V, E>>,
+    ) -> Result<V, E> {
+        if self.inner.ttl.is_zero() {
+            return load.await;
+        }
+
+        let generation = {
+            let entries = self.lock();
+            let now = Instant::now();
+            if let Some(entry) = entries
+                .entries
+                .get(&key)
+                .filter(|entry| entry.expires_at > now)
+            {
+                self.inner.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(entry.value.clone());
+            }
+            entries.generation
+        };
+
+        self.inner.misses.fetch_add(1, Ordering::Relaxed);
+        let value = load.await?;
+
+        let now = Instant::now();
+        let mut entries = self.lock();
+        if entries.generation == generation {
+            if entries.entries.len() >= self.inner.max_entries {
+                entries.entries.retain(|_, entry| entry.expires_at > now);
+            }
+            // Past this point new keys are served uncached until entries expire
+            if entries.entries.len() < self.inner.max_entries {
+                entries.entries.insert(
+                    key,
+                    Entry {
+                        value: value.clone(),
+                        expires_at: now + self.inner.ttl,
+                    },
+                );
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// Drops every entry, including values being loaded.
+    pub fn invalidate(&self) {
+        let mut entries = self.lock();
+        entries.entries.clear();
+        entries.generation += 1;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            name: self.inner.name,
+            ttl_seconds: self.inner.ttl.as_secs(),
+            entries: self.lock().entries.len(),
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries<K, V>> {
+        self.inner
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,334 @@
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use mongodb::bson::oid::ObjectId;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::database::repositories::deployment_repository::DeploymentRepository;
+use crate::database::repositories::models::deployment_repository_models::{
+    ActiveDeploymentDocument, DeploymentDocument, DeploymentHistoryDocument, TrafficSplitUpdate,
+};
+use crate::database::repositories::models::predictor_repository_models::PredictorDocument;
+use crate::database::repositories::predictors_repository::PredictorRepository;
+use crate::error::AppError;
+use crate::services::predictor_service::PredictorService;
+
+#[derive(Debug, Clone)]
+pub struct ActiveDeploymentDetails {
+    pub predictor_id: ObjectId,
+    pub traffic_percentage: f64,
+    pub predictor: Option<PredictorDocument>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeploymentDetails {
+    pub id: Option<ObjectId>,
+    pub prediction_type: String,
+    pub active_deployments: Vec<ActiveDeploymentDetails>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrafficSplit {
+    pub predictor_id: ObjectId,
+    pub traffic_percentage: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrafficSplitChange {
+    pub splits: Vec<TrafficSplit>,
+    pub changed_by: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct DeploymentService {
+    deployment_repository: Arc<dyn DeploymentRepository>,
+    predictor_repository: Arc<dyn PredictorRepository>,
+    predictor_service: PredictorService,
+}
+
+impl DeploymentService {
+    pub fn new(
+        deployment_repository: Arc<dyn DeploymentRepository>,
+        predictor_repository: Arc<dyn PredictorRepository>,
+        predictor_service: PredictorService,
+    ) -> Self {
+        info!("Created DeploymentService");
+        Self {
+            deployment_repository,
+            predictor_repository,
+            predictor_service,
+        }
+    }
+
+    pub async fn list_deployments(&self) -> Result<Vec<DeploymentDetails>, AppError> {
+        info!("Getting all deployments");
+
+        let deployments = self
+            .deployment_repository
+            .list_deployments()
+            .await
+            .map_err(|e| {
+                error!("Failed to get deployments: {}", e);
+                AppError::from(e)
+            })?;
+
+        let deployments = self.with_predictor_details(deployments).await?;
+
+        info!("Successfully retrieved {} deployments", deployments.len());
+
+        Ok(deployments)
+    }
+
+    pub async fn get_deployment(
+        &self,
+        prediction_type: &str,
+    ) -> Result<Option<DeploymentDetails>, AppError> {
+        info!(
+            "Getting deployment for prediction type '{}'",
+            prediction_type
+        );
+
+        let deployment = self
+            .deployment_repository
+            .find_by_prediction_type(prediction_type)
+            .await
+            .map_err(|e| {
+                error!("Failed to get deployment for '{}': {}", prediction_type, e);
+                AppError::from(e)
+            })?;
+
+        match deployment {
+            Some(deployment) => Ok(self.with_predictor_details(vec![deployment]).await?.pop()),
+            _none => Ok(None),
+        }
+    }
+
+    pub async fn get_deployment_history(
+        &self,
+        prediction_type: &str,
+        limit: Option<i64>,
+        skip: Option<u64>,
+    ) -> Result<Vec<DeploymentHistoryDocument>, AppError> {
+        info!(
+            "Getting deployment history for prediction type '{}'",
+            prediction_type
+        );
+
+        let history = self
+            .deployment_repository
+            .list_history(prediction_type, limit, skip)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to get deployment history for '{}': {}",
+                    prediction_type, e
+                );
+                AppError::from(e)
+            })?;
+
+        info!(
+            "Successfully retrieved {} deployment history entries for '{}'",
+            history.len(),
+            prediction_type
+        );
+
+        Ok(history)
+    }
+
+    pub async fn update_traffic_split(
+        &self,
+        prediction_type: &str,
+        change: TrafficSplitChange,
+    ) -> Result<DeploymentDetails, AppError> {
+        info!(
+            "Updating traffic split for prediction type '{}' on behalf of '{}'",
+            prediction_type, change.changed_by
+        );
+
+        validate_traffic_split(&change)?;
+
+        let predictor_ids: Vec<ObjectId> = change
+            .splits
+            .iter()
+            .map(|split| split.predictor_id)
+            .collect();
+
+        let predictors = self
+            .predictor_repository
+            .find_by_ids(&predictor_ids)
+            .await
+            .map_err(|e| {
+                error!("Failed to get predictors of traffic split: {}", e);
+                AppError::from(e)
+            })?;
+
+        validate_split_predictors(prediction_type, &predictor_ids, &predictors)?;
+
+        let update = TrafficSplitUpdate {
+            prediction_type: prediction_type.to_string(),
+            active_deployments: change
+                .splits
+                .iter()
+                .filter(|split| split.traffic_percentage > 0)
+                .map(|split| ActiveDeploymentDocument {
+                    predictor_id: split.predictor_id,
+                    traffic_percentage: split.traffic_percentage as f64,
+                })
+                .collect(),
+            traffic_percentages: change
+                .splits
+                .iter()
+                .map(|split| (split.predictor_id, split.traffic_percentage))
+                .collect(),
+            changed_by: change.changed_by,
+            reason: change.reason,
+            updated_at: Utc::now(),
+        };
+
+        self.deployment_repository
+            .apply_traffic_split(&update)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to apply traffic split for '{}': {}",
+                    prediction_type, e
+                );
+                AppError::from(e)
+            })?;
+
+        // The split is written to the predictors as well
+        self.predictor_service.invalidate_cache();
+
+        info!(
+            "Successfully updated traffic split for prediction type '{}'",
+            prediction_type
+        );
+
+        self.get_deployment(prediction_type).await?.ok_or_else(|| {
+            AppError::internal(format!(
+                "deployment '{}' vanished after update",
+                prediction_type
+            ))
+        })
+    }
+
+    async fn with_predictor_details(
+        &self,
+        deployments: Vec<DeploymentDocument>,
+    ) -> Result<Vec<DeploymentDetails>, AppError> {
+        let predictor_ids: Vec<ObjectId> = deployments
+            .iter()
+            .flat_map(|deployment| deployment.active_deployments.iter())
+            .map(|active_deployment| active_deployment.predictor_id)
+            .collect();
+
+        let predictors: HashMap<ObjectId, PredictorDocument> = self
+            .predictor_repository
+            .find_by_ids(&predictor_ids)
+            .await
+            .map_err(|e| {
+                error!("Failed to get predictors of deployments: {}", e);
+                AppError::from(e)
+            })?
+            .into_iter()
+            .filter_map(|predictor| predictor.id.map(|id| (id, predictor)))
+            .collect();
+
+        Ok(deployments
+            .into_iter()
+            .map(|deployment| DeploymentDetails {
+                id: deployment.id,
+                prediction_type: deployment.prediction_type,
+                active_deployments: deployment
+                    .active_deployments
+                    .into_iter()
+                    .map(|active_deployment| ActiveDeploymentDetails {
+                        predictor_id: active_deployment.predictor_id,
+                        traffic_percentage: active_deployment.traffic_percentage,
+                        predictor: predictors.get(&active_deployment.predictor_id).cloned(),
+                    })
+                    .collect(),
+                created_at: deployment.created_at,
+                updated_at: deployment.updated_at,
+            })
+            .collect())
+    }
+}
+
+fn validate_traffic_split(change: &TrafficSplitChange) -> Result<(), AppError> {
+    if change.splits.is_empty() {
+        return Err(AppError::invalid_param(
+            "splits",
+            "at least one predictor is required",
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    for split in &change.splits {
+        if !(0..=100).contains(&split.traffic_percentage) {
+            return Err(AppError::invalid_param(
+                "splits",
+                format!(
+                    "traffic of predictor {} must be between 0 and 100",
+                    split.predictor_id
+                ),
+            ));
+        }
+
+        if !seen.insert(split.predictor_id) {
+            return Err(AppError::invalid_param(
+                "splits",
+                format!("predictor {} is listed more than once", split.predictor_id),
+            ));
+        }
+    }
+
+    let total: i32 = change
+        .splits
+        .iter()
+        .map(|split| split.traffic_percentage)
+        .sum();
+    if total != 100 {
+        return Err(AppError::invalid_param(
+            "splits",
+            format!("traffic percentages sum to {} instead of 100", total),
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_split_predictors(
+    prediction_type: &str,
+    predictor_ids: &[ObjectId],
+    predictors: &[PredictorDocument],
+) -> Result<(), AppError> {
+    for predictor_id in predictor_ids {
+        match predictors
+            .iter()
+            .find(|predictor| predictor.id.as_ref() == Some(predictor_id))
+        {
+            Some(predictor) if predictor.prediction_type != prediction_type => {
+                return Err(AppError::invalid_param(
+                    "splits",
+                    format!(
+                        "predictor {} serves '{}', not '{}'",
+                        predictor_id, predictor.prediction_type, prediction_type
+                    ),
+                ));
+            }
+            Some(_) => {}
+            _none => {
+                return Err(AppError::invalid_param(
+                    "splits",
+                    format!("predictor {} does not exist", predictor_id),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
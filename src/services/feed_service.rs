@@ -0,0 +1,652 @@
+use chrono::{DateTime, Utc};
+use feed_rs::model::{Entry, Feed};
+use log::{error, info, warn};
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Client, Response, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+use crate::config::FeedsConfig;
+use crate::database::repositories::feed_source_repository::FeedSourceRepository;
+use crate::database::repositories::models::article_repository_models::SourceDocument;
+use crate::database::repositories::models::feed_source_repository_models::{
+    FeedFetchOutcome, FeedSourceDocument,
+};
+use crate::error::{AppError, is_duplicate_key};
+use crate::lifecycle::Shutdown;
+use crate::services::article_service::{ArticleService, NewArticle};
+
+const MAX_FEED_NAME_LENGTH: usize = 200;
+
+/// Feed registry, and the worker that polls its feeds and stores their
+/// entries through `ArticleService`, deduplicated on their canonical URL.
+#[derive(Clone)]
+pub struct FeedService {
+    feed_source_repository: Arc<dyn FeedSourceRepository>,
+    article_service: ArticleService,
+    client: Client,
+    config: FeedsConfig,
+}
+
+impl FeedService {
+    pub fn new(
+        feed_source_repository: Arc<dyn FeedSourceRepository>,
+        article_service: ArticleService,
+        config: FeedsConfig,
+    ) -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.request_timeout_seconds))
+            .user_agent(concat!("smart-news-backend/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        info!("Created FeedService");
+
+        Ok(Self {
+            feed_source_repository,
+            article_service,
+            client,
+            config,
+        })
+    }
+
+    pub async fn add_feed_source(
+        &self,
+        url: &str,
+        name: Option<String>,
+        source_id: Option<String>,
+    ) -> Result<FeedSourceDocument, AppError> {
+        info!("Adding feed source {}", url);
+
+        let url = url.trim();
+        match url::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+            _ => {
+                return Err(AppError::invalid_param(
+                    "url",
+                    "url must be an absolute http(s) URL",
+                ));
+            }
+        }
+
+        let name = name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        if name
+            .as_ref()
+            .is_some_and(|name| name.chars().count() > MAX_FEED_NAME_LENGTH)
+        {
+            return Err(AppError::invalid_param(
+                "name",
+                format!("name must not exceed {} characters", MAX_FEED_NAME_LENGTH),
+            ));
+        }
+
+        if self
+            .feed_source_repository
+            .find_by_url(url)
+            .await
+            .map_err(|e| {
+                error!("Failed to look up feed source {}: {}", url, e);
+                AppError::from(e)
+            })?
+            .is_some()
+        {
+            return Err(AppError::conflict(format!(
+                "feed {} is already registered",
+                url
+            )));
+        }
+
+        let mut feed_source = FeedSourceDocument {
+            id: None,
+            url: url.to_string(),
+            name,
+            source_id: source_id.filter(|source_id| !source_id.trim().is_empty()),
+            enabled: true,
+            etag: None,
+            last_modified: None,
+            last_fetched_at: None,
+            last_success_at: None,
+            last_error: None,
+            last_error_at: None,
+            consecutive_failures: 0,
+            created_at: Utc::now(),
+        };
+
+        let feed_source_id = self
+            .feed_source_repository
+            .insert(&feed_source)
+            .await
+            .map_err(|e| {
+                // The same feed was registered since the check above
+                if is_duplicate_key(&e) {
+                    return AppError::conflict(format!("feed {} is already registered", url));
+                }
+                error!("Failed to insert feed source {}: {}", url, e);
+                AppError::from(e)
+            })?;
+        feed_source.id = Some(feed_source_id);
+
+        info!("Successfully added feed source {}", feed_source_id);
+
+        Ok(feed_source)
+    }
+
+    pub async fn list_feed_sources(&self) -> Result<Vec<FeedSourceDocument>, AppError> {
+        info!("Getting all feed sources");
+
+        let feed_sources = self
+            .feed_source_repository
+            .list_feed_sources(false)
+            .await
+            .map_err(|e| {
+                error!("Failed to get feed sources: {}", e);
+                AppError::from(e)
+            })?;
+
+        info!("Successfully retrieved {} feed sources", feed_sources.len());
+
+        Ok(feed_sources)
+    }
+
+    /// Feeds whose last fetch failed, the longest failing first.
+    pub async fn list_failing_feed_sources(&self) -> Result<Vec<FeedSourceDocument>, AppError> {
+        let mut feed_sources: Vec<FeedSourceDocument> = self
+            .list_feed_sources()
+            .await?
+            .into_iter()
+            .filter(|feed_source| feed_source.consecutive_failures > 0)
+            .collect();
+        feed_sources.sort_by_key(|feed_source| std::cmp::Reverse(feed_source.consecutive_failures));
+
+        Ok(feed_sources)
+    }
+
+    /// Polls every enabled feed on the configured interval, starting right
+    /// away, until the shutdown is triggered.
+    pub async fn run_scheduler(self, shutdown: Shutdown) {
+        info!(
+            "Starting feed worker with a {}s interval",
+            self.config.interval_seconds
+        );
+
+        let mut ticker = tokio::time::interval(Duration::from_secs(self.config.interval_seconds));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.wait() => break,
+                _ = ticker.tick() => self.poll_feeds().await,
+            }
+        }
+
+        info!("Stopped feed worker");
+    }
+
+    /// Fetches every enabled feed once, at most `max_concurrent_fetches` at a time.
+    pub async fn poll_feeds(&self) {
+        let feed_sources = match self.feed_source_repository.list_feed_sources(true).await {
+            Ok(feed_sources) => feed_sources,
+            Err(e) => {
+                error!("Failed to get feed sources: {}", e);
+                return;
+            }
+        };
+
+        let mut pending = feed_sources.into_iter();
+        let mut running = JoinSet::new();
+
+        loop {
+            while running.len() < self.config.max_concurrent_fetches {
+                let Some(feed_source) = pending.next() else {
+                    break;
+                };
+                let feed_service = self.clone();
+                running.spawn(async move { feed_service.poll_feed(feed_source).await });
+            }
+
+            match running.join_next().await {
+                Some(Err(e)) => error!("Feed poll failed: {}", e),
+                Some(Ok(())) => {}
+                _none => break,
+            }
+        }
+    }
+
+    async fn poll_feed(&self, feed_source: FeedSourceDocument) {
+        let Some(feed_source_id) = feed_source.id else {
+            return;
+        };
+
+        let outcome = match self.fetch_feed(&feed_source).await {
+            Ok(outcome) => outcome,
+            Err(message) => {
+                warn!("Failed to poll feed {}: {}", feed_source.url, message);
+                FeedFetchOutcome::Failed(message)
+            }
+        };
+
+        if let Err(e) = self
+            .feed_source_repository
+            .record_fetch(feed_source_id, &outcome, Utc::now())
+            .await
+        {
+            error!(
+                "Failed to record the fetch of feed {}: {}",
+                feed_source.url, e
+            );
+        }
+    }
+
+    async fn fetch_feed(
+        &self,
+        feed_source: &FeedSourceDocument,
+    ) -> Result<FeedFetchOutcome, String> {
+        let mut request = self.client.get(&feed_source.url);
+        if let Some(etag) = &feed_source.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &feed_source.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+
+        if status == StatusCode::NOT_MODIFIED {
+            info!("Feed {} is not modified", feed_source.url);
+            return Ok(FeedFetchOutcome::NotModified);
+        }
+        if !status.is_success() {
+            return Err(format!("HTTP {}", status));
+        }
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+
+        let body = read_body(response, self.config.max_body_bytes).await?;
+        let feed = feed_rs::parser::Builder::new()
+            .base_uri(Some(&feed_source.url))
+            .build()
+            .parse(&body[..])
+            .map_err(|e| format!("unreadable feed: {}", e))?;
+
+        let articles = feed_articles(feed_source, feed, Utc::now());
+        let entry_count = articles.len();
+
+        let result = self
+            .article_service
+            .ingest_articles(articles.into_iter().enumerate().collect())
+            .await
+            .map_err(|e| format!("failed to store articles: {}", e))?;
+
+        info!(
+            "Fetched {} entries from feed {}: {} new, {} rejected",
+            entry_count,
+            feed_source.url,
+            result
+                .upserted
+                .iter()
+                .filter(|article| article.created)
+                .count(),
+            result.failures.len()
+        );
+
+        Ok(FeedFetchOutcome::Fetched {
+            etag,
+            last_modified,
+        })
+    }
+}
+
+/// Reads the body of a feed, failing once it exceeds `max_bytes` rather
+/// than holding an arbitrarily large response in memory.
+async fn read_body(mut response: Response, max_bytes: usize) -> Result<Vec<u8>, String> {
+    let too_large = || format!("the feed exceeds {} bytes", max_bytes);
+
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_bytes as u64)
+    {
+        return Err(too_large());
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(body)
+}
+
+/// Maps RSS items and Atom entries to articles, attributed to the feed.
+fn feed_articles(
+    feed_source: &FeedSourceDocument,
+    feed: Feed,
+    fetched_at: DateTime<Utc>,
+) -> Vec<NewArticle> {
+    let source = SourceDocument {
+        id: feed_source.source_id.clone(),
+        name: feed_source
+            .name
+            .clone()
+            .or_else(|| feed.title.map(|title| plain_text(&title.content)))
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| feed_source.url.clone()),
+    };
+
+    feed.entries
+        .into_iter()
+        .map(|entry| entry_article(entry, &source, fetched_at))
+        .collect()
+}
+
+fn entry_article(entry: Entry, source: &SourceDocument, fetched_at: DateTime<Utc>) -> NewArticle {
+    // Atom entries may link to comments or enclosures besides the article
+    let url = entry
+        .links
+        .iter()
+        .find(|link| link.rel.as_deref().is_none_or(|rel| rel == "alternate"))
+        .or(entry.links.first())
+        .map(|link| link.href.clone())
+        .unwrap_or_default();
+
+    let url_to_image = entry.media.iter().find_map(|media| {
+        media
+            .thumbnails
+            .first()
+            .map(|thumbnail| thumbnail.image.uri.clone())
+            .or_else(|| {
+                media
+                    .content
+                    .iter()
+                    .filter(|content| {
+                        content.content_type.as_ref().is_some_and(|content_type| {
+                            content_type.to_string().starts_with("image/")
+                        })
+                    })
+                    .find_map(|content| content.url.as_ref().map(|url| url.to_string()))
+            })
+    });
+
+    let published_at = entry.published.or(entry.updated);
+
+    NewArticle {
+        source: source.clone(),
+        author: entry.authors.first().map(|author| author.name.clone()),
+        title: entry
+            .title
+            .map(|title| plain_text(&title.content))
+            .unwrap_or_default(),
+        description: entry.summary.map(|summary| plain_text(&summary.content)),
+        url,
+        url_to_image,
+        published_at: published_at.unwrap_or(fetched_at),
+        published_at_estimated: published_at.is_none(),
+        content: entry.content.and_then(|content| content.body),
+    }
+}
+
+/// Strips the markup that feeds commonly embed in titles and summaries.
+fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::repositories::in_memory::InMemoryDatabase;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const RSS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:media="http://search.yahoo.com/mrss/">
+  <channel>
+    <title>BBC News</title>
+    <item>
+      <title>Markets &lt;b&gt;rally&lt;/b&gt;</title>
+      <description><![CDATA[<p>Shares rose&nbsp;sharply.</p>]]></description>
+      <link>https://www.bbc.com/news/articles/markets?at_medium=RSS</link>
+      <pubDate>Thu, 12 Jun 2025 08:30:00 GMT</pubDate>
+      <media:thumbnail url="https://ichef.bbci.co.uk/markets.jpg" width="240" height="135"/>
+    </item>
+    <item>
+      <title>Untitled link</title>
+    </item>
+  </channel>
+</rss>"#;
+
+    const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>9to5Google</title>
+  <id>urn:9to5google</id>
+  <updated>2025-06-12T10:00:00Z</updated>
+  <entry>
+    <title>Pixel update</title>
+    <id>urn:pixel-update</id>
+    <link rel="replies" href="https://9to5google.com/pixel-update/#comments"/>
+    <link rel="alternate" href="https://9to5google.com/pixel-update/"/>
+    <updated>2025-06-12T10:00:00Z</updated>
+    <author><name>Jane Doe</name></author>
+    <summary>The June update</summary>
+  </entry>
+</feed>"#;
+
+    /// Serves the RSS feed with an ETag, answering 304 when it is sent
+    /// back, the Atom feed, and a broken feed. Counts full downloads.
+    async fn feed_stub(
+        State(downloads): State<Arc<AtomicUsize>>,
+        axum::extract::Path(feed): axum::extract::Path<String>,
+        headers: HeaderMap,
+    ) -> Response {
+        match feed.as_str() {
+            "rss.xml"
+                if headers
+                    .get("if-none-match")
+                    .is_some_and(|etag| etag == "\"v1\"") =>
+            {
+                StatusCode::NOT_MODIFIED.into_response()
+            }
+            "rss.xml" => {
+                downloads.fetch_add(1, Ordering::SeqCst);
+                ([(ETAG, "\"v1\"")], RSS).into_response()
+            }
+            "atom.xml" => ATOM.into_response(),
+            _ => "<html>not a feed</html>".into_response(),
+        }
+    }
+
+    async fn serve_feeds(downloads: Arc<AtomicUsize>) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = axum::Router::new()
+            .route("/{feed}", get(feed_stub))
+            .with_state(downloads);
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        address
+    }
+
+    #[tokio::test]
+    async fn stores_feed_entries_and_skips_unchanged_feeds() {
+        let downloads = Arc::new(AtomicUsize::new(0));
+        let address = serve_feeds(downloads.clone()).await;
+
+        let db = Arc::new(InMemoryDatabase::new());
+        let article_service = ArticleService::new(db.clone(), db.clone());
+        let feed_service =
+            FeedService::new(db.clone(), article_service.clone(), FeedsConfig::default()).unwrap();
+        for feed in ["rss.xml", "atom.xml", "broken.xml"] {
+            feed_service
+                .add_feed_source(&format!("http://{}/{}", address, feed), None, None)
+                .await
+                .unwrap();
+        }
+
+        feed_service.poll_feeds().await;
+        feed_service.poll_feeds().await;
+        assert_eq!(downloads.load(Ordering::SeqCst), 1);
+
+        let articles = article_service
+            .get_articles_with_all_predictions(None, None, None, None, None, &[])
+            .await
+            .unwrap()
+            .articles;
+        assert_eq!(articles.len(), 2);
+
+        let pixel = &articles[0];
+        assert_eq!(pixel.source.name, "9to5Google");
+        assert_eq!(pixel.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(
+            pixel.url.as_deref(),
+            Some("https://9to5google.com/pixel-update/")
+        );
+
+        let markets = &articles[1];
+        assert_eq!(markets.source.name, "BBC News");
+        assert_eq!(markets.title.as_deref(), Some("Markets rally"));
+        assert_eq!(markets.description.as_deref(), Some("Shares rose sharply."));
+        assert_eq!(
+            markets.url.as_deref(),
+            Some("https://www.bbc.com/news/articles/markets")
+        );
+        assert_eq!(
+            markets.url_to_image.as_deref(),
+            Some("https://ichef.bbci.co.uk/markets.jpg")
+        );
+
+        let failing = feed_service.list_failing_feed_sources().await.unwrap();
+        assert_eq!(failing.len(), 1);
+        assert!(failing[0].url.ends_with("/broken.xml"));
+        assert_eq!(failing[0].consecutive_failures, 2);
+        assert!(
+            failing[0]
+                .last_error
+                .as_ref()
+                .unwrap()
+                .starts_with("unreadable feed")
+        );
+
+        let feeds = feed_service.list_feed_sources().await.unwrap();
+        assert_eq!(feeds[0].etag.as_deref(), Some("\"v1\""));
+        assert!(feeds[0].last_success_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn rejects_feeds_over_the_size_limit() {
+        let address = serve_feeds(Arc::new(AtomicUsize::new(0))).await;
+
+        let db = Arc::new(InMemoryDatabase::new());
+        let config = FeedsConfig {
+            max_body_bytes: ATOM.len() - 1,
+            ..FeedsConfig::default()
+        };
+        let feed_service =
+            FeedService::new(db.clone(), ArticleService::new(db.clone(), db), config).unwrap();
+        feed_service
+            .add_feed_source(&format!("http://{}/atom.xml", address), None, None)
+            .await
+            .unwrap();
+
+        feed_service.poll_feeds().await;
+
+        let failing = feed_service.list_failing_feed_sources().await.unwrap();
+        assert_eq!(failing.len(), 1);
+        assert_eq!(
+            failing[0].last_error.as_deref(),
+            Some(format!("the feed exceeds {} bytes", ATOM.len() - 1).as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_the_first_seen_date_of_undated_entries() {
+        const UNDATED: &str = r#"<rss version="2.0"><channel><title>Wire</title>
+  <item><title>Breaking</title><link>https://wire.example.com/breaking</link></item>
+</channel></rss>"#;
+
+        let db = Arc::new(InMemoryDatabase::new());
+        let article_service = ArticleService::new(db.clone(), db.clone());
+        let feed_source = FeedSourceDocument {
+            id: None,
+            url: "https://wire.example.com/rss".to_string(),
+            name: None,
+            source_id: None,
+            enabled: true,
+            etag: None,
+            last_modified: None,
+            last_fetched_at: None,
+            last_success_at: None,
+            last_error: None,
+            last_error_at: None,
+            consecutive_failures: 0,
+            created_at: Utc::now(),
+        };
+
+        let first_seen = Utc::now() - chrono::Duration::hours(1);
+        for fetched_at in [first_seen, Utc::now()] {
+            let feed = feed_rs::parser::parse(UNDATED.as_bytes()).unwrap();
+            let articles = feed_articles(&feed_source, feed, fetched_at);
+            assert!(articles[0].published_at_estimated);
+            article_service
+                .ingest_articles(articles.into_iter().enumerate().collect())
+                .await
+                .unwrap();
+        }
+
+        let articles = article_service
+            .get_articles_with_all_predictions(None, None, None, None, None, &[])
+            .await
+            .unwrap()
+            .articles;
+        assert_eq!(articles.len(), 1);
+        assert_eq!(
+            articles[0].published_at.timestamp_millis(),
+            first_seen.timestamp_millis()
+        );
+    }
+
+    #[test]
+    fn strips_markup_from_text() {
+        assert_eq!(
+            plain_text("<p>Tom &amp; Jerry</p><p>return</p>"),
+            "Tom & Jerry return"
+        );
+    }
+}
@@ -0,0 +1,506 @@
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::config::{FetcherConfig, FetcherQuery};
+use crate::database::repositories::lease_repository::LeaseRepository;
+use crate::database::repositories::models::article_repository_models::SourceDocument;
+use crate::lifecycle::Shutdown;
+use crate::services::article_service::{ArticleService, NewArticle};
+use crate::services::metrics_service::{MetricsService, NewMetric};
+
+/// `source` tag of the run metrics.
+const METRICS_SOURCE: &str = "newsapi";
+
+/// Lease taken by the instance that runs a fetch.
+const LEASE_NAME: &str = "news_fetcher";
+
+/// Body of NewsAPI responses, successful (`status` "ok") or not.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewsApiResponse {
+    status: String,
+    total_results: Option<u64>,
+    // Kept raw, so that one malformed article does not discard the page
+    #[serde(default)]
+    articles: Vec<serde_json::Value>,
+    code: Option<String>,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewsApiArticle {
+    source: SourceDocument,
+    author: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    url: String,
+    url_to_image: Option<String>,
+    published_at: DateTime<Utc>,
+    content: Option<String>,
+}
+
+impl From<NewsApiArticle> for NewArticle {
+    fn from(article: NewsApiArticle) -> Self {
+        Self {
+            source: article.source,
+            author: article.author,
+            title: article.title.unwrap_or_default(),
+            description: article.description,
+            url: article.url,
+            url_to_image: article.url_to_image,
+            published_at: article.published_at,
+            published_at_estimated: false,
+            content: article.content,
+        }
+    }
+}
+
+/// Outcome of one query in one run, recorded as `fetcher.*` metrics.
+#[derive(Debug, Clone, Default)]
+pub struct FetchStats {
+    pub query: String,
+    pub requests: u64,
+    /// Failed requests, and pages that could not be stored.
+    pub errors: u64,
+    pub fetched: u64,
+    pub created: u64,
+    pub updated: u64,
+    pub rejected: u64,
+    pub duration: Duration,
+}
+
+impl FetchStats {
+    fn metrics(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("fetcher.requests", self.requests as f64),
+            ("fetcher.errors", self.errors as f64),
+            ("fetcher.articles_fetched", self.fetched as f64),
+            ("fetcher.articles_created", self.created as f64),
+            ("fetcher.articles_updated", self.updated as f64),
+            ("fetcher.articles_rejected", self.rejected as f64),
+            (
+                "fetcher.run_duration_ms",
+                self.duration.as_secs_f64() * 1000.0,
+            ),
+        ]
+    }
+}
+
+/// Polls the queries of a NewsAPI-compatible service and stores the
+/// articles through `ArticleService`, which deduplicates them on their
+/// canonical URL. Instances take a lease before each run, so that a single
+/// one fetches per interval and the API quota is not spent once per replica.
+#[derive(Clone)]
+pub struct FetcherService {
+    client: Client,
+    config: FetcherConfig,
+    lease_repository: Arc<dyn LeaseRepository>,
+    article_service: ArticleService,
+    metrics_service: MetricsService,
+}
+
+impl FetcherService {
+    pub fn new(
+        config: FetcherConfig,
+        lease_repository: Arc<dyn LeaseRepository>,
+        article_service: ArticleService,
+        metrics_service: MetricsService,
+    ) -> Result<Self, reqwest::Error> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.request_timeout_seconds))
+            .user_agent(concat!("smart-news-backend/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        info!(
+            "Created FetcherService for {} with {} queries",
+            config.base_url,
+            config.queries.len()
+        );
+
+        Ok(Self {
+            client,
+            config,
+            lease_repository,
+            article_service,
+            metrics_service,
+        })
+    }
+
+    /// Runs every query on the configured interval, starting right away,
+    /// until the shutdown is triggered. Runs claimed by another instance are
+    /// skipped.
+    pub async fn run_scheduler(self, shutdown: Shutdown) {
+        info!(
+            "Starting news fetcher with a {}s interval",
+            self.config.interval_seconds
+        );
+
+        let mut ticker = tokio::time::interval(Duration::from_secs(self.config.interval_seconds));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.wait() => break,
+                _ = ticker.tick() => {
+                    if self.claim_run().await {
+                        self.run_once().await;
+                    }
+                }
+            }
+        }
+
+        info!("Stopped news fetcher");
+    }
+
+    /// Takes the fetch lease for a little less than the interval, so that the
+    /// next tick of the holder finds it expired while the other instances,
+    /// ticking in between, find it held.
+    async fn claim_run(&self) -> bool {
+        let lease = chrono::Duration::milliseconds(self.config.interval_seconds as i64 * 900);
+
+        match self
+            .lease_repository
+            .try_acquire(LEASE_NAME, Utc::now(), lease)
+            .await
+        {
+            Ok(true) => true,
+            Ok(false) => {
+                info!("Skipping news fetch, another instance holds the lease");
+                false
+            }
+            Err(e) => {
+                error!("Failed to take the news fetch lease: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Fetches and stores every query once, and records the stats of each.
+    pub async fn run_once(&self) -> Vec<FetchStats> {
+        let mut all_stats = Vec::new();
+
+        for query in &self.config.queries {
+            let stats = self.fetch_query(query).await;
+
+            info!(
+                "Fetched {} articles for '{}': {} new, {} updated, {} rejected, {} errors in {}ms",
+                stats.fetched,
+                stats.query,
+                stats.created,
+                stats.updated,
+                stats.rejected,
+                stats.errors,
+                stats.duration.as_millis()
+            );
+
+            self.record_stats(&stats).await;
+            all_stats.push(stats);
+        }
+
+        all_stats
+    }
+
+    async fn fetch_query(&self, query: &FetcherQuery) -> FetchStats {
+        let started = Instant::now();
+        let mut stats = FetchStats {
+            query: query.name.clone(),
+            ..FetchStats::default()
+        };
+
+        for page in 1..=self.config.max_pages {
+            stats.requests += 1;
+
+            let response = match self.fetch_page(query, page).await {
+                Ok(response) => response,
+                Err(message) => {
+                    warn!(
+                        "Failed to fetch page {} of '{}': {}",
+                        page, query.name, message
+                    );
+                    stats.errors += 1;
+                    break;
+                }
+            };
+
+            let page_length = response.articles.len();
+            stats.fetched += page_length as u64;
+
+            let mut articles = Vec::new();
+            for (index, article) in response.articles.into_iter().enumerate() {
+                match serde_json::from_value::<NewsApiArticle>(article) {
+                    Ok(article) => articles.push((index, article.into())),
+                    Err(e) => {
+                        warn!("Skipping malformed article from '{}': {}", query.name, e);
+                        stats.rejected += 1;
+                    }
+                }
+            }
+
+            match self.article_service.ingest_articles(articles).await {
+                Ok(result) => {
+                    let created = result
+                        .upserted
+                        .iter()
+                        .filter(|article| article.created)
+                        .count() as u64;
+                    stats.created += created;
+                    stats.updated += result.upserted.len() as u64 - created;
+                    stats.rejected += result.failures.len() as u64;
+                }
+                Err(e) => {
+                    error!("Failed to store articles of '{}': {}", query.name, e);
+                    stats.errors += 1;
+                    break;
+                }
+            }
+
+            let is_last_page = page_length < self.config.page_size as usize
+                || response
+                    .total_results
+                    .is_some_and(|total_results| stats.fetched >= total_results);
+            if is_last_page {
+                break;
+            }
+        }
+
+        stats.duration = started.elapsed();
+        stats
+    }
+
+    async fn fetch_page(&self, query: &FetcherQuery, page: u32) -> Result<NewsApiResponse, String> {
+        let url = format!(
+            "{}/{}",
+            self.config.base_url.trim_end_matches('/'),
+            query.endpoint.path()
+        );
+
+        let mut request = self
+            .client
+            .get(url)
+            .query(&query.params)
+            .query(&[("page", page), ("pageSize", self.config.page_size)]);
+        if let Some(api_key) = &self.config.api_key {
+            request = request.header("X-Api-Key", api_key);
+        }
+
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        let status = response.status();
+
+        let body: NewsApiResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("HTTP {}: unreadable body: {}", status, e))?;
+
+        if !status.is_success() || body.status != "ok" {
+            return Err(format!(
+                "HTTP {}: {} ({})",
+                status,
+                body.message.as_deref().unwrap_or("no message"),
+                body.code.as_deref().unwrap_or("no code")
+            ));
+        }
+
+        Ok(body)
+    }
+
+    async fn record_stats(&self, stats: &FetchStats) {
+        let tags = HashMap::from([
+            ("source".to_string(), METRICS_SOURCE.to_string()),
+            ("query".to_string(), stats.query.clone()),
+        ]);
+
+        let metrics = stats
+            .metrics()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (metric_name, metric_value))| {
+                let metric = NewMetric {
+                    metric_name: metric_name.to_string(),
+                    metric_value,
+                    description: None,
+                    tags: tags.clone(),
+                };
+                (index, metric)
+            })
+            .collect();
+
+        match self.metrics_service.ingest_metrics(metrics).await {
+            Ok(result) if result.failures.is_empty() => {}
+            Ok(result) => error!(
+                "Failed to record {} fetcher metrics of '{}': {}",
+                result.failures.len(),
+                stats.query,
+                result.failures[0].message
+            ),
+            Err(e) => error!(
+                "Failed to record fetcher metrics of '{}': {}",
+                stats.query, e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CacheConfig, NewsApiEndpoint};
+    use crate::database::repositories::in_memory::InMemoryDatabase;
+    use axum::extract::{Path, Query};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    /// NewsAPI stand-in: `top-headlines` serves two versions of one story
+    /// and a removed article, `everything` rejects the key.
+    async fn newsapi_stub(
+        Path(endpoint): Path<String>,
+        Query(params): Query<HashMap<String, String>>,
+        headers: HeaderMap,
+    ) -> Response {
+        assert_eq!(headers["x-api-key"], "test-key");
+        assert_eq!(params["pageSize"], "10");
+
+        if endpoint != "top-headlines" {
+            let body = json!({
+                "status": "error",
+                "code": "apiKeyInvalid",
+                "message": "Your API key is invalid"
+            });
+            return (StatusCode::UNAUTHORIZED, axum::Json(body)).into_response();
+        }
+
+        assert_eq!(params["country"], "us");
+        let article = |title: &str, url: &str| {
+            json!({
+                "source": { "id": "example", "name": "Example News" },
+                "author": null,
+                "title": title,
+                "description": null,
+                "url": url,
+                "urlToImage": null,
+                "publishedAt": "2025-06-12T08:30:00Z",
+                "content": null
+            })
+        };
+
+        axum::Json(json!({
+            "status": "ok",
+            "totalResults": 3,
+            "articles": [
+                article("Markets rally", "https://example.com/markets/rally?utm_source=newsapi"),
+                article("Markets rally", "https://www.example.com/markets/rally/"),
+                article("[Removed]", "https://removed.com")
+            ]
+        }))
+        .into_response()
+    }
+
+    async fn start_stub() -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = axum::Router::new().route("/v2/{endpoint}", get(newsapi_stub));
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        format!("http://{}/v2", address)
+    }
+
+    fn query(name: &str, endpoint: NewsApiEndpoint) -> FetcherQuery {
+        FetcherQuery {
+            name: name.to_string(),
+            endpoint,
+            params: BTreeMap::from([("country".to_string(), "us".to_string())]),
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_fetched_articles_and_records_run_stats() {
+        let db = Arc::new(InMemoryDatabase::new());
+        let cache = CacheConfig::default();
+        let metrics_service = MetricsService::new(db.clone(), &cache);
+        let config = FetcherConfig {
+            enabled: true,
+            base_url: start_stub().await,
+            api_key: Some("test-key".to_string()),
+            page_size: 10,
+            max_pages: 3,
+            queries: vec![
+                query("headlines", NewsApiEndpoint::TopHeadlines),
+                query("everything", NewsApiEndpoint::Everything),
+            ],
+            ..FetcherConfig::default()
+        };
+        let fetcher = FetcherService::new(
+            config,
+            db.clone(),
+            ArticleService::new(db.clone(), db.clone()),
+            metrics_service.clone(),
+        )
+        .unwrap();
+
+        let stats = fetcher.run_once().await;
+        assert_eq!(
+            (stats[0].requests, stats[0].fetched, stats[0].errors),
+            (1, 3, 0)
+        );
+        assert_eq!(
+            (stats[0].created, stats[0].updated, stats[0].rejected),
+            (1, 1, 1)
+        );
+        assert_eq!((stats[1].requests, stats[1].errors), (1, 1));
+
+        // A second run finds the same story again
+        let stats = fetcher.run_once().await;
+        assert_eq!((stats[0].created, stats[0].updated), (0, 2));
+
+        let created = metrics_service
+            .list_metrics("fetcher.articles_created", None, None, None, None, None)
+            .await
+            .unwrap()
+            .metrics;
+        let mut values: Vec<(String, f64)> = created
+            .iter()
+            .map(|metric| (metric.tags["query"].clone(), metric.metric_value))
+            .collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            values,
+            [
+                ("everything".to_string(), 0.0),
+                ("everything".to_string(), 0.0),
+                ("headlines".to_string(), 0.0),
+                ("headlines".to_string(), 1.0),
+            ]
+        );
+        assert_eq!(created[0].tags["source"], "newsapi");
+    }
+
+    #[tokio::test]
+    async fn lets_a_single_instance_run_each_fetch() {
+        let db = Arc::new(InMemoryDatabase::new());
+        let cache = CacheConfig::default();
+        let instances: Vec<FetcherService> = (0..2)
+            .map(|_| {
+                FetcherService::new(
+                    FetcherConfig::default(),
+                    db.clone(),
+                    ArticleService::new(db.clone(), db.clone()),
+                    MetricsService::new(db.clone(), &cache),
+                )
+                .unwrap()
+            })
+            .collect();
+
+        assert!(instances[0].claim_run().await);
+        assert!(!instances[1].claim_run().await);
+        assert!(!instances[0].claim_run().await);
+    }
+}
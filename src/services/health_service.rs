@@ -0,0 +1,206 @@
+use log::{info, warn};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+use crate::database::repositories::health_repository::HealthRepository;
+
+/// Upper bound on each readiness check, so that an unreachable database
+/// fails the probe instead of hanging until server selection times out.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A collection the application relies on and the indexes it must carry.
+/// Collections that are only created on first write are not `required`.
+#[derive(Debug, Clone)]
+pub struct ExpectedCollection {
+    pub name: String,
+    pub required: bool,
+    pub indexes: Vec<String>,
+    /// Whether missing indexes fail readiness, or only degrade it when
+    /// creating them is left to an operator.
+    pub indexes_required: bool,
+}
+
+impl ExpectedCollection {
+    pub fn new(name: &str, required: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            required,
+            indexes: Vec::new(),
+            indexes_required: true,
+        }
+    }
+
+    pub fn with_indexes<'a>(mut self, index_names: impl IntoIterator<Item = &'a str>) -> Self {
+        self.indexes
+            .extend(index_names.into_iter().map(str::to_string));
+        self
+    }
+
+    pub fn with_indexes_required(mut self, indexes_required: bool) -> Self {
+        self.indexes_required = indexes_required;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    /// Works, but not as configured; does not fail readiness.
+    Degraded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HealthCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub latency_ms: f64,
+    pub details: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub checks: Vec<HealthCheck>,
+}
+
+#[derive(Clone)]
+pub struct HealthService {
+    health_repository: Arc<dyn HealthRepository>,
+    expected_collections: Vec<ExpectedCollection>,
+}
+
+impl HealthService {
+    pub fn new(
+        health_repository: Arc<dyn HealthRepository>,
+        expected_collections: Vec<ExpectedCollection>,
+    ) -> Self {
+        info!("Created HealthService");
+        Self {
+            health_repository,
+            expected_collections,
+        }
+    }
+
+    pub async fn check_readiness(&self) -> ReadinessReport {
+        let mut checks = Vec::with_capacity(self.expected_collections.len() + 1);
+
+        let ping = timed_check("mongodb_ping".to_string(), async {
+            self.health_repository
+                .ping()
+                .await
+                .map(|_| (CheckStatus::Ok, None))
+                .map_err(|e| e.to_string())
+        })
+        .await;
+        let database_reachable = ping.status == CheckStatus::Ok;
+        checks.push(ping);
+
+        // Without a connection every collection check would just wait for its timeout
+        if database_reachable {
+            for expected_collection in &self.expected_collections {
+                checks.push(
+                    timed_check(
+                        format!("collection:{}", expected_collection.name),
+                        self.check_collection(expected_collection),
+                    )
+                    .await,
+                );
+            }
+        }
+
+        let ready = database_reachable
+            && checks
+                .iter()
+                .all(|check| check.status != CheckStatus::Failed);
+
+        if !ready {
+            let failures: Vec<String> = checks
+                .iter()
+                .filter(|check| check.status == CheckStatus::Failed)
+                .map(|check| {
+                    format!(
+                        "{} ({})",
+                        check.name,
+                        check.details.as_deref().unwrap_or("failed")
+                    )
+                })
+                .collect();
+            warn!("Readiness check failed: {}", failures.join(", "));
+        }
+
+        ReadinessReport { ready, checks }
+    }
+
+    async fn check_collection(
+        &self,
+        expected_collection: &ExpectedCollection,
+    ) -> Result<(CheckStatus, Option<String>), String> {
+        let index_names = self
+            .health_repository
+            .list_index_names(&expected_collection.name)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let Some(index_names) = index_names else {
+            return if expected_collection.required {
+                Err("collection does not exist".to_string())
+            } else {
+                Ok((
+                    CheckStatus::Ok,
+                    Some("collection not created yet".to_string()),
+                ))
+            };
+        };
+
+        let missing_indexes: Vec<&str> = expected_collection
+            .indexes
+            .iter()
+            .filter(|index_name| !index_names.contains(index_name))
+            .map(String::as_str)
+            .collect();
+
+        if missing_indexes.is_empty() {
+            return Ok((CheckStatus::Ok, None));
+        }
+
+        let details = format!("missing indexes: {}", missing_indexes.join(", "));
+        if expected_collection.indexes_required {
+            Err(details)
+        } else {
+            Ok((CheckStatus::Degraded, Some(details)))
+        }
+    }
+}
+
+/// Runs a check under `CHECK_TIMEOUT` and records how long it took. The check
+/// yields its status and optional details on success, and the failure reason
+/// otherwise.
+async fn timed_check(
+    name: String,
+    check: impl Future<Output = Result<(CheckStatus, Option<String>), String>>,
+) -> HealthCheck {
+    let started_at = Instant::now();
+    let outcome = tokio::time::timeout(CHECK_TIMEOUT, check).await;
+    let latency_ms = started_at.elapsed().as_secs_f64() * 1000.0;
+
+    let (status, details) = match outcome {
+        Ok(Ok(outcome)) => outcome,
+        Ok(Err(reason)) => (CheckStatus::Failed, Some(reason)),
+        Err(_) => (
+            CheckStatus::Failed,
+            Some(format!("timed out after {}ms", CHECK_TIMEOUT.as_millis())),
+        ),
+    };
+
+    HealthCheck {
+        name,
+        status,
+        latency_ms,
+        details,
+    }
+}
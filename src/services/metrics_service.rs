@@ -1,18 +1,183 @@
-use crate::database::repositories::metrics_repository::MetricsRepository;
+use crate::config::CacheConfig;
+use crate::database::repositories::metrics_repository::{
+    DEFAULT_NUM_DAYS, MetricsRepository, window_start,
+};
 use crate::database::repositories::models::metrics_repository_models::{
-    MetricBinsAggregation, MetricSummaryAggregation, MetricsDocument,
+    MetricBinsAggregation, MetricInterval, MetricSummaryAggregation, MetricTimeseries,
+    MetricsDocument, PaginatedMetrics,
 };
-use log::{error, info};
+use crate::database::repositories::models::pagination_models::PageCursor;
+use crate::error::AppError;
+use crate::services::cache::{CacheStats, TtlCache};
+use chrono::Utc;
+use log::{error, info, warn};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{ContinuousCDF, Normal, StudentsT};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+const MAX_METRIC_NAME_LENGTH: usize = 128;
+const MAX_DESCRIPTION_LENGTH: usize = 1024;
+const MAX_TAGS: usize = 32;
+const MAX_TAG_KEY_LENGTH: usize = 64;
+const MAX_TAG_VALUE_LENGTH: usize = 256;
+
+/// Upper bound on the values loaded per version when comparing predictors.
+const MAX_COMPARISON_SAMPLES: i64 = 100_000;
+
+#[derive(Debug, Clone)]
+pub struct NewMetric {
+    pub metric_name: String,
+    pub metric_value: f64,
+    pub description: Option<String>,
+    pub tags: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricIngestionFailure {
+    pub index: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MetricsIngestionResult {
+    pub inserted: Vec<(usize, ObjectId)>,
+    pub failures: Vec<MetricIngestionFailure>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ComparisonTest {
+    /// Welch's t-test, for roughly normal continuous metrics.
+    #[default]
+    Welch,
+    /// Mann-Whitney U test, which only relies on the ranks of the values.
+    MannWhitney,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MetricSampleSummary {
+    pub predictor_version: String,
+    pub count: usize,
+    pub avg_value: f64,
+    pub std_dev_value: f64,
+    pub min_value: f64,
+    pub max_value: f64,
+    pub p50_value: f64,
+    /// Set when older values were left out because of the sample cap.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SignificanceTestResult {
+    pub test: ComparisonTest,
+    /// t for Welch's test, U of version `a` for Mann-Whitney.
+    pub statistic: f64,
+    /// Welch-Satterthwaite degrees of freedom, Welch's test only.
+    pub degrees_of_freedom: Option<f64>,
+    /// Normal approximation of U, Mann-Whitney only.
+    pub z_score: Option<f64>,
+    pub p_value: f64,
+    pub alpha: f64,
+    pub significant: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EffectSize {
+    /// Cohen's d for Welch's test, rank-biserial correlation for Mann-Whitney.
+    pub measure: String,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricComparisonRequest {
+    pub metric_name: String,
+    pub prediction_type: Option<String>,
+    pub version_a: String,
+    pub version_b: String,
+    pub num_days: i32,
+    pub test: ComparisonTest,
+    pub alpha: f64,
+}
+
+/// Comparison of version `b` against version `a`: positive differences and
+/// effect sizes mean `a` has the larger values.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MetricComparison {
+    pub metric_name: String,
+    pub prediction_type: Option<String>,
+    pub num_days: i32,
+    pub a: MetricSampleSummary,
+    pub b: MetricSampleSummary,
+    pub mean_difference: f64,
+    /// Absent when a sample is too small or has no variance to test against.
+    pub test: Option<SignificanceTestResult>,
+    pub effect_size: Option<EffectSize>,
+}
+
+/// Parameters of an aggregation, which key its cached result.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct AggregationKey {
+    metric_name: String,
+    prediction_type: Option<String>,
+    predictor_version: Option<String>,
+    num_days: i32,
+    num_bins: Option<i32>,
+    /// Bit patterns of the requested quantiles, in order.
+    quantiles: Vec<u64>,
+}
+
+impl AggregationKey {
+    /// Resolves the default window, so that requests selecting the same
+    /// metrics share an entry. Windows of zero or fewer days all select
+    /// nothing and are keyed as 0.
+    fn new(
+        metric_name: &str,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        num_days: Option<i32>,
+    ) -> Self {
+        Self {
+            metric_name: metric_name.to_string(),
+            prediction_type: prediction_type.map(str::to_string),
+            predictor_version: predictor_version.map(str::to_string),
+            num_days: num_days.unwrap_or(DEFAULT_NUM_DAYS).max(0),
+            num_bins: None,
+            quantiles: Vec::new(),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct MetricsService {
-    metrics_repository: MetricsRepository,
+    metrics_repository: Arc<dyn MetricsRepository>,
+    summary_cache: TtlCache<AggregationKey, Option<MetricSummaryAggregation>>,
+    bins_cache: TtlCache<AggregationKey, Vec<MetricBinsAggregation>>,
 }
 
 impl MetricsService {
-    pub fn new(metrics_repository: MetricsRepository) -> Self {
+    pub fn new(metrics_repository: Arc<dyn MetricsRepository>, cache: &CacheConfig) -> Self {
         info!("Created MetricsService");
-        Self { metrics_repository }
+        Self {
+            metrics_repository,
+            summary_cache: TtlCache::new(
+                "metrics_summary",
+                Duration::from_secs(cache.metrics_summary_ttl_seconds),
+                cache.max_entries,
+            ),
+            bins_cache: TtlCache::new(
+                "metrics_bins",
+                Duration::from_secs(cache.metrics_bins_ttl_seconds),
+                cache.max_entries,
+            ),
+        }
+    }
+
+    pub fn cache_stats(&self) -> Vec<CacheStats> {
+        vec![self.summary_cache.stats(), self.bins_cache.stats()]
     }
 
     pub async fn list_metrics(
@@ -20,21 +185,29 @@ impl MetricsService {
         metric_name: &str,
         limit: Option<i64>,
         skip: Option<u64>,
+        page_cursor: Option<&PageCursor>,
         prediction_type: Option<String>,
         predictor_version: Option<String>,
-    ) -> Result<Vec<MetricsDocument>, Box<dyn std::error::Error>> {
+    ) -> Result<PaginatedMetrics, AppError> {
         info!("Getting list of metrics");
 
         let metrics = self
             .metrics_repository
-            .list_metrics(metric_name, limit, skip, prediction_type, predictor_version)
+            .list_metrics(
+                metric_name,
+                limit,
+                skip,
+                page_cursor,
+                prediction_type,
+                predictor_version,
+            )
             .await
             .map_err(|e| {
                 error!("Failed to get metrics list: {}", e);
-                Box::new(e) as Box<dyn std::error::Error>
+                AppError::from(e)
             })?;
 
-        info!("Successfully retrieved {} metrics", metrics.len());
+        info!("Successfully retrieved {} metrics", metrics.metrics.len());
 
         Ok(metrics)
     }
@@ -45,24 +218,35 @@ impl MetricsService {
         prediction_type: Option<&str>,
         predictor_version: Option<&str>,
         num_days: Option<i32>,
-    ) -> Result<Option<MetricSummaryAggregation>, Box<dyn std::error::Error>> {
+        quantiles: &[f64],
+    ) -> Result<Option<MetricSummaryAggregation>, AppError> {
         info!("Getting metric aggregation for '{}'", metric_name);
 
+        let key = AggregationKey {
+            quantiles: quantiles
+                .iter()
+                .map(|quantile| quantile.to_bits())
+                .collect(),
+            ..AggregationKey::new(metric_name, prediction_type, predictor_version, num_days)
+        };
+
+        let load = self.metrics_repository.get_metric_summary_aggregation(
+            metric_name,
+            prediction_type,
+            predictor_version,
+            window_start(Some(key.num_days)),
+            quantiles,
+        );
         let aggregation = self
-            .metrics_repository
-            .get_metric_summary_aggregation(
-                metric_name,
-                prediction_type,
-                predictor_version,
-                num_days,
-            )
+            .summary_cache
+            .get_or_try_load(key, load)
             .await
             .map_err(|e| {
                 error!(
                     "Failed to get metric aggregation for '{}': {}",
                     metric_name, e
                 );
-                Box::new(e) as Box<dyn std::error::Error>
+                AppError::from(e)
             })?;
 
         if aggregation.is_some() {
@@ -81,12 +265,54 @@ impl MetricsService {
         prediction_type: Option<&str>,
         predictor_version: Option<&str>,
         num_days: Option<i32>,
-    ) -> Result<Vec<MetricBinsAggregation>, Box<dyn std::error::Error>> {
+    ) -> Result<Vec<MetricBinsAggregation>, AppError> {
+        let key = AggregationKey {
+            num_bins: Some(num_bins),
+            ..AggregationKey::new(metric_name, prediction_type, predictor_version, num_days)
+        };
+
+        let load = self.metrics_repository.get_metric_bins_aggregation(
+            metric_name,
+            num_bins,
+            prediction_type,
+            predictor_version,
+            Some(key.num_days),
+        );
         let aggregation = self
+            .bins_cache
+            .get_or_try_load(key, load)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to get metric bin aggregation for '{}': {}",
+                    metric_name, e
+                );
+                AppError::from(e)
+            })?;
+
+        info!(
+            "Successfully calculated aggregation bin for '{}'",
+            metric_name
+        );
+
+        Ok(aggregation)
+    }
+
+    pub async fn get_metric_timeseries_aggregation(
+        &self,
+        metric_name: &str,
+        interval: MetricInterval,
+        group_by: Option<&str>,
+        prediction_type: Option<&str>,
+        predictor_version: Option<&str>,
+        num_days: Option<i32>,
+    ) -> Result<Vec<MetricTimeseries>, AppError> {
+        let series = self
             .metrics_repository
-            .get_metric_bins_aggregation(
+            .get_metric_timeseries_aggregation(
                 metric_name,
-                num_bins,
+                interval,
+                group_by,
                 prediction_type,
                 predictor_version,
                 num_days,
@@ -94,17 +320,406 @@ impl MetricsService {
             .await
             .map_err(|e| {
                 error!(
-                    "Failed to get metric bin aggregation for '{}': {}",
+                    "Failed to get metric time series for '{}': {}",
                     metric_name, e
                 );
-                Box::new(e) as Box<dyn std::error::Error>
+                AppError::from(e)
             })?;
 
         info!(
-            "Successfully calculated aggregation bin for '{}'",
+            "Successfully calculated {} time series for '{}'",
+            series.len(),
             metric_name
         );
 
-        Ok(aggregation)
+        Ok(series)
+    }
+
+    /// Compares a metric between two predictor versions. Returns `None` when
+    /// either version has no data in the window.
+    pub async fn compare_predictor_versions(
+        &self,
+        request: &MetricComparisonRequest,
+    ) -> Result<Option<MetricComparison>, AppError> {
+        let metric_name = request.metric_name.as_str();
+        let prediction_type = request.prediction_type.as_deref();
+        let (version_a, version_b) = (request.version_a.as_str(), request.version_b.as_str());
+        let (num_days, alpha) = (request.num_days, request.alpha);
+
+        info!(
+            "Comparing metric '{}' between predictor versions '{}' and '{}'",
+            metric_name, version_a, version_b
+        );
+
+        let mut samples = Vec::with_capacity(2);
+        for version in [version_a, version_b] {
+            let values = self
+                .metrics_repository
+                .list_metric_values(
+                    metric_name,
+                    prediction_type,
+                    Some(version),
+                    Some(num_days),
+                    MAX_COMPARISON_SAMPLES,
+                )
+                .await
+                .map_err(|e| {
+                    error!(
+                        "Failed to get values of metric '{}' for version '{}': {}",
+                        metric_name, version, e
+                    );
+                    AppError::from(e)
+                })?;
+
+            if values.len() as i64 >= MAX_COMPARISON_SAMPLES {
+                warn!(
+                    "Comparison of '{}' for version '{}' capped at {} values",
+                    metric_name, version, MAX_COMPARISON_SAMPLES
+                );
+            }
+
+            samples.push(values);
+        }
+
+        let (values_b, values_a) = (
+            samples.pop().unwrap_or_default(),
+            samples.pop().unwrap_or_default(),
+        );
+
+        if values_a.is_empty() || values_b.is_empty() {
+            info!(
+                "No data to compare for metric '{}' between '{}' and '{}'",
+                metric_name, version_a, version_b
+            );
+            return Ok(None);
+        }
+
+        let (test_result, effect_size) = match request.test {
+            ComparisonTest::Welch => (
+                welch_t_test(&values_a, &values_b, alpha),
+                cohens_d(&values_a, &values_b),
+            ),
+            ComparisonTest::MannWhitney => {
+                let (test_result, rank_biserial) =
+                    match mann_whitney_u_test(&values_a, &values_b, alpha) {
+                        Some((test_result, rank_biserial)) => {
+                            (Some(test_result), Some(rank_biserial))
+                        }
+                        _none => (None, None),
+                    };
+                (test_result, rank_biserial)
+            }
+        };
+
+        let summary_a = sample_summary(version_a, &values_a);
+        let summary_b = sample_summary(version_b, &values_b);
+
+        info!(
+            "Successfully compared metric '{}' between '{}' ({} values) and '{}' ({} values)",
+            metric_name, version_a, summary_a.count, version_b, summary_b.count
+        );
+
+        Ok(Some(MetricComparison {
+            metric_name: metric_name.to_string(),
+            prediction_type: prediction_type.map(str::to_string),
+            num_days,
+            mean_difference: summary_a.avg_value - summary_b.avg_value,
+            a: summary_a,
+            b: summary_b,
+            test: test_result,
+            effect_size,
+        }))
+    }
+
+    /// Validates and stores metrics. Items are identified by the index given by
+    /// the caller so that failures can be reported against the original request.
+    pub async fn ingest_metrics(
+        &self,
+        metrics: Vec<(usize, NewMetric)>,
+    ) -> Result<MetricsIngestionResult, AppError> {
+        info!("Ingesting {} metrics", metrics.len());
+
+        let now = Utc::now();
+        let mut result = MetricsIngestionResult::default();
+        let mut indices = Vec::new();
+        let mut documents = Vec::new();
+
+        for (index, metric) in metrics {
+            if let Err(message) = validate_new_metric(&metric) {
+                result
+                    .failures
+                    .push(MetricIngestionFailure { index, message });
+                continue;
+            }
+
+            indices.push(index);
+            documents.push(MetricsDocument {
+                id: Some(ObjectId::new()),
+                metric_name: metric.metric_name,
+                metric_value: metric.metric_value,
+                description: metric.description,
+                tags: metric.tags,
+                created_at: now,
+                updated_at: now,
+            });
+        }
+
+        let write_failures = self
+            .metrics_repository
+            .insert_metrics(&documents)
+            .await
+            .map_err(|e| {
+                error!("Failed to insert metrics: {}", e);
+                AppError::from(e)
+            })?;
+
+        let write_failures: HashMap<usize, String> = write_failures.into_iter().collect();
+
+        for (position, document) in documents.iter().enumerate() {
+            let index = indices[position];
+            match write_failures.get(&position) {
+                Some(message) => result.failures.push(MetricIngestionFailure {
+                    index,
+                    message: message.clone(),
+                }),
+                _none => result
+                    .inserted
+                    .push((index, document.id.unwrap_or_default())),
+            }
+        }
+
+        result.failures.sort_by_key(|failure| failure.index);
+
+        if !result.inserted.is_empty() {
+            self.summary_cache.invalidate();
+            self.bins_cache.invalidate();
+        }
+
+        info!(
+            "Successfully ingested {} metrics, {} rejected",
+            result.inserted.len(),
+            result.failures.len()
+        );
+
+        Ok(result)
+    }
+}
+
+fn validate_new_metric(metric: &NewMetric) -> Result<(), String> {
+    if metric.metric_name.is_empty() || metric.metric_name.len() > MAX_METRIC_NAME_LENGTH {
+        return Err(format!(
+            "metric_name must contain between 1 and {} characters",
+            MAX_METRIC_NAME_LENGTH
+        ));
+    }
+
+    if !metric
+        .metric_name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        return Err("metric_name may only contain letters, digits, '_', '-' and '.'".to_string());
+    }
+
+    if !metric.metric_value.is_finite() {
+        return Err("metric_value must be a finite number".to_string());
+    }
+
+    if metric
+        .description
+        .as_ref()
+        .is_some_and(|description| description.len() > MAX_DESCRIPTION_LENGTH)
+    {
+        return Err(format!(
+            "description must not exceed {} characters",
+            MAX_DESCRIPTION_LENGTH
+        ));
+    }
+
+    if metric.tags.len() > MAX_TAGS {
+        return Err(format!("at most {} tags are allowed", MAX_TAGS));
+    }
+
+    for (key, value) in &metric.tags {
+        // Tags are queried as `tags.<key>`, so keys must be plain identifiers
+        if key.is_empty()
+            || key.len() > MAX_TAG_KEY_LENGTH
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+        {
+            return Err(format!(
+                "tag key '{}' must contain between 1 and {} letters, digits, '_' or '-'",
+                key, MAX_TAG_KEY_LENGTH
+            ));
+        }
+
+        if value.len() > MAX_TAG_VALUE_LENGTH {
+            return Err(format!(
+                "value of tag '{}' must not exceed {} characters",
+                key, MAX_TAG_VALUE_LENGTH
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Unbiased sample variance; zero for fewer than two values.
+fn sample_variance(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+
+    let mean = mean(values);
+    values
+        .iter()
+        .map(|value| (value - mean).powi(2))
+        .sum::<f64>()
+        / (values.len() - 1) as f64
+}
+
+fn sample_summary(predictor_version: &str, values: &[f64]) -> MetricSampleSummary {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+
+    // Averages the two middle values for even counts, picks the middle one otherwise
+    let p50_value = (sorted[(sorted.len() - 1) / 2] + sorted[sorted.len() / 2]) / 2.0;
+
+    MetricSampleSummary {
+        predictor_version: predictor_version.to_string(),
+        count: values.len(),
+        avg_value: mean(values),
+        std_dev_value: sample_variance(values).sqrt(),
+        min_value: sorted[0],
+        max_value: sorted[sorted.len() - 1],
+        p50_value,
+        truncated: values.len() as i64 >= MAX_COMPARISON_SAMPLES,
+    }
+}
+
+fn welch_t_test(values_a: &[f64], values_b: &[f64], alpha: f64) -> Option<SignificanceTestResult> {
+    if values_a.len() < 2 || values_b.len() < 2 {
+        return None;
     }
+
+    let (n_a, n_b) = (values_a.len() as f64, values_b.len() as f64);
+    let variance_term_a = sample_variance(values_a) / n_a;
+    let variance_term_b = sample_variance(values_b) / n_b;
+    let standard_error = (variance_term_a + variance_term_b).sqrt();
+
+    if standard_error == 0.0 {
+        return None;
+    }
+
+    let t_statistic = (mean(values_a) - mean(values_b)) / standard_error;
+    let degrees_of_freedom = (variance_term_a + variance_term_b).powi(2)
+        / (variance_term_a.powi(2) / (n_a - 1.0) + variance_term_b.powi(2) / (n_b - 1.0));
+
+    let distribution = StudentsT::new(0.0, 1.0, degrees_of_freedom).ok()?;
+    let p_value = (2.0 * distribution.sf(t_statistic.abs())).min(1.0);
+
+    Some(SignificanceTestResult {
+        test: ComparisonTest::Welch,
+        statistic: t_statistic,
+        degrees_of_freedom: Some(degrees_of_freedom),
+        z_score: None,
+        p_value,
+        alpha,
+        significant: p_value < alpha,
+    })
+}
+
+/// Mann-Whitney U test with average ranks for ties, tie-corrected variance and
+/// continuity correction. Also returns the rank-biserial correlation.
+fn mann_whitney_u_test(
+    values_a: &[f64],
+    values_b: &[f64],
+    alpha: f64,
+) -> Option<(SignificanceTestResult, EffectSize)> {
+    let mut combined: Vec<(f64, bool)> = values_a
+        .iter()
+        .map(|value| (*value, true))
+        .chain(values_b.iter().map(|value| (*value, false)))
+        .collect();
+    combined.sort_by(|left, right| left.0.total_cmp(&right.0));
+
+    let total = combined.len() as f64;
+    let mut rank_sum_a = 0.0;
+    let mut tie_correction = 0.0;
+
+    let mut start = 0;
+    while start < combined.len() {
+        let end = combined[start..]
+            .iter()
+            .position(|(value, _)| *value != combined[start].0)
+            .map_or(combined.len(), |offset| start + offset);
+
+        // Ranks are 1-based, tied values share the average of their ranks
+        let average_rank = (start + end + 1) as f64 / 2.0;
+        let ties = (end - start) as f64;
+        rank_sum_a += average_rank
+            * combined[start..end]
+                .iter()
+                .filter(|(_, in_a)| *in_a)
+                .count() as f64;
+        tie_correction += ties.powi(3) - ties;
+
+        start = end;
+    }
+
+    let (n_a, n_b) = (values_a.len() as f64, values_b.len() as f64);
+    let u_statistic = rank_sum_a - n_a * (n_a + 1.0) / 2.0;
+    let expected_u = n_a * n_b / 2.0;
+    let variance = n_a * n_b / 12.0 * ((total + 1.0) - tie_correction / (total * (total - 1.0)));
+
+    if variance <= 0.0 {
+        return None;
+    }
+
+    let deviation = u_statistic - expected_u;
+    let z_score = (deviation.abs() - 0.5).max(0.0).copysign(deviation) / variance.sqrt();
+
+    let distribution = Normal::new(0.0, 1.0).ok()?;
+    let p_value = (2.0 * distribution.sf(z_score.abs())).min(1.0);
+
+    Some((
+        SignificanceTestResult {
+            test: ComparisonTest::MannWhitney,
+            statistic: u_statistic,
+            degrees_of_freedom: None,
+            z_score: Some(z_score),
+            p_value,
+            alpha,
+            significant: p_value < alpha,
+        },
+        EffectSize {
+            measure: "rank_biserial".to_string(),
+            value: 2.0 * u_statistic / (n_a * n_b) - 1.0,
+        },
+    ))
+}
+
+fn cohens_d(values_a: &[f64], values_b: &[f64]) -> Option<EffectSize> {
+    if values_a.len() + values_b.len() < 3 {
+        return None;
+    }
+
+    let (n_a, n_b) = (values_a.len() as f64, values_b.len() as f64);
+    let pooled_variance = ((n_a - 1.0) * sample_variance(values_a)
+        + (n_b - 1.0) * sample_variance(values_b))
+        / (n_a + n_b - 2.0);
+
+    if pooled_variance == 0.0 {
+        return None;
+    }
+
+    Some(EffectSize {
+        measure: "cohens_d".to_string(),
+        value: (mean(values_a) - mean(values_b)) / pooled_variance.sqrt(),
+    })
 }
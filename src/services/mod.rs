@@ -1,3 +1,11 @@
+pub mod api_key_service;
 pub mod article_service;
+pub mod cache;
+pub mod canonical_url;
+pub mod deployment_service;
+pub mod feed_service;
+pub mod fetcher_service;
+pub mod health_service;
 pub mod metrics_service;
 pub mod predictor_service;
+pub mod rollout_service;
@@ -1,33 +1,62 @@
 use log::{error, info};
 use std::collections::HashSet;
+use std::time::Duration;
 
+use crate::config::CacheConfig;
 use crate::database::repositories::predictors_repository::PredictorRepository;
+use crate::error::AppError;
+use crate::services::cache::{CacheStats, TtlCache};
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct PredictorService {
-    predictor_repository: PredictorRepository,
+    predictor_repository: Arc<dyn PredictorRepository>,
+    prediction_types_cache: TtlCache<(), HashSet<String>>,
+    /// Keyed by prediction type.
+    predictor_versions_cache: TtlCache<String, HashSet<i32>>,
 }
 
 impl PredictorService {
-    pub fn new(predictor_repository: PredictorRepository) -> Self {
+    pub fn new(predictor_repository: Arc<dyn PredictorRepository>, cache: &CacheConfig) -> Self {
         info!("Created PredictorService");
         Self {
             predictor_repository,
+            prediction_types_cache: TtlCache::new(
+                "prediction_types",
+                Duration::from_secs(cache.prediction_types_ttl_seconds),
+                cache.max_entries,
+            ),
+            predictor_versions_cache: TtlCache::new(
+                "predictor_versions",
+                Duration::from_secs(cache.predictor_versions_ttl_seconds),
+                cache.max_entries,
+            ),
         }
     }
 
-    pub async fn get_prediction_types(
-        &self,
-    ) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    pub fn cache_stats(&self) -> Vec<CacheStats> {
+        vec![
+            self.prediction_types_cache.stats(),
+            self.predictor_versions_cache.stats(),
+        ]
+    }
+
+    /// Drops cached predictor data, to be called whenever predictors change.
+    pub fn invalidate_cache(&self) {
+        self.prediction_types_cache.invalidate();
+        self.predictor_versions_cache.invalidate();
+    }
+
+    pub async fn get_prediction_types(&self) -> Result<HashSet<String>, AppError> {
         info!("Getting all prediction types");
 
         let prediction_types = self
-            .predictor_repository
-            .get_prediction_types()
+            .prediction_types_cache
+            .get_or_try_load((), self.predictor_repository.get_prediction_types())
             .await
             .map_err(|e| {
                 error!("Failed to get prediction types: {}", e);
-                Box::new(e) as Box<dyn std::error::Error>
+                AppError::from(e)
             })?;
 
         info!(
@@ -41,22 +70,26 @@ impl PredictorService {
     pub async fn get_predictor_versions(
         &self,
         prediction_type: &str,
-    ) -> Result<HashSet<i32>, Box<dyn std::error::Error>> {
+    ) -> Result<HashSet<i32>, AppError> {
         info!(
             "Getting predictor versions for prediction type '{}'",
             prediction_type
         );
 
         let predictor_versions = self
-            .predictor_repository
-            .get_predictor_versions(prediction_type)
+            .predictor_versions_cache
+            .get_or_try_load(
+                prediction_type.to_string(),
+                self.predictor_repository
+                    .get_predictor_versions(prediction_type),
+            )
             .await
             .map_err(|e| {
                 error!(
                     "Failed to get predictor versions for '{}': {}",
                     prediction_type, e
                 );
-                Box::new(e) as Box<dyn std::error::Error>
+                AppError::from(e)
             })?;
 
         info!(
@@ -74,7 +107,7 @@ impl PredictorService {
         min_traffic: Option<i32>,
     ) -> Result<
         Vec<crate::database::repositories::models::predictor_repository_models::PredictorDocument>,
-        Box<dyn std::error::Error>,
+        AppError,
     > {
         info!(
             "Getting all predictors for prediction type '{}'",
@@ -87,7 +120,7 @@ impl PredictorService {
             .await
             .map_err(|e| {
                 error!("Failed to get predictors for '{}': {}", prediction_type, e);
-                Box::new(e) as Box<dyn std::error::Error>
+                AppError::from(e)
             })?;
 
         info!(
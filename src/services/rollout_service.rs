@@ -0,0 +1,705 @@
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info, warn};
+use mongodb::bson::oid::ObjectId;
+
+use crate::database::repositories::metrics_repository::MetricsRepository;
+use crate::database::repositories::models::deployment_repository_models::ActiveDeploymentDocument;
+use crate::database::repositories::models::rollout_repository_models::{
+    RolloutDocument, RolloutGateDocument, RolloutStatus,
+};
+use crate::database::repositories::predictors_repository::PredictorRepository;
+use crate::database::repositories::rollout_repository::RolloutRepository;
+use crate::error::{AppError, is_duplicate_key};
+use crate::lifecycle::Shutdown;
+use crate::services::deployment_service::{
+    DeploymentDetails, DeploymentService, TrafficSplit, TrafficSplitChange,
+};
+use std::sync::Arc;
+
+/// Delay before retrying a step or rollback whose traffic split failed to apply.
+const RETRY_DELAY: Duration = Duration::minutes(1);
+
+/// Values a step must have collected for its gate when `min_samples` is not given.
+const DEFAULT_GATE_MIN_SAMPLES: i64 = 30;
+
+#[derive(Debug, Clone)]
+pub struct RolloutPlan {
+    pub prediction_type: String,
+    pub predictor_id: ObjectId,
+    pub steps: Vec<i32>,
+    pub step_interval_hours: i64,
+    pub gate: Option<RolloutGateDocument>,
+    pub created_by: String,
+    pub start_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone)]
+pub struct RolloutService {
+    rollout_repository: Arc<dyn RolloutRepository>,
+    predictor_repository: Arc<dyn PredictorRepository>,
+    metrics_repository: Arc<dyn MetricsRepository>,
+    deployment_service: DeploymentService,
+}
+
+impl RolloutService {
+    pub fn new(
+        rollout_repository: Arc<dyn RolloutRepository>,
+        predictor_repository: Arc<dyn PredictorRepository>,
+        metrics_repository: Arc<dyn MetricsRepository>,
+        deployment_service: DeploymentService,
+    ) -> Self {
+        info!("Created RolloutService");
+        Self {
+            rollout_repository,
+            predictor_repository,
+            metrics_repository,
+            deployment_service,
+        }
+    }
+
+    pub async fn create_rollout(&self, plan: RolloutPlan) -> Result<RolloutDocument, AppError> {
+        info!(
+            "Creating rollout of predictor {} for prediction type '{}'",
+            plan.predictor_id, plan.prediction_type
+        );
+
+        validate_rollout_plan(&plan)?;
+
+        let predictors = self
+            .predictor_repository
+            .find_by_ids(&[plan.predictor_id])
+            .await
+            .map_err(|e| {
+                error!("Failed to get predictor {}: {}", plan.predictor_id, e);
+                AppError::from(e)
+            })?;
+
+        match predictors.first() {
+            Some(predictor) if predictor.prediction_type != plan.prediction_type => {
+                return Err(AppError::invalid_param(
+                    "predictor_id",
+                    format!(
+                        "predictor {} serves '{}', not '{}'",
+                        plan.predictor_id, predictor.prediction_type, plan.prediction_type
+                    ),
+                ));
+            }
+            Some(_) => {}
+            _none => {
+                return Err(AppError::invalid_param(
+                    "predictor_id",
+                    format!("predictor {} does not exist", plan.predictor_id),
+                ));
+            }
+        }
+
+        if let Some(active_rollout) = self.active_rollout(&plan.prediction_type).await? {
+            return Err(AppError::conflict(format!(
+                "rollout {} is already active for '{}'",
+                active_rollout.id.map(|id| id.to_hex()).unwrap_or_default(),
+                plan.prediction_type
+            )));
+        }
+
+        let now = Utc::now();
+        let mut rollout = RolloutDocument {
+            id: None,
+            prediction_type: plan.prediction_type,
+            predictor_id: plan.predictor_id,
+            steps: plan.steps,
+            step_interval_hours: plan.step_interval_hours,
+            gate: plan.gate,
+            status: RolloutStatus::Active,
+            status_reason: None,
+            current_step: None,
+            step_started_at: None,
+            baseline_deployments: Vec::new(),
+            rollback_reason: None,
+            next_step_at: plan.start_at.unwrap_or(now),
+            created_by: plan.created_by,
+            created_at: now,
+            updated_at: now,
+        };
+
+        let rollout_id = self
+            .rollout_repository
+            .insert(&rollout)
+            .await
+            .map_err(|e| {
+                // Another rollout was created since the check above
+                if is_duplicate_key(&e) {
+                    return AppError::conflict(format!(
+                        "a rollout is already active for '{}'",
+                        rollout.prediction_type
+                    ));
+                }
+                error!("Failed to create rollout: {}", e);
+                AppError::from(e)
+            })?;
+        rollout.id = Some(rollout_id);
+
+        info!("Successfully created rollout {}", rollout_id);
+
+        Ok(rollout)
+    }
+
+    /// Replaces the traffic split of a prediction type by hand, which is
+    /// refused while a rollout drives it; the rollout must be cancelled first.
+    pub async fn update_traffic_split(
+        &self,
+        prediction_type: &str,
+        change: TrafficSplitChange,
+    ) -> Result<DeploymentDetails, AppError> {
+        if let Some(active_rollout) = self.active_rollout(prediction_type).await? {
+            return Err(AppError::conflict(format!(
+                "rollout {} is active for '{}', cancel it before changing the traffic split",
+                active_rollout.id.map(|id| id.to_hex()).unwrap_or_default(),
+                prediction_type
+            )));
+        }
+
+        self.deployment_service
+            .update_traffic_split(prediction_type, change)
+            .await
+    }
+
+    async fn active_rollout(
+        &self,
+        prediction_type: &str,
+    ) -> Result<Option<RolloutDocument>, AppError> {
+        self.rollout_repository
+            .find_active_by_prediction_type(prediction_type)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to get active rollout for '{}': {}",
+                    prediction_type, e
+                );
+                AppError::from(e)
+            })
+    }
+
+    pub async fn list_rollouts(
+        &self,
+        prediction_type: Option<&str>,
+        status: Option<RolloutStatus>,
+        limit: Option<i64>,
+        skip: Option<u64>,
+    ) -> Result<Vec<RolloutDocument>, AppError> {
+        info!("Getting list of rollouts");
+
+        let rollouts = self
+            .rollout_repository
+            .list_rollouts(prediction_type, status, limit, skip)
+            .await
+            .map_err(|e| {
+                error!("Failed to get rollouts: {}", e);
+                AppError::from(e)
+            })?;
+
+        info!("Successfully retrieved {} rollouts", rollouts.len());
+
+        Ok(rollouts)
+    }
+
+    pub async fn get_rollout(
+        &self,
+        rollout_id: ObjectId,
+    ) -> Result<Option<RolloutDocument>, AppError> {
+        info!("Getting rollout {}", rollout_id);
+
+        self.rollout_repository
+            .find_by_id(rollout_id)
+            .await
+            .map_err(|e| {
+                error!("Failed to get rollout {}: {}", rollout_id, e);
+                AppError::from(e)
+            })
+    }
+
+    /// Stops an active rollout, leaving the current traffic split in place.
+    pub async fn cancel_rollout(
+        &self,
+        rollout_id: ObjectId,
+        reason: Option<&str>,
+    ) -> Result<Option<RolloutDocument>, AppError> {
+        info!("Cancelling rollout {}", rollout_id);
+
+        let cancelled = self
+            .rollout_repository
+            .update_status(
+                rollout_id,
+                RolloutStatus::Active,
+                RolloutStatus::Cancelled,
+                reason,
+            )
+            .await
+            .map_err(|e| {
+                error!("Failed to cancel rollout {}: {}", rollout_id, e);
+                AppError::from(e)
+            })?;
+
+        if !cancelled {
+            return match self.get_rollout(rollout_id).await? {
+                Some(rollout) => Err(AppError::conflict(format!(
+                    "rollout {} is {}",
+                    rollout_id,
+                    rollout.status.as_str()
+                ))),
+                _none => Ok(None),
+            };
+        }
+
+        self.get_rollout(rollout_id).await
+    }
+
+    /// Periodically advances every rollout whose next step is due, until the
+    /// shutdown is triggered. A step in progress is completed before stopping.
+    pub async fn run_scheduler(self, interval: std::time::Duration, shutdown: Shutdown) {
+        info!(
+            "Starting rollout scheduler with a {}s interval",
+            interval.as_secs()
+        );
+
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.wait() => break,
+                _ = ticker.tick() => self.process_due_rollouts().await,
+            }
+        }
+
+        info!("Stopped rollout scheduler");
+    }
+
+    pub async fn process_due_rollouts(&self) {
+        loop {
+            let now = Utc::now();
+
+            // The lease only matters if this instance dies mid-step, the step itself
+            // reschedules the rollout
+            let rollout = match self
+                .rollout_repository
+                .claim_due_rollout(now, Duration::minutes(10))
+                .await
+            {
+                Ok(Some(rollout)) => rollout,
+                Ok(_none) => return,
+                Err(e) => {
+                    error!("Failed to claim due rollouts: {}", e);
+                    return;
+                }
+            };
+
+            if let Err(e) = self.advance_rollout(&rollout).await {
+                error!(
+                    "Failed to advance rollout {:?} for '{}': {}",
+                    rollout.id, rollout.prediction_type, e
+                );
+            }
+        }
+    }
+
+    async fn advance_rollout(&self, rollout: &RolloutDocument) -> Result<(), String> {
+        let Some(rollout_id) = rollout.id else {
+            return Err("rollout has no id".to_string());
+        };
+
+        let Some(current_step) = rollout.current_step else {
+            let current_split = self.current_split(&rollout.prediction_type).await?;
+            let baseline: Vec<ActiveDeploymentDocument> = current_split
+                .iter()
+                .filter(|deployment| deployment.predictor_id != rollout.predictor_id)
+                .cloned()
+                .collect();
+
+            if baseline.is_empty() && rollout.steps[0] < 100 {
+                return self
+                    .finish(
+                        rollout_id,
+                        RolloutStatus::Failed,
+                        "no other predictor serves traffic to split with".to_string(),
+                    )
+                    .await;
+            }
+
+            return self.apply_step(rollout, rollout_id, 0, &baseline).await;
+        };
+
+        // A rollback that could not be applied is retried, whatever the gate says now
+        if let Some(rollback_reason) = &rollout.rollback_reason {
+            return self
+                .roll_back(rollout, rollout_id, rollback_reason.clone())
+                .await;
+        }
+
+        if let Some(gate_failure) = self.evaluate_gate(rollout).await? {
+            warn!(
+                "Gate failed for rollout {} at step {}: {}",
+                rollout_id, current_step, gate_failure
+            );
+
+            return self.roll_back(rollout, rollout_id, gate_failure).await;
+        }
+
+        if current_step + 1 >= rollout.steps.len() {
+            return self
+                .finish(
+                    rollout_id,
+                    RolloutStatus::Completed,
+                    "all steps passed".to_string(),
+                )
+                .await;
+        }
+
+        self.apply_step(
+            rollout,
+            rollout_id,
+            current_step + 1,
+            &rollout.baseline_deployments,
+        )
+        .await
+    }
+
+    async fn apply_step(
+        &self,
+        rollout: &RolloutDocument,
+        rollout_id: ObjectId,
+        step: usize,
+        baseline: &[ActiveDeploymentDocument],
+    ) -> Result<(), String> {
+        let traffic_percentage = rollout.steps[step];
+        let splits = compute_step_split(rollout.predictor_id, traffic_percentage, baseline);
+
+        let reason = format!(
+            "step {}/{}: {}%",
+            step + 1,
+            rollout.steps.len(),
+            traffic_percentage
+        );
+        match self.apply_split(rollout, rollout_id, splits, reason).await {
+            Ok(()) => {}
+            // Retrying cannot fix the split itself, e.g. a deleted predictor
+            Err(e @ AppError::Validation { .. }) => {
+                return self
+                    .finish(
+                        rollout_id,
+                        RolloutStatus::Failed,
+                        format!("invalid traffic split: {}", e),
+                    )
+                    .await;
+            }
+            Err(e) => {
+                error!(
+                    "Failed to apply step {} of rollout {}, retrying in {} minutes: {}",
+                    step + 1,
+                    rollout_id,
+                    RETRY_DELAY.num_minutes(),
+                    e
+                );
+
+                return self
+                    .rollout_repository
+                    .reschedule(rollout_id, Utc::now() + RETRY_DELAY)
+                    .await
+                    .map_err(|e| e.to_string());
+            }
+        }
+
+        let step_started_at = Utc::now();
+        let next_step_at = step_started_at + Duration::hours(rollout.step_interval_hours);
+
+        self.rollout_repository
+            .record_step(rollout_id, step, baseline, step_started_at, next_step_at)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Restores the split the other predictors had before the rollout, taking
+    /// all traffic away from the rolled out predictor. If the split cannot be
+    /// applied, the rollout stays active and the rollback is retried.
+    async fn roll_back(
+        &self,
+        rollout: &RolloutDocument,
+        rollout_id: ObjectId,
+        reason: String,
+    ) -> Result<(), String> {
+        if rollout.baseline_deployments.is_empty() {
+            return self
+                .finish(
+                    rollout_id,
+                    RolloutStatus::Failed,
+                    format!("{}, and no other predictor to roll back to", reason),
+                )
+                .await;
+        }
+
+        let splits = compute_step_split(rollout.predictor_id, 0, &rollout.baseline_deployments);
+
+        match self
+            .apply_split(rollout, rollout_id, splits, format!("rollback: {}", reason))
+            .await
+        {
+            Ok(()) => {
+                self.finish(rollout_id, RolloutStatus::RolledBack, reason)
+                    .await
+            }
+            Err(e) => {
+                error!(
+                    "Failed to roll back rollout {}, retrying in {} minutes: {}",
+                    rollout_id,
+                    RETRY_DELAY.num_minutes(),
+                    e
+                );
+
+                self.rollout_repository
+                    .record_rollback(rollout_id, &reason, Utc::now() + RETRY_DELAY)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    async fn apply_split(
+        &self,
+        rollout: &RolloutDocument,
+        rollout_id: ObjectId,
+        splits: Vec<TrafficSplit>,
+        reason: String,
+    ) -> Result<(), AppError> {
+        let change = TrafficSplitChange {
+            splits,
+            changed_by: format!("rollout:{}", rollout_id),
+            reason: Some(reason),
+        };
+
+        self.deployment_service
+            .update_traffic_split(&rollout.prediction_type, change)
+            .await
+            .map(|_| ())
+    }
+
+    async fn finish(
+        &self,
+        rollout_id: ObjectId,
+        status: RolloutStatus,
+        reason: String,
+    ) -> Result<(), String> {
+        info!(
+            "Rollout {} finished as {}: {}",
+            rollout_id,
+            status.as_str(),
+            reason
+        );
+
+        self.rollout_repository
+            .update_status(rollout_id, RolloutStatus::Active, status, Some(&reason))
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn current_split(
+        &self,
+        prediction_type: &str,
+    ) -> Result<Vec<ActiveDeploymentDocument>, String> {
+        let deployment = self
+            .deployment_service
+            .get_deployment(prediction_type)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(deployment
+            .map(|deployment| {
+                deployment
+                    .active_deployments
+                    .into_iter()
+                    .map(|active_deployment| ActiveDeploymentDocument {
+                        predictor_id: active_deployment.predictor_id,
+                        traffic_percentage: active_deployment.traffic_percentage,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Returns the reason the gate failed, or `None` if the rollout may proceed.
+    /// Only the metrics recorded since the current step was applied are
+    /// considered, so earlier steps and other traffic splits do not count.
+    async fn evaluate_gate(&self, rollout: &RolloutDocument) -> Result<Option<String>, String> {
+        let Some(gate) = &rollout.gate else {
+            return Ok(None);
+        };
+
+        let predictor = self
+            .predictor_repository
+            .find_by_ids(&[rollout.predictor_id])
+            .await
+            .map_err(|e| e.to_string())?
+            .pop();
+
+        let Some(predictor) = predictor else {
+            return Ok(Some(format!(
+                "predictor {} no longer exists",
+                rollout.predictor_id
+            )));
+        };
+
+        let summary = self
+            .metrics_repository
+            .get_metric_summary_aggregation(
+                &gate.metric_name,
+                Some(&rollout.prediction_type),
+                Some(&predictor.predictor_version.to_string()),
+                rollout.step_started_at.unwrap_or(rollout.created_at),
+                &[],
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let Some(summary) = summary else {
+            return Ok(Some(format!(
+                "no data for metric '{}' since the step started",
+                gate.metric_name
+            )));
+        };
+
+        let min_samples = gate.min_samples.unwrap_or(DEFAULT_GATE_MIN_SAMPLES);
+        if summary.count < min_samples {
+            return Ok(Some(format!(
+                "only {} values of '{}' since the step started, {} required",
+                summary.count, gate.metric_name, min_samples
+            )));
+        }
+
+        if let Some(max_avg_value) = gate
+            .max_avg_value
+            .filter(|max_avg_value| summary.avg_value > *max_avg_value)
+        {
+            return Ok(Some(format!(
+                "average {} of '{}' is above {}",
+                summary.avg_value, gate.metric_name, max_avg_value
+            )));
+        }
+
+        if let Some(min_avg_value) = gate
+            .min_avg_value
+            .filter(|min_avg_value| summary.avg_value < *min_avg_value)
+        {
+            return Ok(Some(format!(
+                "average {} of '{}' is below {}",
+                summary.avg_value, gate.metric_name, min_avg_value
+            )));
+        }
+
+        Ok(None)
+    }
+}
+
+fn validate_rollout_plan(plan: &RolloutPlan) -> Result<(), AppError> {
+    if plan.steps.is_empty() {
+        return Err(AppError::invalid_param(
+            "steps",
+            "at least one step is required",
+        ));
+    }
+
+    if plan.steps.iter().any(|step| !(1..=100).contains(step)) {
+        return Err(AppError::invalid_param(
+            "steps",
+            "steps must be between 1 and 100",
+        ));
+    }
+
+    if plan.steps.windows(2).any(|steps| steps[0] >= steps[1]) {
+        return Err(AppError::invalid_param(
+            "steps",
+            "steps must be strictly increasing",
+        ));
+    }
+
+    if plan.step_interval_hours <= 0 {
+        return Err(AppError::invalid_param(
+            "step_interval_hours",
+            "step_interval_hours must be positive",
+        ));
+    }
+
+    if plan
+        .gate
+        .as_ref()
+        .is_some_and(|gate| gate.max_avg_value.is_none() && gate.min_avg_value.is_none())
+    {
+        return Err(AppError::invalid_param(
+            "gate",
+            "gate needs max_avg_value or min_avg_value",
+        ));
+    }
+
+    if plan
+        .gate
+        .as_ref()
+        .and_then(|gate| gate.min_samples)
+        .is_some_and(|min_samples| min_samples <= 0)
+    {
+        return Err(AppError::invalid_param(
+            "gate",
+            "gate min_samples must be positive",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Gives `traffic_percentage` to the rolled out predictor and spreads the rest
+/// over the baseline predictors proportionally, using largest remainders so
+/// that the split sums to exactly 100.
+fn compute_step_split(
+    predictor_id: ObjectId,
+    traffic_percentage: i32,
+    baseline: &[ActiveDeploymentDocument],
+) -> Vec<TrafficSplit> {
+    let mut splits = vec![TrafficSplit {
+        predictor_id,
+        traffic_percentage,
+    }];
+
+    let remaining = 100 - traffic_percentage;
+    let baseline_total: f64 = baseline
+        .iter()
+        .map(|deployment| deployment.traffic_percentage)
+        .sum();
+
+    if remaining == 0 || baseline_total <= 0.0 {
+        return splits;
+    }
+
+    let shares: Vec<f64> = baseline
+        .iter()
+        .map(|deployment| deployment.traffic_percentage / baseline_total * remaining as f64)
+        .collect();
+    let mut percentages: Vec<i32> = shares.iter().map(|share| share.floor() as i32).collect();
+
+    let mut by_remainder: Vec<usize> = (0..shares.len()).collect();
+    by_remainder.sort_by(|&a, &b| {
+        (shares[b] - shares[b].floor()).total_cmp(&(shares[a] - shares[a].floor()))
+    });
+
+    let leftover = remaining - percentages.iter().sum::<i32>();
+    for index in by_remainder.into_iter().take(leftover.max(0) as usize) {
+        percentages[index] += 1;
+    }
+
+    splits.extend(
+        baseline
+            .iter()
+            .zip(percentages)
+            .map(|(deployment, traffic_percentage)| TrafficSplit {
+                predictor_id: deployment.predictor_id,
+                traffic_percentage,
+            }),
+    );
+
+    splits
+}
@@ -0,0 +1,63 @@
+use axum::extract::{FromRequest, FromRequestParts, rejection};
+use axum::http::StatusCode;
+
+use crate::error::AppError;
+
+/// `axum::extract::Query` answering rejections with an `AppError` body.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(AppError))]
+pub struct Query<T>(pub T);
+
+/// `axum::extract::Path` answering rejections with an `AppError` body.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(AppError))]
+pub struct Path<T>(pub T);
+
+/// `axum::Json` answering rejections with an `AppError` body. Responses keep
+/// using `axum::Json`.
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct JsonBody<T>(pub T);
+
+impl From<rejection::QueryRejection> for AppError {
+    fn from(rejection: rejection::QueryRejection) -> Self {
+        let message = rejection.body_text();
+
+        match rejected_param(&message) {
+            Some(param) => AppError::invalid_param(param, message),
+            _none => AppError::validation(message),
+        }
+    }
+}
+
+impl From<rejection::PathRejection> for AppError {
+    fn from(rejection: rejection::PathRejection) -> Self {
+        AppError::validation(rejection.body_text())
+    }
+}
+
+impl From<rejection::JsonRejection> for AppError {
+    fn from(rejection: rejection::JsonRejection) -> Self {
+        match rejection.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => AppError::payload_too_large(rejection.body_text()),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => {
+                AppError::unsupported_media_type(rejection.body_text())
+            }
+            _ => AppError::validation(rejection.body_text()),
+        }
+    }
+}
+
+/// Recovers the parameter name from a query rejection, which reads either
+/// "... missing field `name`" or "...: name: <reason>".
+fn rejected_param(message: &str) -> Option<String> {
+    if let Some((_, rest)) = message.split_once("missing field `") {
+        return rest.split_once('`').map(|(param, _)| param.to_string());
+    }
+
+    let (_, detail) = message.split_once(": ")?;
+    let (param, _) = detail.split_once(": ")?;
+
+    (!param.is_empty() && param.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .then(|| param.to_string())
+}
@@ -0,0 +1,155 @@
+use axum::{Extension, extract::State, http::StatusCode, response::Json};
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    database::repositories::models::api_key_repository_models::{ApiKeyDocument, ApiKeyRole},
+    error::{AppError, ErrorResponse},
+    services::api_key_service::Caller,
+    web::{
+        extractors::{JsonBody, Path},
+        routes::AppState,
+    },
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct IssueApiKeyRequest {
+    /// Who or what the key is for, e.g. `metrics-exporter`.
+    pub name: String,
+    pub role: ApiKeyRole,
+}
+
+/// A key as listed; the key itself is only returned when it is issued.
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyResponse {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<crate::web::openapi::ObjectIdSchema>)]
+    pub id: Option<ObjectId>,
+    pub name: String,
+    pub role: ApiKeyRole,
+    /// First characters of the key, to tell keys apart.
+    pub key_prefix: String,
+    pub created_by: String,
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime_optional")]
+    #[schema(value_type = Option<crate::web::openapi::BsonDateTimeSchema>)]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKeyDocument> for ApiKeyResponse {
+    fn from(document: ApiKeyDocument) -> Self {
+        Self {
+            id: document.id,
+            name: document.name,
+            role: document.role,
+            key_prefix: document.key_prefix,
+            created_by: document.created_by,
+            created_at: document.created_at,
+            revoked_at: document.revoked_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct IssuedApiKeyResponse {
+    #[serde(flatten)]
+    pub api_key: ApiKeyResponse,
+    /// The key to send in the `X-API-Key` header. It cannot be retrieved later.
+    pub key: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeysResponse {
+    pub api_keys: Vec<ApiKeyResponse>,
+}
+
+/// Issue an API key
+#[utoipa::path(
+    post,
+    path = "/admin/api-keys",
+    tag = "admin",
+    request_body = IssueApiKeyRequest,
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 201, description = "The issued key", body = IssuedApiKeyResponse),
+        (status = 400, description = "Invalid name or role", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key is not an admin key", body = ErrorResponse),
+    )
+)]
+pub async fn issue_api_key(
+    State(app_state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    JsonBody(request): JsonBody<IssueApiKeyRequest>,
+) -> Result<(StatusCode, Json<IssuedApiKeyResponse>), AppError> {
+    let issued = app_state
+        .api_key_service
+        .issue_api_key(&request.name, request.role, &caller.name)
+        .await?;
+
+    let response = IssuedApiKeyResponse {
+        api_key: issued.api_key.into(),
+        key: issued.key,
+    };
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// List API keys
+#[utoipa::path(
+    get,
+    path = "/admin/api-keys",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "Every key, newest first, revoked ones included", body = ApiKeysResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key is not an admin key", body = ErrorResponse),
+    )
+)]
+pub async fn list_api_keys(
+    State(app_state): State<AppState>,
+) -> Result<Json<ApiKeysResponse>, AppError> {
+    let api_keys = app_state.api_key_service.list_api_keys().await?;
+
+    let response = ApiKeysResponse {
+        api_keys: api_keys.into_iter().map(ApiKeyResponse::from).collect(),
+    };
+    Ok(Json(response))
+}
+
+/// Revoke an API key
+#[utoipa::path(
+    post,
+    path = "/admin/api-keys/{id}/revoke",
+    tag = "admin",
+    params(("id" = String, Path, description = "API key id")),
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "The revoked key", body = ApiKeyResponse),
+        (status = 400, description = "Invalid id", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key is not an admin key", body = ErrorResponse),
+        (status = 404, description = "No such key", body = ErrorResponse),
+        (status = 409, description = "The key is already revoked", body = ErrorResponse),
+    )
+)]
+pub async fn revoke_api_key(
+    Path(api_key_id): Path<String>,
+    State(app_state): State<AppState>,
+) -> Result<Json<ApiKeyResponse>, AppError> {
+    let api_key_id = ObjectId::parse_str(&api_key_id).map_err(|_| {
+        AppError::invalid_param("id", format!("'{}' is not a valid id", api_key_id))
+    })?;
+
+    match app_state.api_key_service.revoke_api_key(api_key_id).await? {
+        Some(api_key) => Ok(Json(api_key.into())),
+        _none => Err(AppError::not_found(format!(
+            "API key {} does not exist",
+            api_key_id
+        ))),
+    }
+}
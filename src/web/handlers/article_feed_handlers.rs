@@ -0,0 +1,431 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue, Uri, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    database::ArticleDocument,
+    error::{AppError, ErrorResponse},
+    web::{
+        extractors::Query,
+        handlers::articles_handlers::{ArticlesQuery, PaginatedArticlesResponse, list_articles},
+        routes::AppState,
+    },
+};
+
+const FEED_TITLE: &str = "Smart News";
+const JSON_FEED_VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+/// Query parameters that page through a listing rather than filter it.
+const PAGING_PARAMS: &[&str] = &["limit", "skip", "cursor"];
+
+#[derive(Serialize, ToSchema)]
+pub struct JsonFeed {
+    /// Always `https://jsonfeed.org/version/1.1`.
+    pub version: String,
+    pub title: String,
+    pub feed_url: String,
+    /// The next page of the same listing, from its `next_cursor`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_url: Option<String>,
+    pub items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct JsonFeedItem {
+    /// Article id.
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// The content of the article, or its description or title when unset.
+    pub content_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    pub date_published: String,
+    pub date_modified: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<JsonFeedAuthor>,
+    /// Selected predictions as `<type>:<value>`, e.g. `sentiment_analysis:positive`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct JsonFeedAuthor {
+    pub name: String,
+}
+
+/// Articles as an RSS 2.0 feed
+///
+/// The page of `GET /articles` for the same parameters, with the selected
+/// prediction of each type as a category whose domain is the type.
+#[utoipa::path(
+    get,
+    path = "/feeds/articles.rss",
+    tag = "articles",
+    params(ArticlesQuery),
+    responses(
+        (status = 200, description = "RSS 2.0 feed of the articles", content_type = "application/rss+xml", body = String),
+        (status = 400, description = "Invalid parameter", body = ErrorResponse),
+    )
+)]
+pub async fn get_articles_rss(
+    Query(params): Query<ArticlesQuery>,
+    Query(raw_params): Query<Vec<(String, String)>>,
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Response, AppError> {
+    let articles = list_articles(params, &raw_params, &app_state).await?;
+    let feed_url = request_url(&headers, &uri);
+
+    Ok(feed_response(
+        "application/rss+xml; charset=utf-8",
+        render_rss(&feed_title(&raw_params), &feed_url, &articles),
+    ))
+}
+
+/// Articles as an Atom feed
+///
+/// The page of `GET /articles` for the same parameters, with the selected
+/// prediction of each type as a category.
+#[utoipa::path(
+    get,
+    path = "/feeds/articles.atom",
+    tag = "articles",
+    params(ArticlesQuery),
+    responses(
+        (status = 200, description = "Atom feed of the articles", content_type = "application/atom+xml", body = String),
+        (status = 400, description = "Invalid parameter", body = ErrorResponse),
+    )
+)]
+pub async fn get_articles_atom(
+    Query(params): Query<ArticlesQuery>,
+    Query(raw_params): Query<Vec<(String, String)>>,
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Response, AppError> {
+    let articles = list_articles(params, &raw_params, &app_state).await?;
+    let feed_url = request_url(&headers, &uri);
+
+    Ok(feed_response(
+        "application/atom+xml; charset=utf-8",
+        render_atom(&feed_title(&raw_params), &feed_url, &articles),
+    ))
+}
+
+/// Articles as a JSON Feed
+///
+/// The page of `GET /articles` for the same parameters in JSON Feed 1.1, with
+/// the selected predictions as tags and the next page as `next_url`.
+#[utoipa::path(
+    get,
+    path = "/feeds/articles.json",
+    tag = "articles",
+    params(ArticlesQuery),
+    responses(
+        (status = 200, description = "JSON Feed of the articles", content_type = "application/feed+json", body = JsonFeed),
+        (status = 400, description = "Invalid parameter", body = ErrorResponse),
+    )
+)]
+pub async fn get_articles_json_feed(
+    Query(params): Query<ArticlesQuery>,
+    Query(raw_params): Query<Vec<(String, String)>>,
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Response, AppError> {
+    let articles = list_articles(params, &raw_params, &app_state).await?;
+    let feed_url = request_url(&headers, &uri);
+
+    let next_url = articles.next_cursor.as_ref().map(|next_cursor| {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        for (key, value) in &raw_params {
+            if key != "cursor" && key != "skip" {
+                query.append_pair(key, value);
+            }
+        }
+        query.append_pair("cursor", next_cursor);
+
+        format!("{}?{}", feed_path(&feed_url), query.finish())
+    });
+
+    let feed = JsonFeed {
+        version: JSON_FEED_VERSION.to_string(),
+        title: feed_title(&raw_params),
+        feed_url,
+        next_url,
+        items: articles.articles.iter().map(json_feed_item).collect(),
+    };
+
+    let body = serde_json::to_string(&feed)
+        .map_err(|e| AppError::internal(format!("Failed to serialize the feed: {}", e)))?;
+
+    Ok(feed_response("application/feed+json; charset=utf-8", body))
+}
+
+fn feed_response(content_type: &'static str, body: String) -> Response {
+    (
+        [(header::CONTENT_TYPE, HeaderValue::from_static(content_type))],
+        body,
+    )
+        .into_response()
+}
+
+/// URL the feed was requested at, as seen by the client behind our proxy.
+fn request_url(headers: &HeaderMap, uri: &Uri) -> String {
+    let header_value = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty())
+    };
+
+    let scheme = header_value("x-forwarded-proto").unwrap_or("http");
+    let host = header_value(header::HOST.as_str()).unwrap_or("localhost");
+    let path_and_query = uri
+        .path_and_query()
+        .map(|path_and_query| path_and_query.as_str())
+        .unwrap_or("/");
+
+    format!("{}://{}{}", scheme, host, path_and_query)
+}
+
+fn feed_path(feed_url: &str) -> &str {
+    feed_url
+        .split_once('?')
+        .map_or(feed_url, |(feed_path, _)| feed_path)
+}
+
+/// Title naming the filters of the feed, so that subscriptions to different
+/// filters can be told apart in a feed reader.
+fn feed_title(raw_params: &[(String, String)]) -> String {
+    let filters: Vec<String> = raw_params
+        .iter()
+        .filter(|(key, value)| !PAGING_PARAMS.contains(&key.as_str()) && !value.is_empty())
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+
+    match filters.as_slice() {
+        [] => FEED_TITLE.to_string(),
+        _ => format!("{}: {}", FEED_TITLE, filters.join(", ")),
+    }
+}
+
+/// Selected prediction of each type as `(type, value)`, ordered by type.
+fn prediction_categories(article: &ArticleDocument) -> Vec<(String, String)> {
+    let mut categories: Vec<(String, String)> = article
+        .predictions
+        .iter()
+        .flatten()
+        .map(|(prediction_type, prediction)| {
+            let value = match &prediction.prediction_value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            (prediction_type.clone(), value)
+        })
+        .collect();
+    categories.sort();
+
+    categories
+}
+
+fn article_id(article: &ArticleDocument) -> String {
+    article.id.map(|id| id.to_hex()).unwrap_or_default()
+}
+
+fn last_updated(articles: &PaginatedArticlesResponse) -> DateTime<Utc> {
+    articles
+        .articles
+        .iter()
+        .map(|article| article.updated_at)
+        .max()
+        .unwrap_or_else(Utc::now)
+}
+
+fn rfc3339(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn render_rss(title: &str, feed_url: &str, articles: &PaginatedArticlesResponse) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push_str(r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom" xmlns:dc="http://purl.org/dc/elements/1.1/"><channel>"#);
+    xml.push_str(&format!("<title>{}</title>", escape_xml(title)));
+    xml.push_str(&format!("<link>{}</link>", escape_xml(feed_url)));
+    xml.push_str(&format!(
+        r#"<atom:link href="{}" rel="self" type="application/rss+xml"/>"#,
+        escape_xml(feed_url)
+    ));
+    xml.push_str(&format!("<description>{}</description>", escape_xml(title)));
+    xml.push_str(&format!(
+        "<lastBuildDate>{}</lastBuildDate>",
+        last_updated(articles).to_rfc2822()
+    ));
+
+    for article in &articles.articles {
+        xml.push_str("<item>");
+        if let Some(title) = &article.title {
+            xml.push_str(&format!("<title>{}</title>", escape_xml(title)));
+        }
+        if let Some(url) = &article.url {
+            xml.push_str(&format!("<link>{}</link>", escape_xml(url)));
+        }
+        if let Some(description) = &article.description {
+            xml.push_str(&format!(
+                "<description>{}</description>",
+                escape_xml(description)
+            ));
+        }
+        if let Some(author) = &article.author {
+            xml.push_str(&format!("<dc:creator>{}</dc:creator>", escape_xml(author)));
+        }
+        for (prediction_type, value) in prediction_categories(article) {
+            xml.push_str(&format!(
+                r#"<category domain="{}">{}</category>"#,
+                escape_xml(&prediction_type),
+                escape_xml(&value)
+            ));
+        }
+        xml.push_str(&format!(
+            r#"<guid isPermaLink="false">{}</guid>"#,
+            article_id(article)
+        ));
+        xml.push_str(&format!(
+            "<pubDate>{}</pubDate>",
+            article.published_at.to_rfc2822()
+        ));
+        xml.push_str("</item>");
+    }
+
+    xml.push_str("</channel></rss>");
+    xml
+}
+
+fn render_atom(title: &str, feed_url: &str, articles: &PaginatedArticlesResponse) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+    xml.push_str(&format!("<id>{}</id>", escape_xml(feed_url)));
+    xml.push_str(&format!("<title>{}</title>", escape_xml(title)));
+    xml.push_str(&format!(
+        "<updated>{}</updated>",
+        rfc3339(last_updated(articles))
+    ));
+    xml.push_str(&format!(
+        r#"<link rel="self" type="application/atom+xml" href="{}"/>"#,
+        escape_xml(feed_url)
+    ));
+    xml.push_str(&format!("<author><name>{}</name></author>", FEED_TITLE));
+
+    for article in &articles.articles {
+        xml.push_str("<entry>");
+        xml.push_str(&format!(
+            "<id>urn:smart-news:article:{}</id>",
+            article_id(article)
+        ));
+        xml.push_str(&format!(
+            "<title>{}</title>",
+            escape_xml(article.title.as_deref().unwrap_or_default())
+        ));
+        if let Some(url) = &article.url {
+            xml.push_str(&format!(
+                r#"<link rel="alternate" href="{}"/>"#,
+                escape_xml(url)
+            ));
+        }
+        xml.push_str(&format!(
+            "<published>{}</published>",
+            rfc3339(article.published_at)
+        ));
+        xml.push_str(&format!(
+            "<updated>{}</updated>",
+            rfc3339(article.updated_at)
+        ));
+        if let Some(author) = &article.author {
+            xml.push_str(&format!(
+                "<author><name>{}</name></author>",
+                escape_xml(author)
+            ));
+        }
+        if let Some(description) = &article.description {
+            xml.push_str(&format!("<summary>{}</summary>", escape_xml(description)));
+        }
+        for (prediction_type, value) in prediction_categories(article) {
+            xml.push_str(&format!(
+                r#"<category term="{}" scheme="urn:smart-news:prediction:{}" label="{}: {}"/>"#,
+                escape_xml(&value),
+                escape_xml(&prediction_type),
+                escape_xml(&prediction_type),
+                escape_xml(&value)
+            ));
+        }
+        xml.push_str(&format!(
+            "<source><title>{}</title></source>",
+            escape_xml(&article.source.name)
+        ));
+        xml.push_str("</entry>");
+    }
+
+    xml.push_str("</feed>");
+    xml
+}
+
+fn json_feed_item(article: &ArticleDocument) -> JsonFeedItem {
+    let content_text = article
+        .content
+        .as_ref()
+        .or(article.description.as_ref())
+        .or(article.title.as_ref())
+        .cloned()
+        .unwrap_or_default();
+
+    JsonFeedItem {
+        id: article_id(article),
+        url: article.url.clone(),
+        title: article.title.clone(),
+        summary: article.description.clone(),
+        content_text,
+        image: article.url_to_image.clone(),
+        date_published: rfc3339(article.published_at),
+        date_modified: rfc3339(article.updated_at),
+        authors: article
+            .author
+            .iter()
+            .map(|name| JsonFeedAuthor { name: name.clone() })
+            .collect(),
+        tags: prediction_categories(article)
+            .into_iter()
+            .map(|(prediction_type, value)| format!("{}:{}", prediction_type, value))
+            .collect(),
+    }
+}
+
+/// Escapes text and attribute values, dropping the control characters XML
+/// 1.0 does not allow, which scraped articles sometimes contain.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -1,20 +1,55 @@
-use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::Json,
-};
+use axum::{extract::State, http::StatusCode, response::Json};
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::{database::ArticleDocument, web::routes::AppState};
+use crate::{
+    database::{
+        ArticleDocument,
+        repositories::models::{
+            article_prediction_repository_models::{
+                ArticlePredictionsDocument, PredictionDocument,
+            },
+            article_repository_models::{PredictionFilter, SourceDocument},
+            pagination_models::PageCursor,
+        },
+    },
+    error::{AppError, ErrorResponse},
+    services::article_service::{ArticlesIngestionResult, NewArticle, UpsertedArticle},
+    web::{
+        extractors::{JsonBody, Path, Query},
+        handlers::page_size,
+        routes::AppState,
+    },
+};
 
-#[derive(Deserialize)]
+/// Scalar query parameters of `GET /articles` and of the article feeds.
+///
+/// Prediction filters use a bracketed syntax that `serde_urlencoded` cannot
+/// deserialize, so they are parsed separately by `parse_prediction_filters`:
+/// `prediction[<type>]=<value>` (repeatable) and `min_confidence[<type>]=<f64>`.
+///
+/// When `cursor` is set, `skip` is ignored. `q` runs a full-text search whose
+/// results are ranked by relevance and paginated with `skip` only.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ArticlesQuery {
+    /// Page size, `default_page_size` when unset.
     pub limit: Option<i64>,
     pub skip: Option<u64>,
+    /// `next_cursor` or `prev_cursor` of a previous page.
+    pub cursor: Option<String>,
+    /// Full-text search query.
+    pub q: Option<String>,
+    /// Alias of `prediction[sentiment_analysis]`.
     pub sentiment: Option<String>,
+    /// Only returns the articles of this source, by `source.id` or `source.name`.
+    pub source: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct PaginatedArticlesResponse {
     pub articles: Vec<ArticleDocument>,
     pub total_count: u64,
@@ -22,34 +57,528 @@ pub struct PaginatedArticlesResponse {
     pub page: u64,
     pub per_page: i64,
     pub total_pages: u64,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArticleDetailsQuery {
+    /// Only returns the predictions of this type.
+    pub prediction_type: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ArticlePredictionDetailsResponse {
+    #[schema(value_type = crate::web::openapi::ObjectIdSchema)]
+    pub selected_predictor_id: ObjectId,
+    pub selected_prediction: PredictionDocument,
+    // JSON object keys must be strings, so predictor ids are rendered as hex
+    pub predictions: HashMap<String, PredictionDocument>,
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<ArticlePredictionsDocument> for ArticlePredictionDetailsResponse {
+    fn from(document: ArticlePredictionsDocument) -> Self {
+        Self {
+            selected_predictor_id: document.selected_predictor_id,
+            selected_prediction: document.selected_prediction,
+            predictions: document
+                .predictions
+                .into_iter()
+                .map(|(predictor_id, prediction)| (predictor_id.to_hex(), prediction))
+                .collect(),
+            created_at: document.created_at,
+            updated_at: document.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ArticleDetailsResponse {
+    pub article: ArticleDocument,
+    pub predictions: HashMap<String, ArticlePredictionDetailsResponse>,
+}
+
+/// An article as NewsAPI returns it. The NewsAPI camelCase names
+/// `urlToImage` and `publishedAt` are accepted as well.
+#[derive(Deserialize, ToSchema)]
+pub struct NewArticleRequest {
+    pub source: SourceDocument,
+    pub author: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub url: String,
+    #[serde(alias = "urlToImage")]
+    pub url_to_image: Option<String>,
+    #[serde(alias = "publishedAt")]
+    pub published_at: DateTime<Utc>,
+    pub content: Option<String>,
+}
+
+impl From<NewArticleRequest> for NewArticle {
+    fn from(request: NewArticleRequest) -> Self {
+        Self {
+            source: request.source,
+            author: request.author,
+            title: request.title,
+            description: request.description,
+            url: request.url,
+            url_to_image: request.url_to_image,
+            published_at: request.published_at,
+            published_at_estimated: false,
+            content: request.content,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UpsertedArticleResponse {
+    pub index: usize,
+    #[schema(value_type = crate::web::openapi::ObjectIdSchema)]
+    pub id: ObjectId,
+    /// `false` when an article with the same canonical URL was updated.
+    pub created: bool,
+    /// URL the article is stored under, without tracking parameters.
+    pub url: String,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct ArticleErrorResponse {
+    pub index: usize,
+    pub message: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ArticlesBatchResponse {
+    pub created_count: usize,
+    pub updated_count: usize,
+    pub failed_count: usize,
+    pub articles: Vec<UpsertedArticleResponse>,
+    pub errors: Vec<ArticleErrorResponse>,
+}
+
+/// List articles
+///
+/// Newest first, or by relevance when `q` is set. Besides the listed
+/// parameters, predictions can be filtered with `prediction[<type>]=<value>`
+/// (repeatable) and `min_confidence[<type>]=<number>`.
+#[utoipa::path(
+    get,
+    path = "/articles",
+    tag = "articles",
+    params(ArticlesQuery),
+    responses(
+        (status = 200, description = "A page of articles", body = PaginatedArticlesResponse),
+        (status = 400, description = "Invalid parameter", body = ErrorResponse),
+    )
+)]
 pub async fn get_articles(
     Query(params): Query<ArticlesQuery>,
+    Query(raw_params): Query<Vec<(String, String)>>,
+    State(app_state): State<AppState>,
+) -> Result<Json<PaginatedArticlesResponse>, AppError> {
+    list_articles(params, &raw_params, &app_state)
+        .await
+        .map(Json)
+}
+
+/// Search articles
+///
+/// Same as `GET /articles`, with a required `q`.
+#[utoipa::path(
+    get,
+    path = "/articles/search",
+    tag = "articles",
+    params(ArticlesQuery),
+    responses(
+        (status = 200, description = "A page of articles ranked by relevance", body = PaginatedArticlesResponse),
+        (status = 400, description = "Missing or invalid parameter", body = ErrorResponse),
+    )
+)]
+pub async fn search_articles(
+    Query(params): Query<ArticlesQuery>,
+    Query(raw_params): Query<Vec<(String, String)>>,
+    State(app_state): State<AppState>,
+) -> Result<Json<PaginatedArticlesResponse>, AppError> {
+    if params.q.as_deref().is_none_or(|q| q.trim().is_empty()) {
+        return Err(AppError::missing_param("q"));
+    }
+
+    list_articles(params, &raw_params, &app_state)
+        .await
+        .map(Json)
+}
+
+/// Page of articles matching the search query, source and prediction filters
+/// of a listing request.
+pub(super) async fn list_articles(
+    params: ArticlesQuery,
+    raw_params: &[(String, String)],
+    app_state: &AppState,
+) -> Result<PaginatedArticlesResponse, AppError> {
+    let search_query = params.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let source = params
+        .source
+        .as_deref()
+        .map(str::trim)
+        .filter(|source| !source.is_empty());
+
+    // Relevance-ranked results cannot be resumed from a `published_at` cursor
+    if search_query.is_some() && params.cursor.is_some() {
+        return Err(AppError::invalid_param(
+            "cursor",
+            "cursor cannot be combined with a search query",
+        ));
+    }
+
+    let page_cursor = match params.cursor.as_deref().map(PageCursor::decode) {
+        Some(Some(page_cursor)) => Some(page_cursor),
+        Some(_none) => return Err(AppError::invalid_param("cursor", "cursor is invalid")),
+        _none => None,
+    };
+
+    let limit = page_size(&app_state.limits, params.limit)?;
+
+    let mut prediction_filters = parse_prediction_filters(raw_params)?;
+
+    // `sentiment` predates the generic syntax and is kept as an alias
+    if let Some(sentiment) = params.sentiment {
+        match prediction_filters
+            .iter_mut()
+            .find(|filter| filter.prediction_type == "sentiment_analysis")
+        {
+            Some(filter) => filter.values.push(sentiment),
+            _none => {
+                let mut filter = PredictionFilter::new("sentiment_analysis");
+                filter.values.push(sentiment);
+                prediction_filters.push(filter);
+            }
+        }
+    }
+
+    validate_prediction_types(app_state, &prediction_filters).await?;
+
+    let paginated_articles = app_state
+        .article_service
+        .get_articles_with_all_predictions(
+            Some(limit),
+            params.skip,
+            page_cursor.as_ref(),
+            search_query,
+            source,
+            &prediction_filters,
+        )
+        .await?;
+
+    let response = PaginatedArticlesResponse {
+        articles: paginated_articles.articles,
+        total_count: paginated_articles.total_count,
+        current_page_count: paginated_articles.current_page_count,
+        page: paginated_articles.page,
+        per_page: paginated_articles.per_page,
+        total_pages: paginated_articles.total_pages,
+        next_cursor: paginated_articles.next_cursor.map(|cursor| cursor.encode()),
+        prev_cursor: paginated_articles.prev_cursor.map(|cursor| cursor.encode()),
+    };
+
+    Ok(response)
+}
+
+/// Get an article with its predictions
+#[utoipa::path(
+    get,
+    path = "/articles/{id}",
+    tag = "articles",
+    params(("id" = String, Path, description = "Article id"), ArticleDetailsQuery),
+    responses(
+        (status = 200, description = "The article and its predictions by type", body = ArticleDetailsResponse),
+        (status = 400, description = "Invalid id", body = ErrorResponse),
+        (status = 404, description = "No such article", body = ErrorResponse),
+    )
+)]
+pub async fn get_article(
+    Path(article_id): Path<String>,
+    Query(params): Query<ArticleDetailsQuery>,
     State(app_state): State<AppState>,
-) -> Result<Json<PaginatedArticlesResponse>, StatusCode> {
-    let sentiment = params.sentiment.as_deref();
+) -> Result<Json<ArticleDetailsResponse>, AppError> {
+    let article_id = match ObjectId::parse_str(&article_id) {
+        Ok(article_id) => article_id,
+        Err(_) => {
+            return Err(AppError::invalid_param(
+                "id",
+                format!("'{}' is not a valid id", article_id),
+            ));
+        }
+    };
 
     match app_state
         .article_service
-        .get_articles_with_all_predictions(params.limit, params.skip, sentiment)
-        .await
+        .get_article_with_predictions(article_id, params.prediction_type.as_deref())
+        .await?
     {
-        Ok(paginated_articles) => {
-            let response = PaginatedArticlesResponse {
-                articles: paginated_articles.articles,
-                total_count: paginated_articles.total_count,
-                current_page_count: paginated_articles.current_page_count,
-                page: paginated_articles.page,
-                per_page: paginated_articles.per_page,
-                total_pages: paginated_articles.total_pages,
-            };
+        Some(article_with_predictions) => {
+            let predictions = article_with_predictions
+                .predictions
+                .into_iter()
+                .map(|prediction| (prediction.prediction_type.clone(), prediction.into()))
+                .collect();
 
+            let response = ArticleDetailsResponse {
+                article: article_with_predictions.article,
+                predictions,
+            };
             Ok(Json(response))
         }
-        Err(e) => {
-            log::error!("Service error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        _none => Err(AppError::not_found(format!(
+            "article {} does not exist",
+            article_id
+        ))),
+    }
+}
+
+fn parse_prediction_filters(
+    raw_params: &[(String, String)],
+) -> Result<Vec<PredictionFilter>, AppError> {
+    let mut filters: BTreeMap<&str, PredictionFilter> = BTreeMap::new();
+
+    for (key, value) in raw_params {
+        let Some((field, prediction_type)) =
+            key.strip_suffix(']').and_then(|key| key.split_once('['))
+        else {
+            continue;
+        };
+
+        if field != "prediction" && field != "min_confidence" {
+            continue;
         }
+
+        // The type ends up in a document path, so only plain identifiers are accepted
+        if prediction_type.is_empty()
+            || !prediction_type
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(AppError::invalid_param(
+                key.as_str(),
+                "prediction type must only contain letters, digits, '_' and '-'",
+            ));
+        }
+
+        let filter = filters
+            .entry(prediction_type)
+            .or_insert_with(|| PredictionFilter::new(prediction_type));
+
+        if field == "prediction" {
+            filter.values.push(value.clone());
+        } else {
+            match value.parse::<f64>() {
+                Ok(min_confidence) if min_confidence.is_finite() => {
+                    filter.min_confidence = Some(min_confidence)
+                }
+                _ => {
+                    return Err(AppError::invalid_param(
+                        key.as_str(),
+                        "min_confidence must be a finite number",
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(filters.into_values().collect())
+}
+
+async fn validate_prediction_types(
+    app_state: &AppState,
+    prediction_filters: &[PredictionFilter],
+) -> Result<(), AppError> {
+    if prediction_filters.is_empty() {
+        return Ok(());
+    }
+
+    let prediction_types = app_state.predictor_service.get_prediction_types().await?;
+
+    match prediction_filters
+        .iter()
+        .find(|filter| !prediction_types.contains(&filter.prediction_type))
+    {
+        Some(filter) => Err(AppError::invalid_param(
+            format!("prediction[{}]", filter.prediction_type),
+            format!("unknown prediction type '{}'", filter.prediction_type),
+        )),
+        _none => Ok(()),
+    }
+}
+
+/// Store an article
+///
+/// The URL is normalized (tracking parameters and redirect wrappers such as
+/// Google News links removed), and an article already stored under the same
+/// canonical URL is updated instead of duplicated.
+#[utoipa::path(
+    post,
+    path = "/articles",
+    tag = "articles",
+    request_body = NewArticleRequest,
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 201, description = "The article was stored", body = UpsertedArticleResponse),
+        (status = 200, description = "The article with the same canonical URL was updated", body = UpsertedArticleResponse),
+        (status = 400, description = "Invalid article", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key lacks the ingest role", body = ErrorResponse),
+    )
+)]
+pub async fn create_article(
+    State(app_state): State<AppState>,
+    JsonBody(request): JsonBody<NewArticleRequest>,
+) -> Result<(StatusCode, Json<UpsertedArticleResponse>), AppError> {
+    let result = app_state
+        .article_service
+        .ingest_articles(vec![(0, request.into())])
+        .await?;
+
+    match (result.upserted.into_iter().next(), result.failures.first()) {
+        (Some(article), _) => {
+            let status = if article.created {
+                StatusCode::CREATED
+            } else {
+                StatusCode::OK
+            };
+            Ok((status, Json(upserted_response(article))))
+        }
+        (_, Some(failure)) => Err(AppError::validation(failure.message.clone())),
+        _ => Err(AppError::internal(
+            "article was neither stored nor rejected",
+        )),
+    }
+}
+
+/// Store a batch of articles
+///
+/// Accepts a JSON array of articles, or a NewsAPI response as is (an object
+/// with an `articles` array). Articles are stored like with `POST /articles`,
+/// reporting malformed or invalid items individually.
+#[utoipa::path(
+    post,
+    path = "/articles/batch",
+    tag = "articles",
+    request_body = Vec<NewArticleRequest>,
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 201, description = "Every article was stored, some of them new", body = ArticlesBatchResponse),
+        (status = 200, description = "Every article was already known and updated", body = ArticlesBatchResponse),
+        (status = 207, description = "Some articles were stored", body = ArticlesBatchResponse),
+        (status = 400, description = "No article was stored", body = ArticlesBatchResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key lacks the ingest role", body = ErrorResponse),
+        (status = 413, description = "Too many articles", body = ErrorResponse),
+    )
+)]
+pub async fn create_articles_batch(
+    State(app_state): State<AppState>,
+    JsonBody(body): JsonBody<serde_json::Value>,
+) -> Result<(StatusCode, Json<ArticlesBatchResponse>), AppError> {
+    let items = match body {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Object(mut response) => match response.remove("articles") {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => {
+                return Err(AppError::validation(
+                    "the batch must be an array of articles or an object with an 'articles' array",
+                ));
+            }
+        },
+        _ => {
+            return Err(AppError::validation(
+                "the batch must be an array of articles or an object with an 'articles' array",
+            ));
+        }
+    };
+
+    if items.is_empty() {
+        return Err(AppError::validation("the batch contains no articles"));
+    }
+
+    if items.len() > app_state.limits.max_articles_batch_size {
+        return Err(AppError::payload_too_large(format!(
+            "a batch holds at most {} articles, got {}",
+            app_state.limits.max_articles_batch_size,
+            items.len()
+        )));
+    }
+
+    let mut parse_errors = Vec::new();
+    let mut articles = Vec::new();
+
+    for (index, item) in items.into_iter().enumerate() {
+        match serde_json::from_value::<NewArticleRequest>(item) {
+            Ok(request) => articles.push((index, request.into())),
+            Err(e) => parse_errors.push(ArticleErrorResponse {
+                index,
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    let result = app_state.article_service.ingest_articles(articles).await?;
+    let response = batch_response(result, parse_errors);
+
+    let status = match (
+        response.created_count + response.updated_count,
+        response.failed_count,
+    ) {
+        (0, _) => StatusCode::BAD_REQUEST,
+        (_, 0) if response.created_count == 0 => StatusCode::OK,
+        (_, 0) => StatusCode::CREATED,
+        _ => StatusCode::MULTI_STATUS,
+    };
+
+    Ok((status, Json(response)))
+}
+
+fn upserted_response(article: UpsertedArticle) -> UpsertedArticleResponse {
+    UpsertedArticleResponse {
+        index: article.index,
+        id: article.id,
+        created: article.created,
+        url: article.url,
+    }
+}
+
+fn batch_response(
+    result: ArticlesIngestionResult,
+    parse_errors: Vec<ArticleErrorResponse>,
+) -> ArticlesBatchResponse {
+    let mut errors: Vec<ArticleErrorResponse> = parse_errors
+        .into_iter()
+        .chain(
+            result
+                .failures
+                .into_iter()
+                .map(|failure| ArticleErrorResponse {
+                    index: failure.index,
+                    message: failure.message,
+                }),
+        )
+        .collect();
+    errors.sort_by_key(|error| error.index);
+
+    let articles: Vec<UpsertedArticleResponse> =
+        result.upserted.into_iter().map(upserted_response).collect();
+    let created_count = articles.iter().filter(|article| article.created).count();
+
+    ArticlesBatchResponse {
+        created_count,
+        updated_count: articles.len() - created_count,
+        failed_count: errors.len(),
+        articles,
+        errors,
     }
 }
@@ -0,0 +1,35 @@
+use axum::{extract::State, response::Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{
+    error::{AppError, ErrorResponse},
+    services::cache::CacheStats,
+    web::routes::AppState,
+};
+
+#[derive(Serialize, ToSchema)]
+pub struct CacheStatsResponse {
+    pub caches: Vec<CacheStats>,
+}
+
+/// Report hits and misses of the aggregation caches
+#[utoipa::path(
+    get,
+    path = "/admin/cache",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "Counters of each cache since startup", body = CacheStatsResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key is not an admin key", body = ErrorResponse),
+    )
+)]
+pub async fn get_cache_stats(
+    State(app_state): State<AppState>,
+) -> Result<Json<CacheStatsResponse>, AppError> {
+    let mut caches = app_state.metrics_service.cache_stats();
+    caches.extend(app_state.predictor_service.cache_stats());
+
+    Ok(Json(CacheStatsResponse { caches }))
+}
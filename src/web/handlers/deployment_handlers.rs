@@ -0,0 +1,231 @@
+use axum::{Extension, extract::State, response::Json};
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    database::repositories::models::{
+        deployment_repository_models::DeploymentHistoryDocument,
+        predictor_repository_models::PredictorDocument,
+    },
+    error::{AppError, ErrorResponse},
+    services::{
+        api_key_service::Caller,
+        deployment_service::{
+            ActiveDeploymentDetails, DeploymentDetails, TrafficSplit, TrafficSplitChange,
+        },
+    },
+    web::{
+        extractors::{JsonBody, Path, Query},
+        handlers::page_size,
+        routes::AppState,
+    },
+};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeploymentHistoryQuery {
+    pub limit: Option<i64>,
+    pub skip: Option<u64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TrafficSplitRequest {
+    /// Hex string of the predictor id.
+    #[schema(value_type = String)]
+    pub predictor_id: ObjectId,
+    pub traffic_percentage: i32,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateTrafficRequest {
+    /// Complete split; percentages must add up to 100.
+    pub splits: Vec<TrafficSplitRequest>,
+    /// Note recorded in the history; the change is attributed to the API key.
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ActiveDeploymentResponse {
+    #[schema(value_type = crate::web::openapi::ObjectIdSchema)]
+    pub predictor_id: ObjectId,
+    pub traffic_percentage: f64,
+    pub predictor: Option<PredictorDocument>,
+}
+
+impl From<ActiveDeploymentDetails> for ActiveDeploymentResponse {
+    fn from(details: ActiveDeploymentDetails) -> Self {
+        Self {
+            predictor_id: details.predictor_id,
+            traffic_percentage: details.traffic_percentage,
+            predictor: details.predictor,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DeploymentResponse {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<crate::web::openapi::ObjectIdSchema>)]
+    pub id: Option<ObjectId>,
+    pub prediction_type: String,
+    pub active_deployments: Vec<ActiveDeploymentResponse>,
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[schema(value_type = crate::web::openapi::BsonDateTimeSchema)]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<DeploymentDetails> for DeploymentResponse {
+    fn from(details: DeploymentDetails) -> Self {
+        Self {
+            id: details.id,
+            prediction_type: details.prediction_type,
+            active_deployments: details
+                .active_deployments
+                .into_iter()
+                .map(ActiveDeploymentResponse::from)
+                .collect(),
+            created_at: details.created_at,
+            updated_at: details.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DeploymentsResponse {
+    pub deployments: Vec<DeploymentResponse>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DeploymentHistoryResponse {
+    pub prediction_type: String,
+    pub history: Vec<DeploymentHistoryDocument>,
+}
+
+/// List deployments
+#[utoipa::path(
+    get,
+    path = "/deployments",
+    tag = "deployments",
+    responses(
+        (status = 200, description = "The deployment of every prediction type", body = DeploymentsResponse),
+    )
+)]
+pub async fn list_deployments(
+    State(app_state): State<AppState>,
+) -> Result<Json<DeploymentsResponse>, AppError> {
+    let deployments = app_state.deployment_service.list_deployments().await?;
+
+    let response = DeploymentsResponse {
+        deployments: deployments
+            .into_iter()
+            .map(DeploymentResponse::from)
+            .collect(),
+    };
+    Ok(Json(response))
+}
+
+/// Get the deployment of a prediction type
+#[utoipa::path(
+    get,
+    path = "/deployments/{prediction_type}",
+    tag = "deployments",
+    params(("prediction_type" = String, Path)),
+    responses(
+        (status = 200, description = "The deployment", body = DeploymentResponse),
+        (status = 404, description = "No deployment for this prediction type", body = ErrorResponse),
+    )
+)]
+pub async fn get_deployment(
+    Path(prediction_type): Path<String>,
+    State(app_state): State<AppState>,
+) -> Result<Json<DeploymentResponse>, AppError> {
+    match app_state
+        .deployment_service
+        .get_deployment(&prediction_type)
+        .await?
+    {
+        Some(deployment) => Ok(Json(deployment.into())),
+        _none => Err(AppError::not_found(format!(
+            "no deployment for prediction type '{}'",
+            prediction_type
+        ))),
+    }
+}
+
+/// List the traffic changes of a prediction type
+#[utoipa::path(
+    get,
+    path = "/deployments/{prediction_type}/history",
+    tag = "deployments",
+    params(("prediction_type" = String, Path), DeploymentHistoryQuery),
+    responses(
+        (status = 200, description = "Traffic changes, newest first", body = DeploymentHistoryResponse),
+        (status = 400, description = "Invalid parameter", body = ErrorResponse),
+    )
+)]
+pub async fn get_deployment_history(
+    Path(prediction_type): Path<String>,
+    Query(params): Query<DeploymentHistoryQuery>,
+    State(app_state): State<AppState>,
+) -> Result<Json<DeploymentHistoryResponse>, AppError> {
+    let limit = page_size(&app_state.limits, params.limit)?;
+
+    let history = app_state
+        .deployment_service
+        .get_deployment_history(&prediction_type, Some(limit), params.skip)
+        .await?;
+
+    let response = DeploymentHistoryResponse {
+        prediction_type,
+        history,
+    };
+    Ok(Json(response))
+}
+
+/// Replace the traffic split of a prediction type
+#[utoipa::path(
+    put,
+    path = "/deployments/{prediction_type}/traffic",
+    tag = "deployments",
+    params(("prediction_type" = String, Path)),
+    request_body = UpdateTrafficRequest,
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "The updated deployment", body = DeploymentResponse),
+        (status = 400, description = "Invalid split", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key lacks the operator role", body = ErrorResponse),
+        (status = 409, description = "A rollout is active for the prediction type", body = ErrorResponse),
+    )
+)]
+pub async fn update_deployment_traffic(
+    Path(prediction_type): Path<String>,
+    State(app_state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    JsonBody(request): JsonBody<UpdateTrafficRequest>,
+) -> Result<Json<DeploymentResponse>, AppError> {
+    let change = TrafficSplitChange {
+        splits: request
+            .splits
+            .into_iter()
+            .map(|split| TrafficSplit {
+                predictor_id: split.predictor_id,
+                traffic_percentage: split.traffic_percentage,
+            })
+            .collect(),
+        changed_by: caller.name,
+        reason: request.reason,
+    };
+
+    let deployment = app_state
+        .rollout_service
+        .update_traffic_split(&prediction_type, change)
+        .await?;
+
+    Ok(Json(deployment.into()))
+}
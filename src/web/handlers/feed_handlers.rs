@@ -0,0 +1,94 @@
+use axum::{extract::State, http::StatusCode, response::Json};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    database::repositories::models::feed_source_repository_models::FeedSourceDocument,
+    error::{AppError, ErrorResponse},
+    web::{extractors::JsonBody, routes::AppState},
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct NewFeedSourceRequest {
+    /// URL of the RSS or Atom feed.
+    pub url: String,
+    /// `source.name` of the articles, the title of the feed when unset.
+    pub name: Option<String>,
+    /// `source.id` of the articles, e.g. `bbc-news`.
+    pub source_id: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeedSourcesResponse {
+    pub feeds: Vec<FeedSourceDocument>,
+}
+
+/// Register a feed
+#[utoipa::path(
+    post,
+    path = "/admin/feeds",
+    tag = "admin",
+    request_body = NewFeedSourceRequest,
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 201, description = "The feed, polled from the next run of the feed worker", body = FeedSourceDocument),
+        (status = 400, description = "Invalid url or name", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key is not an admin key", body = ErrorResponse),
+        (status = 409, description = "The feed is already registered", body = ErrorResponse),
+    )
+)]
+pub async fn create_feed_source(
+    State(app_state): State<AppState>,
+    JsonBody(request): JsonBody<NewFeedSourceRequest>,
+) -> Result<(StatusCode, Json<FeedSourceDocument>), AppError> {
+    let feed_source = app_state
+        .feed_service
+        .add_feed_source(&request.url, request.name, request.source_id)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(feed_source)))
+}
+
+/// List feeds
+#[utoipa::path(
+    get,
+    path = "/admin/feeds",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "Every registered feed with the outcome of its last fetch", body = FeedSourcesResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key is not an admin key", body = ErrorResponse),
+    )
+)]
+pub async fn list_feed_sources(
+    State(app_state): State<AppState>,
+) -> Result<Json<FeedSourcesResponse>, AppError> {
+    let feeds = app_state.feed_service.list_feed_sources().await?;
+
+    Ok(Json(FeedSourcesResponse { feeds }))
+}
+
+/// List failing feeds
+///
+/// Feeds whose last fetch failed, with the error and the number of failed
+/// fetches in a row, the longest failing first.
+#[utoipa::path(
+    get,
+    path = "/admin/feeds/errors",
+    tag = "admin",
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "The failing feeds", body = FeedSourcesResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key is not an admin key", body = ErrorResponse),
+    )
+)]
+pub async fn list_feed_errors(
+    State(app_state): State<AppState>,
+) -> Result<Json<FeedSourcesResponse>, AppError> {
+    let feeds = app_state.feed_service.list_failing_feed_sources().await?;
+
+    Ok(Json(FeedSourcesResponse { feeds }))
+}
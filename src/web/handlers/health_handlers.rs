@@ -1,13 +1,66 @@
-use axum::response::Json;
+use axum::{extract::State, http::StatusCode, response::Json};
 use serde::Serialize;
+use utoipa::ToSchema;
 
-#[derive(Serialize)]
+use crate::services::health_service::ReadinessReport;
+use crate::web::routes::AppState;
+
+#[derive(Serialize, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
 }
 
+/// Health check
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses((status = 200, description = "The server is running", body = HealthResponse))
+)]
 pub async fn health_check() -> Json<HealthResponse> {
     Json(HealthResponse {
         status: "healthy".to_string(),
     })
 }
+
+/// Liveness probe
+///
+/// Only tells whether the process is serving requests; dependencies are
+/// covered by `readiness_check`.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses((status = 200, description = "The server is running", body = HealthResponse))
+)]
+pub async fn liveness_check() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "alive".to_string(),
+    })
+}
+
+/// Readiness probe
+///
+/// Checks that MongoDB is reachable and holds the expected collections and indexes.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "No check failed, though some may be degraded", body = ReadinessReport),
+        (status = 503, description = "At least one check failed", body = ReadinessReport),
+    )
+)]
+pub async fn readiness_check(
+    State(app_state): State<AppState>,
+) -> (StatusCode, Json<ReadinessReport>) {
+    let report = app_state.health_service.check_readiness().await;
+
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(report))
+}
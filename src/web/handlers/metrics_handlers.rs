@@ -1,47 +1,149 @@
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
     response::Json,
 };
+use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::database::repositories::models::metrics_repository_models::{
-    MetricBinsAggregation, MetricsDocument,
+    MetricBinsAggregation, MetricInterval, MetricQuantile, MetricTimeseries, MetricsDocument,
 };
+use crate::database::repositories::models::pagination_models::PageCursor;
+use crate::error::{AppError, ErrorResponse};
+use crate::services::metrics_service::{
+    ComparisonTest, MetricComparison, MetricComparisonRequest, MetricsIngestionResult, NewMetric,
+};
+use crate::web::extractors::{JsonBody, Query};
+use crate::web::handlers::page_size;
 use crate::web::routes::AppState;
 
-#[derive(Deserialize)]
+const MAX_SUMMARY_QUANTILES: usize = 20;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct MetricsListQuery {
     pub metric_name: String,
     pub limit: Option<i64>,
     pub skip: Option<u64>,
+    pub cursor: Option<String>,
     pub prediction_type: Option<String>,
     pub predictor_version: Option<String>,
 }
 
-#[derive(Deserialize)]
+/// `quantiles` is a comma-separated list of extra quantiles in `[0, 1]`,
+/// e.g. `quantiles=0.5,0.99`.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct MetricsSummaryQuery {
+    #[param(required = true)]
     pub metric_name: Option<String>,
     pub prediction_type: Option<String>,
     pub predictor_version: Option<String>,
     pub num_days: Option<i32>,
+    pub quantiles: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct MetricBinsQuery {
+    #[param(required = true)]
     pub metric_name: Option<String>,
+    #[param(required = true)]
     pub num_bins: Option<i32>,
     pub prediction_type: Option<String>,
     pub predictor_version: Option<String>,
     pub num_days: Option<i32>,
 }
 
-#[derive(Serialize)]
+/// `interval` defaults to `1h`; `group_by` names a tag (e.g. `predictor_version`)
+/// whose values split the result into one series each.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MetricTimeseriesQuery {
+    #[param(required = true)]
+    pub metric_name: Option<String>,
+    pub interval: Option<String>,
+    pub group_by: Option<String>,
+    pub prediction_type: Option<String>,
+    pub predictor_version: Option<String>,
+    pub num_days: Option<i32>,
+}
+
+/// `a` and `b` are the `predictor_version` tags to compare. `test` is `welch`
+/// (default) or `mann_whitney`, and `alpha` the significance level (0.05).
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MetricsCompareQuery {
+    #[param(required = true)]
+    pub metric_name: Option<String>,
+    pub prediction_type: Option<String>,
+    #[param(required = true)]
+    pub a: Option<String>,
+    #[param(required = true)]
+    pub b: Option<String>,
+    pub num_days: Option<i32>,
+    pub test: Option<ComparisonTest>,
+    pub alpha: Option<f64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct NewMetricRequest {
+    pub metric_name: String,
+    pub metric_value: f64,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+impl From<NewMetricRequest> for NewMetric {
+    fn from(request: NewMetricRequest) -> Self {
+        Self {
+            metric_name: request.metric_name,
+            metric_value: request.metric_value,
+            description: request.description,
+            tags: request.tags,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateMetricResponse {
+    #[schema(value_type = crate::web::openapi::ObjectIdSchema)]
+    pub id: ObjectId,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct InsertedMetricResponse {
+    pub index: usize,
+    #[schema(value_type = crate::web::openapi::ObjectIdSchema)]
+    pub id: ObjectId,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MetricErrorResponse {
+    pub index: usize,
+    pub message: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MetricsBatchResponse {
+    pub inserted_count: usize,
+    pub failed_count: usize,
+    pub inserted: Vec<InsertedMetricResponse>,
+    pub errors: Vec<MetricErrorResponse>,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct MetricsListResponse {
     pub metrics: Vec<MetricsDocument>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct MetricAggregationResponse {
     pub metric_name: String,
     pub avg_value: f64,
@@ -49,46 +151,104 @@ pub struct MetricAggregationResponse {
     pub count: i64,
     pub min_value: f64,
     pub max_value: f64,
+    pub std_dev_value: f64,
+    pub p50_value: f64,
+    pub p90_value: f64,
+    pub p95_value: f64,
+    pub p99_value: f64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub quantiles: Vec<MetricQuantile>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct MetricBinsAggregationResponse {
     pub metric_bins: Vec<MetricBinsAggregation>,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct MetricTimeseriesResponse {
+    pub metric_name: String,
+    pub interval: String,
+    pub group_by: Option<String>,
+    pub series: Vec<MetricTimeseries>,
+}
+
+/// List the values of a metric
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "metrics",
+    params(MetricsListQuery),
+    responses(
+        (status = 200, description = "A page of values, newest first", body = MetricsListResponse),
+        (status = 400, description = "Invalid parameter", body = ErrorResponse),
+    )
+)]
 pub async fn list_metrics(
     Query(params): Query<MetricsListQuery>,
     State(app_state): State<AppState>,
-) -> Result<Json<MetricsListResponse>, StatusCode> {
-    match app_state
+) -> Result<Json<MetricsListResponse>, AppError> {
+    let page_cursor = match params.cursor.as_deref().map(PageCursor::decode) {
+        Some(Some(page_cursor)) => Some(page_cursor),
+        Some(_none) => return Err(AppError::invalid_param("cursor", "cursor is invalid")),
+        _none => None,
+    };
+
+    let limit = page_size(&app_state.limits, params.limit)?;
+
+    let paginated_metrics = app_state
         .metrics_service
         .list_metrics(
             &params.metric_name,
-            params.limit,
+            Some(limit),
             params.skip,
+            page_cursor.as_ref(),
             params.prediction_type,
             params.predictor_version,
         )
-        .await
-    {
-        Ok(metrics) => {
-            let response = MetricsListResponse { metrics };
-            Ok(Json(response))
-        }
-        Err(e) => {
-            log::error!("Service error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+        .await?;
+
+    let response = MetricsListResponse {
+        metrics: paginated_metrics.metrics,
+        next_cursor: paginated_metrics.next_cursor.map(|cursor| cursor.encode()),
+        prev_cursor: paginated_metrics.prev_cursor.map(|cursor| cursor.encode()),
+    };
+    Ok(Json(response))
 }
 
+/// Summarize a metric
+#[utoipa::path(
+    get,
+    path = "/metrics/summary",
+    tag = "metrics",
+    params(MetricsSummaryQuery),
+    responses(
+        (status = 200, description = "Summary statistics of the matching values", body = MetricAggregationResponse),
+        (status = 400, description = "Missing or invalid parameter", body = ErrorResponse),
+        (status = 404, description = "No matching values", body = ErrorResponse),
+    )
+)]
 pub async fn get_metric_summary_aggregation(
     Query(params): Query<MetricsSummaryQuery>,
     State(app_state): State<AppState>,
-) -> Result<Json<MetricAggregationResponse>, StatusCode> {
+) -> Result<Json<MetricAggregationResponse>, AppError> {
     let metric_name = match params.metric_name {
         Some(name) => name,
-        _none => return Err(StatusCode::BAD_REQUEST),
+        _none => return Err(AppError::missing_param("metric_name")),
+    };
+
+    let quantiles = match params.quantiles.as_deref().map(parse_quantiles) {
+        Some(Some(quantiles)) => quantiles,
+        Some(_none) => {
+            return Err(AppError::invalid_param(
+                "quantiles",
+                format!(
+                    "quantiles must be at most {} comma-separated numbers between 0 and 1",
+                    MAX_SUMMARY_QUANTILES
+                ),
+            ));
+        }
+        _none => Vec::new(),
     };
 
     match app_state
@@ -98,10 +258,11 @@ pub async fn get_metric_summary_aggregation(
             params.prediction_type.as_deref(),
             params.predictor_version.as_deref(),
             params.num_days,
+            &quantiles,
         )
-        .await
+        .await?
     {
-        Ok(Some(aggregation)) => {
+        Some(aggregation) => {
             let response = MetricAggregationResponse {
                 metric_name,
                 avg_value: aggregation.avg_value,
@@ -109,32 +270,48 @@ pub async fn get_metric_summary_aggregation(
                 count: aggregation.count,
                 min_value: aggregation.min_value,
                 max_value: aggregation.max_value,
+                std_dev_value: aggregation.std_dev_value,
+                p50_value: aggregation.p50_value,
+                p90_value: aggregation.p90_value,
+                p95_value: aggregation.p95_value,
+                p99_value: aggregation.p99_value,
+                quantiles: aggregation.quantiles,
             };
             Ok(Json(response))
         }
-        Ok(_none) => Err(StatusCode::NOT_FOUND),
-        Err(e) => {
-            log::error!("Service error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+        _none => Err(AppError::not_found(format!(
+            "no data for metric '{}'",
+            metric_name
+        ))),
     }
 }
 
+/// Histogram of a metric
+#[utoipa::path(
+    get,
+    path = "/metrics/bins",
+    tag = "metrics",
+    params(MetricBinsQuery),
+    responses(
+        (status = 200, description = "Equal-width bins between the smallest and largest values", body = MetricBinsAggregationResponse),
+        (status = 400, description = "Missing or invalid parameter", body = ErrorResponse),
+    )
+)]
 pub async fn get_metric_bins_aggregation(
     Query(params): Query<MetricBinsQuery>,
     State(app_state): State<AppState>,
-) -> Result<Json<MetricBinsAggregationResponse>, StatusCode> {
+) -> Result<Json<MetricBinsAggregationResponse>, AppError> {
     let metric_name = match params.metric_name {
         Some(name) => name,
-        _none => return Err(StatusCode::BAD_REQUEST),
+        _none => return Err(AppError::missing_param("metric_name")),
     };
 
     let num_bins = match params.num_bins {
         Some(num) => num,
-        _none => return Err(StatusCode::BAD_REQUEST),
+        _none => return Err(AppError::missing_param("num_bins")),
     };
 
-    match app_state
+    let aggregation = app_state
         .metrics_service
         .get_metric_bins_aggregation(
             &metric_name,
@@ -143,17 +320,336 @@ pub async fn get_metric_bins_aggregation(
             params.predictor_version.as_deref(),
             params.num_days,
         )
-        .await
+        .await?;
+
+    let response = MetricBinsAggregationResponse {
+        metric_bins: aggregation,
+    };
+    Ok(Json(response))
+}
+
+/// Time series of a metric
+#[utoipa::path(
+    get,
+    path = "/metrics/timeseries",
+    tag = "metrics",
+    params(MetricTimeseriesQuery),
+    responses(
+        (status = 200, description = "Per-bucket statistics, one series per group", body = MetricTimeseriesResponse),
+        (status = 400, description = "Missing or invalid parameter", body = ErrorResponse),
+    )
+)]
+pub async fn get_metric_timeseries_aggregation(
+    Query(params): Query<MetricTimeseriesQuery>,
+    State(app_state): State<AppState>,
+) -> Result<Json<MetricTimeseriesResponse>, AppError> {
+    let metric_name = match params.metric_name {
+        Some(name) => name,
+        _none => return Err(AppError::missing_param("metric_name")),
+    };
+
+    let interval_param = params.interval.unwrap_or_else(|| "1h".to_string());
+    let interval = match MetricInterval::parse(&interval_param) {
+        Some(interval) => interval,
+        _none => {
+            return Err(AppError::invalid_param(
+                "interval",
+                "interval must be a positive count followed by m, h, d or w (e.g. 1h)",
+            ));
+        }
+    };
+
+    let num_days = params.num_days.unwrap_or(7);
+    if num_days <= 0 {
+        return Err(AppError::invalid_param(
+            "num_days",
+            "num_days must be positive",
+        ));
+    }
+
+    // Keeps a fine interval over a long window from producing an unbounded response
+    let bucket_count =
+        chrono::Duration::days(num_days as i64).num_seconds() / interval.duration().num_seconds();
+    if bucket_count > app_state.limits.max_timeseries_buckets {
+        return Err(AppError::invalid_param(
+            "interval",
+            format!(
+                "interval is too fine for {} days, at most {} buckets are allowed",
+                num_days, app_state.limits.max_timeseries_buckets
+            ),
+        ));
+    }
+
+    // The tag ends up in a document path, so only plain identifiers are accepted
+    if params.group_by.as_deref().is_some_and(|tag| {
+        tag.is_empty()
+            || !tag
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    }) {
+        return Err(AppError::invalid_param(
+            "group_by",
+            "group_by must only contain letters, digits, '_' and '-'",
+        ));
+    }
+
+    let series = app_state
+        .metrics_service
+        .get_metric_timeseries_aggregation(
+            &metric_name,
+            interval,
+            params.group_by.as_deref(),
+            params.prediction_type.as_deref(),
+            params.predictor_version.as_deref(),
+            Some(num_days),
+        )
+        .await?;
+
+    let response = MetricTimeseriesResponse {
+        metric_name,
+        interval: interval_param,
+        group_by: params.group_by,
+        series,
+    };
+    Ok(Json(response))
+}
+
+/// Compare a metric between two predictor versions
+#[utoipa::path(
+    get,
+    path = "/metrics/compare",
+    tag = "metrics",
+    params(MetricsCompareQuery),
+    responses(
+        (status = 200, description = "Sample summaries, significance test and effect size", body = MetricComparison),
+        (status = 400, description = "Missing or invalid parameter", body = ErrorResponse),
+        (status = 404, description = "No values for one of the versions", body = ErrorResponse),
+    )
+)]
+pub async fn compare_metric_versions(
+    Query(params): Query<MetricsCompareQuery>,
+    State(app_state): State<AppState>,
+) -> Result<Json<MetricComparison>, AppError> {
+    let metric_name = params
+        .metric_name
+        .ok_or_else(|| AppError::missing_param("metric_name"))?;
+    let version_a = params.a.ok_or_else(|| AppError::missing_param("a"))?;
+    let version_b = params.b.ok_or_else(|| AppError::missing_param("b"))?;
+
+    let num_days = params.num_days.unwrap_or(7);
+    if num_days <= 0 {
+        return Err(AppError::invalid_param(
+            "num_days",
+            "num_days must be positive",
+        ));
+    }
+
+    let alpha = params.alpha.unwrap_or(0.05);
+    if !(alpha > 0.0 && alpha < 1.0) {
+        return Err(AppError::invalid_param(
+            "alpha",
+            "alpha must be strictly between 0 and 1",
+        ));
+    }
+
+    let request = MetricComparisonRequest {
+        metric_name,
+        prediction_type: params.prediction_type,
+        version_a,
+        version_b,
+        num_days,
+        test: params.test.unwrap_or_default(),
+        alpha,
+    };
+
+    match app_state
+        .metrics_service
+        .compare_predictor_versions(&request)
+        .await?
     {
-        Ok(aggregation) => {
-            let response = MetricBinsAggregationResponse {
-                metric_bins: aggregation,
-            };
-            Ok(Json(response))
+        Some(comparison) => Ok(Json(comparison)),
+        _none => Err(AppError::not_found(format!(
+            "no data for metric '{}' for both versions '{}' and '{}'",
+            request.metric_name, request.version_a, request.version_b
+        ))),
+    }
+}
+
+/// Record a metric value
+#[utoipa::path(
+    post,
+    path = "/metrics",
+    tag = "metrics",
+    request_body = NewMetricRequest,
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 201, description = "The value was recorded", body = CreateMetricResponse),
+        (status = 400, description = "Invalid metric", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key lacks the ingest role", body = ErrorResponse),
+    )
+)]
+pub async fn create_metric(
+    State(app_state): State<AppState>,
+    JsonBody(request): JsonBody<NewMetricRequest>,
+) -> Result<(StatusCode, Json<CreateMetricResponse>), AppError> {
+    let result = app_state
+        .metrics_service
+        .ingest_metrics(vec![(0, request.into())])
+        .await?;
+
+    match (result.inserted.first(), result.failures.first()) {
+        (Some((_, id)), _) => Ok((StatusCode::CREATED, Json(CreateMetricResponse { id: *id }))),
+        (_, Some(failure)) => Err(AppError::validation(failure.message.clone())),
+        _ => Err(AppError::internal(
+            "metric was neither inserted nor rejected",
+        )),
+    }
+}
+
+/// Record a batch of metric values
+///
+/// Accepts either a JSON array (`application/json`) or newline-delimited JSON
+/// (`application/x-ndjson`), as told by `Content-Type`, reporting malformed
+/// or invalid items individually.
+#[utoipa::path(
+    post,
+    path = "/metrics/batch",
+    tag = "metrics",
+    request_body(content(
+        (Vec<NewMetricRequest> = "application/json"),
+        (NewMetricRequest = "application/x-ndjson"),
+    )),
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 201, description = "Every value was recorded", body = MetricsBatchResponse),
+        (status = 207, description = "Some values were recorded", body = MetricsBatchResponse),
+        (status = 400, description = "No value was recorded, or the body is not a JSON array or NDJSON", body = MetricsBatchResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key lacks the ingest role", body = ErrorResponse),
+        (status = 413, description = "Too many values", body = ErrorResponse),
+        (status = 415, description = "The Content-Type is neither JSON nor NDJSON", body = ErrorResponse),
+    )
+)]
+pub async fn create_metrics_batch(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<(StatusCode, Json<MetricsBatchResponse>), AppError> {
+    let media_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .map(|media_type| media_type.trim().to_ascii_lowercase());
+
+    let is_ndjson = match media_type.as_deref() {
+        Some("application/x-ndjson") => true,
+        Some("application/json") => false,
+        _ => {
+            return Err(AppError::unsupported_media_type(
+                "Content-Type must be application/json or application/x-ndjson",
+            ));
         }
-        Err(e) => {
-            log::error!("Service error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+    };
+
+    let items: Vec<Result<NewMetricRequest, String>> = if is_ndjson {
+        body.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(line_number, line)| {
+                serde_json::from_str(line).map_err(|e| format!("line {}: {}", line_number + 1, e))
+            })
+            .collect()
+    } else {
+        serde_json::from_str::<Vec<serde_json::Value>>(&body)
+            .map_err(|e| AppError::validation(format!("malformed metrics batch: {}", e)))?
+            .into_iter()
+            .map(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+            .collect()
+    };
+
+    if items.is_empty() {
+        return Err(AppError::validation("the batch contains no metrics"));
+    }
+
+    if items.len() > app_state.limits.max_metrics_batch_size {
+        return Err(AppError::payload_too_large(format!(
+            "a batch holds at most {} metrics, got {}",
+            app_state.limits.max_metrics_batch_size,
+            items.len()
+        )));
+    }
+
+    let mut parse_errors = Vec::new();
+    let mut metrics = Vec::new();
+
+    for (index, item) in items.into_iter().enumerate() {
+        match item {
+            Ok(request) => metrics.push((index, request.into())),
+            Err(message) => parse_errors.push(MetricErrorResponse { index, message }),
         }
     }
+
+    let result = app_state.metrics_service.ingest_metrics(metrics).await?;
+    let response = batch_response(result, parse_errors);
+
+    let status = match (response.inserted_count, response.failed_count) {
+        (_, 0) => StatusCode::CREATED,
+        (0, _) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::MULTI_STATUS,
+    };
+
+    Ok((status, Json(response)))
+}
+
+fn parse_quantiles(value: &str) -> Option<Vec<f64>> {
+    let quantiles = value
+        .split(',')
+        .map(str::trim)
+        .filter(|quantile| !quantile.is_empty())
+        .map(|quantile| {
+            quantile
+                .parse::<f64>()
+                .ok()
+                .filter(|quantile| (0.0..=1.0).contains(quantile))
+        })
+        .collect::<Option<Vec<f64>>>()?;
+
+    if quantiles.len() > MAX_SUMMARY_QUANTILES {
+        return None;
+    }
+
+    Some(quantiles)
+}
+
+fn batch_response(
+    result: MetricsIngestionResult,
+    parse_errors: Vec<MetricErrorResponse>,
+) -> MetricsBatchResponse {
+    let mut errors: Vec<MetricErrorResponse> = parse_errors
+        .into_iter()
+        .chain(
+            result
+                .failures
+                .into_iter()
+                .map(|failure| MetricErrorResponse {
+                    index: failure.index,
+                    message: failure.message,
+                }),
+        )
+        .collect();
+    errors.sort_by_key(|error| error.index);
+
+    let inserted: Vec<InsertedMetricResponse> = result
+        .inserted
+        .into_iter()
+        .map(|(index, id)| InsertedMetricResponse { index, id })
+        .collect();
+
+    MetricsBatchResponse {
+        inserted_count: inserted.len(),
+        failed_count: errors.len(),
+        inserted,
+        errors,
+    }
 }
@@ -1,4 +1,27 @@
+pub mod api_key_handlers;
+pub mod article_feed_handlers;
 pub mod articles_handlers;
+pub mod cache_handlers;
+pub mod deployment_handlers;
+pub mod feed_handlers;
 pub mod health_handlers;
 pub mod metrics_handlers;
 pub mod predictor_handlers;
+pub mod rollout_handlers;
+
+use crate::config::LimitsConfig;
+use crate::error::AppError;
+
+/// Resolves the `limit` of a listing against the configured page sizes.
+pub fn page_size(limits: &LimitsConfig, limit: Option<i64>) -> Result<i64, AppError> {
+    match limit {
+        Some(limit) if !(1..=limits.max_page_size).contains(&limit) => {
+            Err(AppError::invalid_param(
+                "limit",
+                format!("limit must be between 1 and {}", limits.max_page_size),
+            ))
+        }
+        Some(limit) => Ok(limit),
+        _none => Ok(limits.default_page_size),
+    }
+}
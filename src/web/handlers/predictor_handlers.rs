@@ -1,117 +1,131 @@
-use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::Json,
-};
+use axum::{extract::State, response::Json};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
     database::repositories::models::predictor_repository_models::PredictorDocument,
-    web::routes::AppState,
+    error::{AppError, ErrorResponse},
+    web::{extractors::Query, routes::AppState},
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PredictorsQuery {
+    #[param(required = true)]
     pub prediction_type: Option<String>,
+    /// Only returns predictors with at least this traffic percentage.
     pub min_traffic: Option<i32>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct PredictorVersionsQuery {
+    #[param(required = true)]
     pub prediction_type: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct PredictorsResponse {
     pub prediction_type: String,
     pub predictors: Vec<PredictorDocument>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct PredictionTypesResponse {
     pub prediction_types: Vec<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct PredictorVersionsResponse {
     pub prediction_type: String,
     pub predictor_versions: Vec<i32>,
 }
 
+/// List prediction types
+#[utoipa::path(
+    get,
+    path = "/predictors/types",
+    tag = "predictors",
+    responses(
+        (status = 200, description = "Prediction types, sorted", body = PredictionTypesResponse),
+    )
+)]
 pub async fn get_prediction_types(
     State(app_state): State<AppState>,
-) -> Result<Json<PredictionTypesResponse>, StatusCode> {
-    match app_state.predictor_service.get_prediction_types().await {
-        Ok(prediction_types_set) => {
-            let mut prediction_types: Vec<String> = prediction_types_set.into_iter().collect();
-            prediction_types.sort();
-
-            let response = PredictionTypesResponse { prediction_types };
-            Ok(Json(response))
-        }
-        Err(e) => {
-            log::error!("Service error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+) -> Result<Json<PredictionTypesResponse>, AppError> {
+    let prediction_types_set = app_state.predictor_service.get_prediction_types().await?;
+
+    let mut prediction_types: Vec<String> = prediction_types_set.into_iter().collect();
+    prediction_types.sort();
+
+    let response = PredictionTypesResponse { prediction_types };
+    Ok(Json(response))
 }
 
+/// List the predictor versions of a prediction type
+#[utoipa::path(
+    get,
+    path = "/predictors/versions",
+    tag = "predictors",
+    params(PredictorVersionsQuery),
+    responses(
+        (status = 200, description = "Predictor versions, sorted", body = PredictorVersionsResponse),
+        (status = 400, description = "Missing prediction_type", body = ErrorResponse),
+    )
+)]
 pub async fn get_predictor_versions(
     Query(params): Query<PredictorVersionsQuery>,
     State(app_state): State<AppState>,
-) -> Result<Json<PredictorVersionsResponse>, StatusCode> {
+) -> Result<Json<PredictorVersionsResponse>, AppError> {
     let prediction_type = match params.prediction_type {
         Some(prediction_type) => prediction_type,
-        _none => return Err(StatusCode::BAD_REQUEST),
+        _none => return Err(AppError::missing_param("prediction_type")),
     };
 
-    match app_state
+    let predictor_versions_set = app_state
         .predictor_service
         .get_predictor_versions(&prediction_type)
-        .await
-    {
-        Ok(predictor_versions_set) => {
-            // Convert HashSet to Vec and sort for consistent ordering
-            let mut predictor_versions: Vec<i32> = predictor_versions_set.into_iter().collect();
-            predictor_versions.sort();
-
-            let response = PredictorVersionsResponse {
-                prediction_type,
-                predictor_versions,
-            };
-            Ok(Json(response))
-        }
-        Err(e) => {
-            log::error!("Service error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+        .await?;
+
+    // Convert HashSet to Vec and sort for consistent ordering
+    let mut predictor_versions: Vec<i32> = predictor_versions_set.into_iter().collect();
+    predictor_versions.sort();
+
+    let response = PredictorVersionsResponse {
+        prediction_type,
+        predictor_versions,
+    };
+    Ok(Json(response))
 }
 
+/// List the predictors of a prediction type
+#[utoipa::path(
+    get,
+    path = "/predictors",
+    tag = "predictors",
+    params(PredictorsQuery),
+    responses(
+        (status = 200, description = "Predictors by ascending version", body = PredictorsResponse),
+        (status = 400, description = "Missing prediction_type", body = ErrorResponse),
+    )
+)]
 pub async fn get_predictors(
     Query(params): Query<PredictorsQuery>,
     State(app_state): State<AppState>,
-) -> Result<Json<PredictorsResponse>, StatusCode> {
+) -> Result<Json<PredictorsResponse>, AppError> {
     let prediction_type = match params.prediction_type {
         Some(prediction_type) => prediction_type,
-        _none => return Err(StatusCode::BAD_REQUEST),
+        _none => return Err(AppError::missing_param("prediction_type")),
     };
 
-    match app_state
+    let predictors = app_state
         .predictor_service
         .get_predictors_by_type(&prediction_type, params.min_traffic)
-        .await
-    {
-        Ok(predictors) => {
-            let response = PredictorsResponse {
-                prediction_type,
-                predictors,
-            };
-            Ok(Json(response))
-        }
-        Err(e) => {
-            log::error!("Service error: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
+        .await?;
+
+    let response = PredictorsResponse {
+        prediction_type,
+        predictors,
+    };
+    Ok(Json(response))
 }
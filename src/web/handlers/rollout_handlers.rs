@@ -0,0 +1,187 @@
+use axum::{Extension, extract::State, http::StatusCode, response::Json};
+use chrono::{DateTime, Utc};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    database::repositories::models::rollout_repository_models::{
+        RolloutDocument, RolloutGateDocument, RolloutStatus,
+    },
+    error::{AppError, ErrorResponse},
+    services::{api_key_service::Caller, rollout_service::RolloutPlan},
+    web::{
+        extractors::{JsonBody, Path, Query},
+        handlers::page_size,
+        routes::AppState,
+    },
+};
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateRolloutRequest {
+    pub prediction_type: String,
+    /// Hex string of the predictor to roll out.
+    #[schema(value_type = String)]
+    pub predictor_id: ObjectId,
+    /// Strictly increasing traffic percentages of the predictor, between 1 and 100.
+    pub steps: Vec<i32>,
+    pub step_interval_hours: i64,
+    pub gate: Option<RolloutGateDocument>,
+    /// When the first step is applied, immediately when unset.
+    pub start_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RolloutsQuery {
+    pub prediction_type: Option<String>,
+    pub status: Option<RolloutStatus>,
+    pub limit: Option<i64>,
+    pub skip: Option<u64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CancelRolloutQuery {
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RolloutsResponse {
+    pub rollouts: Vec<RolloutDocument>,
+}
+
+/// Start a rollout
+#[utoipa::path(
+    post,
+    path = "/rollouts",
+    tag = "rollouts",
+    request_body = CreateRolloutRequest,
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 201, description = "The created rollout", body = RolloutDocument),
+        (status = 400, description = "Invalid plan", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key lacks the operator role", body = ErrorResponse),
+        (status = 409, description = "A rollout is already active for this prediction type", body = ErrorResponse),
+    )
+)]
+pub async fn create_rollout(
+    State(app_state): State<AppState>,
+    Extension(caller): Extension<Caller>,
+    JsonBody(request): JsonBody<CreateRolloutRequest>,
+) -> Result<(StatusCode, Json<RolloutDocument>), AppError> {
+    let plan = RolloutPlan {
+        prediction_type: request.prediction_type,
+        predictor_id: request.predictor_id,
+        steps: request.steps,
+        step_interval_hours: request.step_interval_hours,
+        gate: request.gate,
+        created_by: caller.name,
+        start_at: request.start_at,
+    };
+
+    let rollout = app_state.rollout_service.create_rollout(plan).await?;
+
+    Ok((StatusCode::CREATED, Json(rollout)))
+}
+
+/// List rollouts
+#[utoipa::path(
+    get,
+    path = "/rollouts",
+    tag = "rollouts",
+    params(RolloutsQuery),
+    responses(
+        (status = 200, description = "Rollouts, newest first", body = RolloutsResponse),
+        (status = 400, description = "Invalid parameter", body = ErrorResponse),
+    )
+)]
+pub async fn list_rollouts(
+    Query(params): Query<RolloutsQuery>,
+    State(app_state): State<AppState>,
+) -> Result<Json<RolloutsResponse>, AppError> {
+    let limit = page_size(&app_state.limits, params.limit)?;
+
+    let rollouts = app_state
+        .rollout_service
+        .list_rollouts(
+            params.prediction_type.as_deref(),
+            params.status,
+            Some(limit),
+            params.skip,
+        )
+        .await?;
+
+    Ok(Json(RolloutsResponse { rollouts }))
+}
+
+/// Get a rollout
+#[utoipa::path(
+    get,
+    path = "/rollouts/{id}",
+    tag = "rollouts",
+    params(("id" = String, Path, description = "Rollout id")),
+    responses(
+        (status = 200, description = "The rollout", body = RolloutDocument),
+        (status = 400, description = "Invalid id", body = ErrorResponse),
+        (status = 404, description = "No such rollout", body = ErrorResponse),
+    )
+)]
+pub async fn get_rollout(
+    Path(rollout_id): Path<String>,
+    State(app_state): State<AppState>,
+) -> Result<Json<RolloutDocument>, AppError> {
+    let rollout_id = parse_rollout_id(&rollout_id)?;
+
+    match app_state.rollout_service.get_rollout(rollout_id).await? {
+        Some(rollout) => Ok(Json(rollout)),
+        _none => Err(AppError::not_found(format!(
+            "rollout {} does not exist",
+            rollout_id
+        ))),
+    }
+}
+
+/// Cancel an active rollout
+///
+/// The traffic split is left as it is.
+#[utoipa::path(
+    post,
+    path = "/rollouts/{id}/cancel",
+    tag = "rollouts",
+    params(("id" = String, Path, description = "Rollout id"), CancelRolloutQuery),
+    security(("api_key" = []), ("bearer" = [])),
+    responses(
+        (status = 200, description = "The cancelled rollout", body = RolloutDocument),
+        (status = 400, description = "Invalid id", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "The API key lacks the operator role", body = ErrorResponse),
+        (status = 404, description = "No such rollout", body = ErrorResponse),
+        (status = 409, description = "The rollout is no longer active", body = ErrorResponse),
+    )
+)]
+pub async fn cancel_rollout(
+    Path(rollout_id): Path<String>,
+    Query(params): Query<CancelRolloutQuery>,
+    State(app_state): State<AppState>,
+) -> Result<Json<RolloutDocument>, AppError> {
+    let rollout_id = parse_rollout_id(&rollout_id)?;
+
+    match app_state
+        .rollout_service
+        .cancel_rollout(rollout_id, params.reason.as_deref())
+        .await?
+    {
+        Some(rollout) => Ok(Json(rollout)),
+        _none => Err(AppError::not_found(format!(
+            "rollout {} does not exist",
+            rollout_id
+        ))),
+    }
+}
+
+fn parse_rollout_id(rollout_id: &str) -> Result<ObjectId, AppError> {
+    ObjectId::parse_str(rollout_id)
+        .map_err(|_| AppError::invalid_param("id", format!("'{}' is not a valid id", rollout_id)))
+}
@@ -0,0 +1,102 @@
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderMap, HeaderName, Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::database::repositories::models::api_key_repository_models::ApiKeyRole;
+use crate::error::AppError;
+use crate::services::api_key_service::Caller;
+use crate::web::routes::AppState;
+
+pub const API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+
+/// Role a route requires, or `None` if anyone may call it. Writes default to
+/// `operator`, so that a new write route is never left open by mistake.
+pub fn required_role(method: &Method, path: &str, public_reads: bool) -> Option<ApiKeyRole> {
+    if path.starts_with("/admin/") {
+        return Some(ApiKeyRole::Admin);
+    }
+
+    match *method {
+        Method::GET | Method::HEAD if public_reads => None,
+        Method::GET | Method::HEAD => Some(ApiKeyRole::Reader),
+        Method::POST
+            if matches!(
+                path,
+                "/articles" | "/articles/batch" | "/metrics" | "/metrics/batch"
+            ) =>
+        {
+            Some(ApiKeyRole::Ingest)
+        }
+        _ => Some(ApiKeyRole::Operator),
+    }
+}
+
+/// Checks the API key of the request against the role its route requires,
+/// and makes the authenticated `Caller` available to the handler.
+pub async fn authorize(
+    State(app_state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let path = match request.extensions().get::<MatchedPath>() {
+        Some(matched_path) => matched_path.as_str(),
+        _none => request.uri().path(),
+    };
+
+    let Some(required_role) = required_role(request.method(), path, app_state.auth.public_reads)
+    else {
+        return next.run(request).await;
+    };
+
+    let caller = match request.extensions().get::<Caller>() {
+        // Already authenticated by the rate limiter
+        Some(caller) => caller.clone(),
+        _none => {
+            let Some(key) = api_key(request.headers()) else {
+                return AppError::unauthorized(
+                    "an API key is required, in the X-API-Key header or as a Bearer token",
+                )
+                .into_response();
+            };
+
+            match app_state.api_key_service.authenticate(key).await {
+                Ok(Some(caller)) => caller,
+                Ok(_none) => {
+                    return AppError::unauthorized("the API key is invalid or revoked")
+                        .into_response();
+                }
+                Err(e) => return e.into_response(),
+            }
+        }
+    };
+
+    if caller.role < required_role {
+        return AppError::forbidden(format!(
+            "this route requires the {} role, the API key has the {} role",
+            required_role.as_str(),
+            caller.role.as_str()
+        ))
+        .into_response();
+    }
+
+    request.extensions_mut().insert(caller);
+
+    next.run(request).await
+}
+
+/// Reads the key from `X-API-Key`, or from `Authorization: Bearer <key>`.
+pub fn api_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(value) = headers.get(&API_KEY_HEADER) {
+        return value.to_str().ok().filter(|key| !key.is_empty());
+    }
+
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
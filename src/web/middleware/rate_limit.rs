@@ -0,0 +1,265 @@
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::HeaderName,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use log::error;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::auth;
+use crate::config::{RateLimitConfig, RateLimitsConfig};
+use crate::error::AppError;
+use crate::web::routes::AppState;
+
+const FORWARDED_FOR_HEADER: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// How often buckets that have refilled completely are dropped, so that
+/// one-off clients do not accumulate.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Routes sharing a budget. Each client gets a bucket per group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    Health,
+    Articles,
+    Aggregations,
+    Default,
+}
+
+impl RouteGroup {
+    pub fn of(path: &str) -> Self {
+        match path {
+            "/metrics/bins" | "/metrics/summary" | "/metrics/timeseries" | "/metrics/compare" => {
+                RouteGroup::Aggregations
+            }
+            _ if path == "/health" || path.starts_with("/health/") => RouteGroup::Health,
+            _ if path == "/articles" || path.starts_with("/articles/") => RouteGroup::Articles,
+            _ if path.starts_with("/feeds/articles.") => RouteGroup::Articles,
+            _ => RouteGroup::Default,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteGroup::Health => "health",
+            RouteGroup::Articles => "articles",
+            RouteGroup::Aggregations => "aggregations",
+            RouteGroup::Default => "default",
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+struct Buckets {
+    buckets: HashMap<(RouteGroup, String), Bucket>,
+    swept_at: Instant,
+}
+
+/// In-process token buckets, one per client and route group. Each instance of
+/// the API enforces its own limits.
+#[derive(Clone)]
+pub struct RateLimiter {
+    config: Arc<RateLimitsConfig>,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitsConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            buckets: Arc::new(Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                swept_at: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn limit(&self, group: RouteGroup) -> &RateLimitConfig {
+        match group {
+            RouteGroup::Health => &self.config.health,
+            RouteGroup::Articles => &self.config.articles,
+            RouteGroup::Aggregations => &self.config.aggregations,
+            RouteGroup::Default => &self.config.default,
+        }
+    }
+
+    /// Takes a token from the bucket of `client` for `group`, or returns how
+    /// long until one is available.
+    pub fn acquire(&self, group: RouteGroup, client: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut state = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if now.duration_since(state.swept_at) >= SWEEP_INTERVAL {
+            state.buckets.retain(|(group, _), bucket| {
+                let limit = self.limit(*group);
+                refilled(bucket, limit, now) < f64::from(limit.burst)
+            });
+            state.swept_at = now;
+        }
+
+        let limit = self.limit(group);
+        let bucket = state
+            .buckets
+            .entry((group, client.to_string()))
+            .or_insert(Bucket {
+                tokens: f64::from(limit.burst),
+                updated_at: now,
+            });
+
+        bucket.tokens = refilled(bucket, limit, now);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / refill_per_second(limit),
+            ))
+        }
+    }
+
+    /// Gives back a token taken by `acquire`.
+    pub fn refund(&self, group: RouteGroup, client: &str) {
+        let mut state = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(bucket) = state.buckets.get_mut(&(group, client.to_string())) {
+            bucket.tokens = (bucket.tokens + 1.0).min(f64::from(self.limit(group).burst));
+        }
+    }
+
+    fn rejection(&self, group: RouteGroup, retry_after: Duration) -> Response {
+        let limit = self.limit(group);
+        AppError::too_many_requests(
+            format!(
+                "rate limit of {} requests per minute exceeded for {} routes",
+                limit.requests_per_minute,
+                group.as_str()
+            ),
+            retry_after.as_secs_f64().ceil().max(1.0) as u64,
+        )
+        .into_response()
+    }
+}
+
+fn refill_per_second(limit: &RateLimitConfig) -> f64 {
+    f64::from(limit.requests_per_minute) / 60.0
+}
+
+fn refilled(bucket: &Bucket, limit: &RateLimitConfig, now: Instant) -> f64 {
+    let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+    (bucket.tokens + elapsed * refill_per_second(limit)).min(f64::from(limit.burst))
+}
+
+/// Applies the rate limit of the route group to the client, identified by its
+/// API key when it sends a valid one and by its IP address otherwise. The
+/// authenticated `Caller` is handed on to `auth::authorize`.
+///
+/// Keys that are not cached are looked up in MongoDB, so the lookup is
+/// charged to the address first: sending random keys is throttled like
+/// sending none. The token is given back when the key turns out valid.
+pub async fn limit_rate(
+    State(app_state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let rate_limiter = &app_state.rate_limiter;
+    if !rate_limiter.config.enabled {
+        return next.run(request).await;
+    }
+
+    let group = match request.extensions().get::<MatchedPath>() {
+        Some(matched_path) => RouteGroup::of(matched_path.as_str()),
+        _none => RouteGroup::of(request.uri().path()),
+    };
+
+    let address = match client_ip(&request, rate_limiter.config.trust_forwarded_for) {
+        Some(ip) => format!("ip:{}", ip),
+        _none => "ip:unknown".to_string(),
+    };
+    let mut address_charged = false;
+
+    let caller = match auth::api_key(request.headers()) {
+        Some(key) => match app_state.api_key_service.cached_authentication(key) {
+            Some(caller) => caller,
+            _none => {
+                if let Err(retry_after) = rate_limiter.acquire(group, &address) {
+                    return rate_limiter.rejection(group, retry_after);
+                }
+                address_charged = true;
+
+                match app_state.api_key_service.authenticate(key).await {
+                    Ok(caller) => caller,
+                    Err(e) => {
+                        // Limit by address instead, authorization reports the failure
+                        error!("Failed to authenticate API key for rate limiting: {}", e);
+                        None
+                    }
+                }
+            }
+        },
+        _none => None,
+    };
+
+    let client = match &caller {
+        Some(caller) => {
+            if address_charged {
+                rate_limiter.refund(group, &address);
+            }
+            match caller.key_id {
+                Some(key_id) => Some(format!("key:{}", key_id)),
+                _none => Some(format!("key:{}", caller.name)),
+            }
+        }
+        // The lookup already took the token of an invalid key
+        _none if address_charged => None,
+        _none => Some(address),
+    };
+
+    if let Some(Err(retry_after)) = client.map(|client| rate_limiter.acquire(group, &client)) {
+        return rate_limiter.rejection(group, retry_after);
+    }
+
+    if let Some(caller) = caller {
+        request.extensions_mut().insert(caller);
+    }
+
+    next.run(request).await
+}
+
+/// The last `X-Forwarded-For` address when it is trusted, as earlier ones are
+/// set by the client, or else the peer address of the connection.
+fn client_ip(request: &Request, trust_forwarded_for: bool) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let forwarded_for = request
+            .headers()
+            .get(&FORWARDED_FOR_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|address| address.trim().parse().ok());
+
+        if forwarded_for.is_some() {
+            return forwarded_for;
+        }
+    }
+
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip())
+}
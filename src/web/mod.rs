@@ -1,2 +1,8 @@
+pub mod extractors;
 pub mod handlers;
+pub mod middleware;
+pub mod openapi;
 pub mod routes;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,137 @@
+use std::borrow::Cow;
+use utoipa::openapi::schema::{ObjectBuilder, Schema, SchemaFormat, Type};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme};
+use utoipa::openapi::{KnownFormat, RefOr};
+use utoipa::{Modify, OpenApi, PartialSchema, ToSchema};
+
+use super::handlers;
+
+/// OpenAPI document of every route of `create_router`, served at `/openapi.json`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Smart News API",
+        description = "Articles with their model predictions, and the predictors, deployments, rollouts and metrics behind them."
+    ),
+    paths(
+        handlers::api_key_handlers::issue_api_key,
+        handlers::api_key_handlers::list_api_keys,
+        handlers::api_key_handlers::revoke_api_key,
+        handlers::cache_handlers::get_cache_stats,
+        handlers::feed_handlers::create_feed_source,
+        handlers::feed_handlers::list_feed_sources,
+        handlers::feed_handlers::list_feed_errors,
+        handlers::articles_handlers::get_articles,
+        handlers::articles_handlers::search_articles,
+        handlers::articles_handlers::get_article,
+        handlers::articles_handlers::create_article,
+        handlers::articles_handlers::create_articles_batch,
+        handlers::article_feed_handlers::get_articles_rss,
+        handlers::article_feed_handlers::get_articles_atom,
+        handlers::article_feed_handlers::get_articles_json_feed,
+        handlers::deployment_handlers::list_deployments,
+        handlers::deployment_handlers::get_deployment,
+        handlers::deployment_handlers::get_deployment_history,
+        handlers::deployment_handlers::update_deployment_traffic,
+        handlers::health_handlers::health_check,
+        handlers::health_handlers::liveness_check,
+        handlers::health_handlers::readiness_check,
+        handlers::metrics_handlers::list_metrics,
+        handlers::metrics_handlers::create_metric,
+        handlers::metrics_handlers::create_metrics_batch,
+        handlers::metrics_handlers::get_metric_bins_aggregation,
+        handlers::metrics_handlers::compare_metric_versions,
+        handlers::metrics_handlers::get_metric_summary_aggregation,
+        handlers::metrics_handlers::get_metric_timeseries_aggregation,
+        handlers::predictor_handlers::get_predictors,
+        handlers::predictor_handlers::get_prediction_types,
+        handlers::predictor_handlers::get_predictor_versions,
+        handlers::rollout_handlers::list_rollouts,
+        handlers::rollout_handlers::create_rollout,
+        handlers::rollout_handlers::get_rollout,
+        handlers::rollout_handlers::cancel_rollout,
+    ),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "admin", description = "API key management, cache statistics and the feed registry"),
+        (name = "articles", description = "News articles, their ingestion, their predictions and their feeds"),
+        (name = "deployments", description = "Traffic split between the predictors of a prediction type"),
+        (name = "health", description = "Liveness and readiness probes"),
+        (name = "metrics", description = "Metric ingestion and aggregations"),
+        (name = "predictors", description = "Predictor versions per prediction type"),
+        (name = "rollouts", description = "Stepwise traffic rollouts of a predictor"),
+    )
+)]
+pub struct ApiDoc;
+
+/// API keys are accepted in the `X-API-Key` header or as a Bearer token.
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+        );
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+/// ObjectId as serialized in responses, in MongoDB extended JSON:
+/// `{"$oid": "<24 hex digits>"}`. Request bodies take the bare hex string.
+pub struct ObjectIdSchema;
+
+impl PartialSchema for ObjectIdSchema {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .property(
+                "$oid",
+                ObjectBuilder::new()
+                    .schema_type(Type::String)
+                    .pattern(Some("^[0-9a-f]{24}$")),
+            )
+            .required("$oid")
+            .description(Some("MongoDB ObjectId in extended JSON"))
+            .into()
+    }
+}
+
+impl ToSchema for ObjectIdSchema {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("ObjectId")
+    }
+}
+
+/// Timestamp of a stored document as serialized in responses, in MongoDB
+/// extended JSON: `{"$date": {"$numberLong": "<milliseconds since epoch>"}}`.
+pub struct BsonDateTimeSchema;
+
+impl PartialSchema for BsonDateTimeSchema {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .property(
+                "$date",
+                ObjectBuilder::new()
+                    .property(
+                        "$numberLong",
+                        ObjectBuilder::new()
+                            .schema_type(Type::String)
+                            .format(Some(SchemaFormat::KnownFormat(KnownFormat::Int64))),
+                    )
+                    .required("$numberLong"),
+            )
+            .required("$date")
+            .description(Some("Timestamp in MongoDB extended JSON"))
+            .into()
+    }
+}
+
+impl ToSchema for BsonDateTimeSchema {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("BsonDateTime")
+    }
+}
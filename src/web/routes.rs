@@ -1,39 +1,159 @@
 use super::handlers;
+use super::middleware::rate_limit::{self, RateLimiter};
+use super::middleware::{auth, request_id};
+use super::openapi::ApiDoc;
+use crate::config::{AuthConfig, LimitsConfig};
+use crate::services::api_key_service::ApiKeyService;
 use crate::services::article_service::ArticleService;
+use crate::services::deployment_service::DeploymentService;
+use crate::services::feed_service::FeedService;
+use crate::services::health_service::HealthService;
 use crate::services::metrics_service::MetricsService;
 use crate::services::predictor_service::PredictorService;
-use axum::{Router, routing::get};
-use http::Method;
-use tower_http::cors::{Any, CorsLayer};
+use crate::services::rollout_service::RolloutService;
+use axum::{
+    Json, Router, middleware,
+    routing::{get, post, put},
+};
+use http::{Method, header};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use utoipa::OpenApi;
+use utoipa_redoc::{Redoc, Servable};
 
 #[derive(Clone)]
 pub struct AppState {
+    pub api_key_service: ApiKeyService,
     pub article_service: ArticleService,
+    pub deployment_service: DeploymentService,
+    pub feed_service: FeedService,
+    pub health_service: HealthService,
     pub metrics_service: MetricsService,
     pub predictor_service: PredictorService,
+    pub rollout_service: RolloutService,
+    pub limits: LimitsConfig,
+    pub auth: AuthConfig,
+    pub rate_limiter: RateLimiter,
 }
 
-pub fn create_router(app_state: AppState) -> Router {
+/// `cors_origins` are validated with the configuration; `*` allows any origin.
+///
+/// Every route must also be listed in `ApiDoc`, which is served at
+/// `/openapi.json` and rendered at `/docs`. Routes added before the `authorize`
+/// layer require the API key role given by `auth::required_role`. Every route
+/// is rate limited according to its `rate_limit::RouteGroup`.
+pub fn create_router(app_state: AppState, cors_origins: &[String]) -> Router {
+    let allow_origin = if cors_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(cors_origins.iter().filter_map(|origin| origin.parse().ok()))
+    };
+
     let cors = CorsLayer::new()
-        .allow_origin([
-            "http://localhost:3000".parse().unwrap(),
-            "https://smart-news-frontend.vercel.app".parse().unwrap(),
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::OPTIONS])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            auth::API_KEY_HEADER,
+            request_id::REQUEST_ID_HEADER,
         ])
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-        .allow_headers(Any);
+        .expose_headers([request_id::REQUEST_ID_HEADER, header::RETRY_AFTER]);
+
+    let openapi = ApiDoc::openapi();
 
     Router::new()
-        .route("/articles", get(handlers::articles_handlers::get_articles))
-        .route("/health", get(handlers::health_handlers::health_check))
-        .route("/metrics", get(handlers::metrics_handlers::list_metrics))
+        .route(
+            "/admin/api-keys",
+            get(handlers::api_key_handlers::list_api_keys)
+                .post(handlers::api_key_handlers::issue_api_key),
+        )
+        .route(
+            "/admin/api-keys/{id}/revoke",
+            post(handlers::api_key_handlers::revoke_api_key),
+        )
+        .route(
+            "/admin/cache",
+            get(handlers::cache_handlers::get_cache_stats),
+        )
+        .route(
+            "/admin/feeds",
+            get(handlers::feed_handlers::list_feed_sources)
+                .post(handlers::feed_handlers::create_feed_source),
+        )
+        .route(
+            "/admin/feeds/errors",
+            get(handlers::feed_handlers::list_feed_errors),
+        )
+        .route(
+            "/articles",
+            get(handlers::articles_handlers::get_articles)
+                .post(handlers::articles_handlers::create_article),
+        )
+        .route(
+            "/articles/batch",
+            post(handlers::articles_handlers::create_articles_batch),
+        )
+        .route(
+            "/articles/search",
+            get(handlers::articles_handlers::search_articles),
+        )
+        .route(
+            "/articles/{id}",
+            get(handlers::articles_handlers::get_article),
+        )
+        .route(
+            "/feeds/articles.rss",
+            get(handlers::article_feed_handlers::get_articles_rss),
+        )
+        .route(
+            "/feeds/articles.atom",
+            get(handlers::article_feed_handlers::get_articles_atom),
+        )
+        .route(
+            "/feeds/articles.json",
+            get(handlers::article_feed_handlers::get_articles_json_feed),
+        )
+        .route(
+            "/deployments",
+            get(handlers::deployment_handlers::list_deployments),
+        )
+        .route(
+            "/deployments/{prediction_type}",
+            get(handlers::deployment_handlers::get_deployment),
+        )
+        .route(
+            "/deployments/{prediction_type}/history",
+            get(handlers::deployment_handlers::get_deployment_history),
+        )
+        .route(
+            "/deployments/{prediction_type}/traffic",
+            put(handlers::deployment_handlers::update_deployment_traffic),
+        )
+        .route(
+            "/metrics",
+            get(handlers::metrics_handlers::list_metrics)
+                .post(handlers::metrics_handlers::create_metric),
+        )
+        .route(
+            "/metrics/batch",
+            post(handlers::metrics_handlers::create_metrics_batch),
+        )
         .route(
             "/metrics/bins",
             get(handlers::metrics_handlers::get_metric_bins_aggregation),
         )
+        .route(
+            "/metrics/compare",
+            get(handlers::metrics_handlers::compare_metric_versions),
+        )
         .route(
             "/metrics/summary",
             get(handlers::metrics_handlers::get_metric_summary_aggregation),
         )
+        .route(
+            "/metrics/timeseries",
+            get(handlers::metrics_handlers::get_metric_timeseries_aggregation),
+        )
         .route(
             "/predictors",
             get(handlers::predictor_handlers::get_predictors),
@@ -46,6 +166,46 @@ pub fn create_router(app_state: AppState) -> Router {
             "/predictors/versions",
             get(handlers::predictor_handlers::get_predictor_versions),
         )
+        .route(
+            "/rollouts",
+            get(handlers::rollout_handlers::list_rollouts)
+                .post(handlers::rollout_handlers::create_rollout),
+        )
+        .route(
+            "/rollouts/{id}",
+            get(handlers::rollout_handlers::get_rollout),
+        )
+        .route(
+            "/rollouts/{id}/cancel",
+            post(handlers::rollout_handlers::cancel_rollout),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            auth::authorize,
+        ))
+        // Probes and documentation stay public
+        .route("/health", get(handlers::health_handlers::health_check))
+        .route(
+            "/health/live",
+            get(handlers::health_handlers::liveness_check),
+        )
+        .route(
+            "/health/ready",
+            get(handlers::health_handlers::readiness_check),
+        )
+        .route(
+            "/openapi.json",
+            get({
+                let openapi = openapi.clone();
+                move || async move { Json(openapi) }
+            }),
+        )
+        .merge(Redoc::with_url("/docs", openapi))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            rate_limit::limit_rate,
+        ))
         .layer(cors)
+        .layer(middleware::from_fn(request_id::assign_request_id))
         .with_state(app_state)
 }
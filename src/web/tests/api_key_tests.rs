@@ -0,0 +1,173 @@
+use axum::body::Body;
+use http::{Method, Request, StatusCode, header};
+use serde_json::{Value, json};
+
+use super::{TestApp, assert_error, oid, predictor};
+
+fn new_metric() -> Value {
+    json!({ "metric_name": "latency", "metric_value": 1.0 })
+}
+
+/// Issues a key with `role` through the admin route and returns it.
+async fn issue_key(app: &TestApp, role: &str) -> (String, String) {
+    let (status, body) = app
+        .post(
+            "/admin/api-keys",
+            json!({ "name": format!("{}-key", role), "role": role }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+
+    (oid(&body["_id"]), body["key"].as_str().unwrap().to_string())
+}
+
+#[tokio::test]
+async fn writes_require_an_api_key() {
+    let app = TestApp::new().with_api_key(None);
+
+    let (status, body) = app.post("/metrics", new_metric()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_error(&body, "unauthorized", None);
+
+    let (status, body) = TestApp::new()
+        .with_api_key(Some("snk_not-a-real-key"))
+        .post("/metrics", new_metric())
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_error(&body, "unauthorized", None);
+
+    // Reads stay public by default
+    let (status, _) = app.get("/predictors/types").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn issued_keys_are_limited_to_their_role() {
+    let app = TestApp::new();
+
+    let (status, body) = app
+        .post(
+            "/admin/api-keys",
+            json!({ "name": "exporter", "role": "ingest" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(body["role"], "ingest");
+    assert_eq!(body["created_by"], "bootstrap-admin");
+    assert!(body.get("key_hash").is_none());
+    let key = body["key"].as_str().unwrap().to_string();
+    assert!(key.starts_with(body["key_prefix"].as_str().unwrap()));
+
+    let ingest_app = app.client(None);
+
+    // The key is also accepted as a Bearer token
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/metrics")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, format!("Bearer {}", key))
+        .body(Body::from(new_metric().to_string()))
+        .unwrap();
+    let (status, _) = ingest_app.call(request).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let ingest_app = app.client(Some(&key));
+
+    let (status, body) = ingest_app
+        .put(
+            "/deployments/sentiment_analysis/traffic",
+            json!({ "splits": [] }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_error(&body, "forbidden", None);
+
+    let (status, _) = ingest_app.get("/admin/api-keys").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn revoked_keys_are_rejected() {
+    let app = TestApp::new();
+    let (key_id, key) = issue_key(&app, "operator").await;
+
+    let (status, body) = app
+        .post(&format!("/admin/api-keys/{}/revoke", key_id), json!({}))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["revoked_at"].is_object());
+
+    let (status, body) = app
+        .post(&format!("/admin/api-keys/{}/revoke", key_id), json!({}))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_error(&body, "conflict", None);
+
+    let (status, _) = app.client(Some(&key)).post("/metrics", new_metric()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (_, body) = app.get("/admin/api-keys").await;
+    assert_eq!(body["api_keys"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn private_reads_require_a_reader_key() {
+    let mut app = TestApp::new();
+    app.state.auth.public_reads = false;
+    let (_, key) = issue_key(&app, "reader").await;
+
+    let anonymous_app = app.client(None);
+
+    let (status, _) = anonymous_app.get("/predictors/types").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Probes stay public
+    let (status, _) = anonymous_app.get("/health/live").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app.client(Some(&key)).get("/predictors/types").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn changes_are_attributed_to_the_api_key() {
+    let app = TestApp::new();
+    let v1 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 1, 100));
+    let v2 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 2, 0));
+    let (_, key) = issue_key(&app, "operator").await;
+    let operator_app = app.client(Some(&key));
+
+    // Names claimed in the body are not taken at face value
+    let (status, _) = operator_app
+        .put(
+            "/deployments/sentiment_analysis/traffic",
+            json!({
+                "splits": [{ "predictor_id": v1.to_hex(), "traffic_percentage": 100 }],
+                "changed_by": "someone-else"
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = app.get("/deployments/sentiment_analysis/history").await;
+    assert_eq!(body["history"][0]["changed_by"], "operator-key");
+
+    let (status, body) = operator_app
+        .post(
+            "/rollouts",
+            json!({
+                "prediction_type": "sentiment_analysis",
+                "predictor_id": v2.to_hex(),
+                "steps": [10, 100],
+                "step_interval_hours": 1,
+                "created_by": "someone-else"
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    assert_eq!(body["created_by"], "operator-key");
+}
@@ -0,0 +1,145 @@
+use axum::body::{Body, to_bytes};
+use http::{Request, StatusCode, header};
+use serde_json::Value;
+use tower::ServiceExt;
+
+use super::{TestApp, article, article_prediction, assert_error, minutes_ago, predictor};
+use crate::database::repositories::models::article_repository_models::SourceDocument;
+
+/// Seeds a positive BBC article with every field set and a negative one
+/// with the defaults of `article`.
+fn seed_articles(app: &TestApp) {
+    app.db
+        .insert_predictor(predictor("sentiment_analysis", 1, 100));
+
+    let mut good_news = article("Rates & markets <up>", minutes_ago(1));
+    good_news.source = SourceDocument {
+        id: Some("bbc-news".to_string()),
+        name: "BBC News".to_string(),
+    };
+    good_news.author = Some("Jane Doe".to_string());
+    good_news.description = Some("Markets rally\u{1} on rates".to_string());
+    good_news.url = Some("https://example.com/markets?a=1&b=2".to_string());
+    good_news.url_to_image = Some("https://example.com/markets.jpg".to_string());
+    let good_news = app.db.insert_article(good_news);
+    let bad_news = app.db.insert_article(article("Bad news", minutes_ago(2)));
+
+    app.db.insert_article_prediction(article_prediction(
+        good_news,
+        "sentiment_analysis",
+        "positive",
+        0.9,
+    ));
+    app.db.insert_article_prediction(article_prediction(
+        bad_news,
+        "sentiment_analysis",
+        "negative",
+        0.8,
+    ));
+}
+
+/// GETs `uri`, returning the content type and the raw body.
+async fn get_feed(app: &TestApp, uri: &str) -> (StatusCode, String, String) {
+    let request = Request::get(uri)
+        .header(header::HOST, "news.example.com")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.router().oneshot(request).await.unwrap();
+    let status = response.status();
+    let content_type = response.headers()[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    (
+        status,
+        content_type,
+        String::from_utf8(bytes.to_vec()).unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn renders_filtered_articles_as_rss_and_atom() {
+    let app = TestApp::new();
+    seed_articles(&app);
+
+    for (extension, content_type) in [
+        ("rss", "application/rss+xml; charset=utf-8"),
+        ("atom", "application/atom+xml; charset=utf-8"),
+    ] {
+        let (status, actual_content_type, body) = get_feed(
+            &app,
+            &format!("/feeds/articles.{}?sentiment=positive", extension),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(actual_content_type, content_type);
+
+        let feed = feed_rs::parser::parse(body.as_bytes()).unwrap();
+        assert_eq!(
+            feed.title.unwrap().content,
+            "Smart News: sentiment=positive"
+        );
+        assert_eq!(feed.entries.len(), 1, "{}", body);
+
+        let entry = &feed.entries[0];
+        assert_eq!(
+            entry.title.as_ref().unwrap().content,
+            "Rates & markets <up>"
+        );
+        assert_eq!(entry.links[0].href, "https://example.com/markets?a=1&b=2");
+        assert_eq!(
+            entry.summary.as_ref().unwrap().content,
+            "Markets rally on rates"
+        );
+        assert_eq!(entry.authors[0].name, "Jane Doe");
+        assert_eq!(entry.categories.len(), 1);
+        assert_eq!(entry.categories[0].term, "positive");
+    }
+
+    let (_, _, body) = get_feed(&app, "/feeds/articles.atom?source=bbc-news").await;
+    let feed = feed_rs::parser::parse(body.as_bytes()).unwrap();
+    assert_eq!(feed.entries.len(), 1);
+    assert_eq!(
+        feed.links[0].href,
+        "http://news.example.com/feeds/articles.atom?source=bbc-news"
+    );
+}
+
+#[tokio::test]
+async fn renders_articles_as_a_paginated_json_feed() {
+    let app = TestApp::new();
+    seed_articles(&app);
+
+    let (status, content_type, body) = get_feed(&app, "/feeds/articles.json?limit=1").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(content_type, "application/feed+json; charset=utf-8");
+
+    let feed: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+    assert_eq!(feed["title"], "Smart News");
+    assert_eq!(feed["items"][0]["title"], "Rates & markets <up>");
+    assert_eq!(feed["items"][0]["image"], "https://example.com/markets.jpg");
+    assert_eq!(feed["items"][0]["authors"][0]["name"], "Jane Doe");
+    assert_eq!(
+        feed["items"][0]["tags"],
+        serde_json::json!(["sentiment_analysis:positive"])
+    );
+
+    let next_url = feed["next_url"].as_str().unwrap();
+    let next_uri = next_url.strip_prefix("http://news.example.com").unwrap();
+    let (_, _, body) = get_feed(&app, next_uri).await;
+    let next_page: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(next_page["items"][0]["title"], "Bad news");
+    // Without a description or content, the title stands in for the text
+    assert_eq!(next_page["items"][0]["content_text"], "Bad news");
+    assert!(next_page["next_url"].is_null());
+
+    let (status, body) = app
+        .get("/feeds/articles.json?prediction[topic]=sports")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("prediction[topic]"));
+}
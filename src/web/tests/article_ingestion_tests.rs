@@ -0,0 +1,106 @@
+use http::StatusCode;
+use serde_json::{Value, json};
+
+use super::{TestApp, assert_error, oid};
+
+fn news_article(title: &str, url: &str) -> Value {
+    json!({
+        "source": { "id": null, "name": "Example News" },
+        "author": "Jane Doe",
+        "title": title,
+        "description": "A story",
+        "url": url,
+        "urlToImage": null,
+        "publishedAt": "2025-06-12T08:30:00Z",
+        "content": "The full story"
+    })
+}
+
+#[tokio::test]
+async fn upserts_articles_on_their_canonical_url() {
+    let app = TestApp::new();
+
+    let (status, created) = app
+        .post(
+            "/articles",
+            news_article(
+                "Markets rally",
+                "http://www.example.com/markets/rally/?utm_source=newsapi&utm_medium=rss",
+            ),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", created);
+    assert_eq!(created["created"], true);
+    assert_eq!(created["url"], "http://www.example.com/markets/rally/");
+
+    let (status, updated) = app
+        .post(
+            "/articles",
+            news_article(
+                "Markets rally again",
+                "https://www.google.com/url?q=https://example.com/markets/rally%23top&sa=D",
+            ),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{}", updated);
+    assert_eq!(updated["created"], false);
+    assert_eq!(oid(&updated["id"]), oid(&created["id"]));
+
+    let (_, body) = app.get("/articles").await;
+    assert_eq!(body["total_count"], 1);
+    assert_eq!(body["articles"][0]["title"], "Markets rally again");
+    assert_eq!(
+        body["articles"][0]["url"],
+        "https://example.com/markets/rally"
+    );
+
+    let (status, body) = app
+        .post("/articles", news_article("Markets rally", "not a url"))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", None);
+}
+
+#[tokio::test]
+async fn ingests_newsapi_responses_in_batches() {
+    let app = TestApp::new();
+    let wrapper = "https://news.google.com/rss/articles/CBMiQkFVX3lxTE5pSVE0NnNZ";
+
+    let (status, body) = app
+        .post(
+            "/articles/batch",
+            json!({
+                "status": "ok",
+                "totalResults": 4,
+                "articles": [
+                    news_article("From the feed", &format!("{}?oc=5", wrapper)),
+                    news_article("From another feed", &format!("{}?hl=en-US&gl=US", wrapper)),
+                    news_article("[Removed]", "https://removed.com"),
+                    { "title": "No url" }
+                ]
+            }),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::MULTI_STATUS, "{}", body);
+    assert_eq!(body["created_count"], 1);
+    assert_eq!(body["updated_count"], 1);
+    assert_eq!(body["failed_count"], 2);
+    assert_eq!(body["articles"][0]["id"], body["articles"][1]["id"]);
+    assert_eq!(
+        body["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error["index"].as_u64().unwrap())
+            .collect::<Vec<_>>(),
+        [2, 3]
+    );
+
+    let (_, body) = app.get("/articles").await;
+    assert_eq!(body["total_count"], 1);
+
+    let (status, body) = app.post("/articles/batch", json!([])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", None);
+}
@@ -0,0 +1,205 @@
+use super::{TestApp, article, article_prediction, assert_error, minutes_ago, oid, predictor};
+use http::StatusCode;
+
+/// Seeds `count` articles published one minute apart, newest first.
+fn seed_articles(app: &TestApp, count: i64) {
+    for i in 0..count {
+        app.db
+            .insert_article(article(&format!("Article {}", i), minutes_ago(i)));
+    }
+}
+
+fn titles(body: &serde_json::Value) -> Vec<String> {
+    body["articles"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|article| article["title"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn lists_articles_with_skip_pagination() {
+    let app = TestApp::new();
+    seed_articles(&app, 5);
+
+    let (status, body) = app.get("/articles?limit=2&skip=2").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(titles(&body), ["Article 2", "Article 3"]);
+    assert_eq!(body["total_count"], 5);
+    assert_eq!(body["page"], 2);
+    assert_eq!(body["total_pages"], 3);
+    assert!(body["prev_cursor"].is_string());
+    assert!(body["next_cursor"].is_string());
+}
+
+#[tokio::test]
+async fn follows_cursors_in_both_directions() {
+    let app = TestApp::new();
+    seed_articles(&app, 5);
+
+    let (_, first_page) = app.get("/articles?limit=2").await;
+    assert_eq!(titles(&first_page), ["Article 0", "Article 1"]);
+    assert!(first_page["prev_cursor"].is_null());
+
+    let next_cursor = first_page["next_cursor"].as_str().unwrap();
+    let (_, second_page) = app
+        .get(&format!("/articles?limit=2&cursor={}", next_cursor))
+        .await;
+    assert_eq!(titles(&second_page), ["Article 2", "Article 3"]);
+    assert_eq!(second_page["page"], 2);
+
+    let prev_cursor = second_page["prev_cursor"].as_str().unwrap();
+    let (_, back) = app
+        .get(&format!("/articles?limit=2&cursor={}", prev_cursor))
+        .await;
+    assert_eq!(titles(&back), ["Article 0", "Article 1"]);
+    assert_eq!(back["page"], 1);
+}
+
+#[tokio::test]
+async fn rejects_an_invalid_cursor() {
+    let app = TestApp::new();
+
+    let (status, body) = app.get("/articles?cursor=not-a-cursor").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("cursor"));
+}
+
+#[tokio::test]
+async fn filters_articles_by_prediction() {
+    let app = TestApp::new();
+    app.db
+        .insert_predictor(predictor("sentiment_analysis", 1, 100));
+
+    let positive = app.db.insert_article(article("Good news", minutes_ago(1)));
+    let negative = app.db.insert_article(article("Bad news", minutes_ago(2)));
+    app.db.insert_article_prediction(article_prediction(
+        positive,
+        "sentiment_analysis",
+        "positive",
+        0.9,
+    ));
+    app.db.insert_article_prediction(article_prediction(
+        negative,
+        "sentiment_analysis",
+        "negative",
+        0.6,
+    ));
+
+    let (status, body) = app
+        .get("/articles?prediction[sentiment_analysis]=positive")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(titles(&body), ["Good news"]);
+    assert_eq!(
+        body["articles"][0]["sentiment_analysis"]["prediction_value"],
+        "positive"
+    );
+
+    let (_, body) = app.get("/articles?sentiment=negative").await;
+    assert_eq!(titles(&body), ["Bad news"]);
+
+    let (_, body) = app
+        .get("/articles?min_confidence[sentiment_analysis]=0.8")
+        .await;
+    assert_eq!(titles(&body), ["Good news"]);
+}
+
+#[tokio::test]
+async fn rejects_an_unknown_prediction_type() {
+    let app = TestApp::new();
+
+    let (status, body) = app.get("/articles?prediction[topic]=sports").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("prediction[topic]"));
+}
+
+#[tokio::test]
+async fn searches_articles_by_relevance() {
+    let app = TestApp::new();
+    app.db
+        .insert_article(article("Markets rally", minutes_ago(1)));
+    app.db
+        .insert_article(article("Election results", minutes_ago(2)));
+
+    let (status, body) = app.get("/articles/search?q=election").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(titles(&body), ["Election results"]);
+    assert!(body["articles"][0]["search_score"].as_f64().unwrap() > 0.0);
+    assert!(body["next_cursor"].is_null());
+
+    let (status, body) = app.get("/articles/search?q=%20").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("q"));
+}
+
+#[tokio::test]
+async fn gets_an_article_with_its_predictions() {
+    let app = TestApp::new();
+    let article_id = app.db.insert_article(article("Good news", minutes_ago(1)));
+    app.db.insert_article_prediction(article_prediction(
+        article_id,
+        "sentiment_analysis",
+        "positive",
+        0.9,
+    ));
+
+    let (status, body) = app.get(&format!("/articles/{}", article_id)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(oid(&body["article"]["_id"]), article_id.to_hex());
+    assert_eq!(
+        body["predictions"]["sentiment_analysis"]["selected_prediction"]["prediction_value"],
+        "positive"
+    );
+}
+
+#[tokio::test]
+async fn reports_missing_and_malformed_article_ids() {
+    let app = TestApp::new();
+
+    let (status, body) = app.get("/articles/not-an-id").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("id"));
+
+    let (status, body) = app
+        .get(&format!(
+            "/articles/{}",
+            mongodb::bson::oid::ObjectId::new()
+        ))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_error(&body, "not_found", None);
+}
+
+#[tokio::test]
+async fn rejects_a_limit_outside_the_page_size_bounds() {
+    let app = TestApp::new();
+
+    for limit in [0, 101] {
+        let (status, body) = app.get(&format!("/articles?limit={}", limit)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_error(&body, "validation_error", Some("limit"));
+    }
+}
+
+#[tokio::test]
+async fn filters_articles_by_source() {
+    let app = TestApp::new();
+    let mut bbc = article("From the BBC", minutes_ago(1));
+    bbc.source.id = Some("bbc-news".to_string());
+    bbc.source.name = "BBC News".to_string();
+    app.db.insert_article(bbc);
+    app.db.insert_article(article("Elsewhere", minutes_ago(2)));
+
+    let (_, body) = app.get("/articles?source=bbc-news").await;
+    assert_eq!(titles(&body), ["From the BBC"]);
+
+    let (_, body) = app.get("/articles?source=BBC%20News").await;
+    assert_eq!(titles(&body), ["From the BBC"]);
+    assert_eq!(body["total_count"], 1);
+}
@@ -0,0 +1,110 @@
+use http::StatusCode;
+use serde_json::{Value, json};
+
+use super::{TestApp, metric, minutes_ago, predictor};
+
+/// Counters of the cache called `name` in `/admin/cache`.
+async fn cache_stats(app: &TestApp, name: &str) -> (u64, u64) {
+    let (status, body) = app.get("/admin/cache").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let caches = body["caches"].as_array().unwrap();
+    let stats: &Value = caches.iter().find(|cache| cache["name"] == name).unwrap();
+    (
+        stats["hits"].as_u64().unwrap(),
+        stats["misses"].as_u64().unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn caches_summaries_until_metrics_are_written() {
+    let app = TestApp::new();
+    app.db
+        .insert_metric(metric("latency", 10.0, "1", minutes_ago(1)));
+
+    let (_, body) = app
+        .get("/metrics/summary?metric_name=latency&predictor_version=1")
+        .await;
+    assert_eq!(body["count"], 1);
+
+    app.db
+        .insert_metric(metric("latency", 20.0, "1", minutes_ago(1)));
+
+    // Query parameters are normalized, so their order does not matter
+    let (_, body) = app
+        .get("/metrics/summary?predictor_version=1&metric_name=latency")
+        .await;
+    assert_eq!(body["count"], 1);
+
+    let (_, body) = app.get("/metrics/summary?metric_name=latency").await;
+    assert_eq!(body["count"], 2);
+    assert_eq!(cache_stats(&app, "metrics_summary").await, (1, 2));
+
+    let (status, _) = app
+        .post(
+            "/metrics",
+            json!({ "metric_name": "latency", "metric_value": 30.0 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (_, body) = app.get("/metrics/summary?metric_name=latency").await;
+    assert_eq!(body["count"], 3);
+}
+
+#[tokio::test]
+async fn caches_summaries_under_their_resolved_window() {
+    let app = TestApp::new();
+    app.db
+        .insert_metric(metric("latency", 10.0, "1", minutes_ago(1)));
+
+    let (_, body) = app.get("/metrics/summary?metric_name=latency").await;
+    assert_eq!(body["count"], 1);
+
+    let (_, body) = app
+        .get("/metrics/summary?metric_name=latency&num_days=7")
+        .await;
+    assert_eq!(body["count"], 1);
+    assert_eq!(cache_stats(&app, "metrics_summary").await, (1, 1));
+
+    let (_, body) = app
+        .get("/metrics/summary?metric_name=latency&num_days=30")
+        .await;
+    assert_eq!(body["count"], 1);
+    assert_eq!(cache_stats(&app, "metrics_summary").await, (1, 2));
+}
+
+#[tokio::test]
+async fn caches_predictor_versions_until_traffic_changes() {
+    let app = TestApp::new();
+    let v1 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 1, 100));
+    let uri = "/predictors/versions?prediction_type=sentiment_analysis";
+
+    let (_, body) = app.get(uri).await;
+    assert_eq!(body["predictor_versions"], json!([1]));
+
+    let v2 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 2, 0));
+    let (_, body) = app.get(uri).await;
+    assert_eq!(body["predictor_versions"], json!([1]));
+    assert_eq!(cache_stats(&app, "predictor_versions").await, (1, 1));
+
+    let (status, _) = app
+        .put(
+            "/deployments/sentiment_analysis/traffic",
+            json!({
+                "splits": [
+                    { "predictor_id": v1.to_hex(), "traffic_percentage": 90 },
+                    { "predictor_id": v2.to_hex(), "traffic_percentage": 10 }
+                ]
+            }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = app.get(uri).await;
+    assert_eq!(body["predictor_versions"], json!([1, 2]));
+}
@@ -0,0 +1,120 @@
+use super::{TestApp, assert_error, oid, predictor};
+use crate::services::api_key_service::ADMIN_KEY_CALLER;
+use http::StatusCode;
+use mongodb::bson::oid::ObjectId;
+use serde_json::json;
+
+fn split(predictor_id: ObjectId, traffic_percentage: i32) -> serde_json::Value {
+    json!({ "predictor_id": predictor_id.to_hex(), "traffic_percentage": traffic_percentage })
+}
+
+#[tokio::test]
+async fn updates_the_traffic_split_and_records_history() {
+    let app = TestApp::new();
+    let v1 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 1, 100));
+    let v2 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 2, 0));
+
+    let (status, body) = app
+        .put(
+            "/deployments/sentiment_analysis/traffic",
+            json!({
+                "splits": [split(v1, 70), split(v2, 30)],
+                "reason": "canary"
+            }),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let active_deployments = body["active_deployments"].as_array().unwrap();
+    assert_eq!(active_deployments.len(), 2);
+    assert_eq!(active_deployments[1]["traffic_percentage"], 30.0);
+    assert_eq!(active_deployments[1]["predictor"]["predictor_version"], 2);
+
+    let traffic: Vec<(i32, i32)> = app
+        .db
+        .predictors()
+        .iter()
+        .map(|predictor| (predictor.predictor_version, predictor.traffic_percentage))
+        .collect();
+    assert_eq!(traffic, [(1, 70), (2, 30)]);
+
+    let (status, body) = app.get("/deployments/sentiment_analysis/history").await;
+    assert_eq!(status, StatusCode::OK);
+    let history = body["history"].as_array().unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["changed_by"], ADMIN_KEY_CALLER);
+    assert_eq!(history[0]["previous_deployments"], json!([]));
+
+    let (_, body) = app.get("/deployments").await;
+    assert_eq!(body["deployments"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn drops_predictors_left_at_zero_from_the_deployment() {
+    let app = TestApp::new();
+    let v1 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 1, 50));
+    let v2 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 2, 50));
+
+    let (status, body) = app
+        .put(
+            "/deployments/sentiment_analysis/traffic",
+            json!({ "splits": [split(v1, 0), split(v2, 100)] }),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let active_deployments = body["active_deployments"].as_array().unwrap();
+    assert_eq!(active_deployments.len(), 1);
+    assert_eq!(oid(&active_deployments[0]["predictor_id"]), v2.to_hex());
+}
+
+#[tokio::test]
+async fn rejects_invalid_traffic_splits() {
+    let app = TestApp::new();
+    let v1 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 1, 100));
+    let other = app.db.insert_predictor(predictor("topic", 1, 100));
+
+    let cases = [
+        json!({ "splits": [split(v1, 90)] }),
+        json!({ "splits": [split(v1, 50), split(v1, 50)] }),
+        json!({ "splits": [split(v1, 50), split(other, 50)] }),
+        json!({ "splits": [split(ObjectId::new(), 100)] }),
+        json!({ "splits": [] }),
+    ];
+
+    for case in cases {
+        let (status, body) = app
+            .put("/deployments/sentiment_analysis/traffic", case)
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_error(&body, "validation_error", Some("splits"));
+    }
+
+    let (status, _) = app.get("/deployments/sentiment_analysis").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn rejects_malformed_json_bodies() {
+    let app = TestApp::new();
+
+    let (status, body) = app
+        .put(
+            "/deployments/sentiment_analysis/traffic",
+            json!({ "reason": "canary" }),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", None);
+}
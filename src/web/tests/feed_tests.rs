@@ -0,0 +1,82 @@
+use http::StatusCode;
+use serde_json::json;
+
+use super::{TestApp, assert_error};
+
+#[tokio::test]
+async fn registers_feeds_once() {
+    let app = TestApp::new();
+
+    let (status, feed) = app
+        .post(
+            "/admin/feeds",
+            json!({ "url": "https://feeds.bbci.co.uk/news/rss.xml", "name": "BBC News" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", feed);
+    assert_eq!(feed["enabled"], true);
+    assert_eq!(feed["consecutive_failures"], 0);
+
+    let (status, body) = app
+        .post(
+            "/admin/feeds",
+            json!({ "url": "https://feeds.bbci.co.uk/news/rss.xml" }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_error(&body, "conflict", None);
+
+    let (status, body) = app
+        .post("/admin/feeds", json!({ "url": "feeds.bbci.co.uk" }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("url"));
+
+    let (_, body) = app.get("/admin/feeds").await;
+    assert_eq!(body["feeds"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn limits_feed_names_in_characters() {
+    let app = TestApp::new();
+
+    let (status, feed) = app
+        .post(
+            "/admin/feeds",
+            json!({ "url": "https://www.lemonde.fr/rss/une.xml", "name": "é".repeat(200) }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::CREATED, "{}", feed);
+
+    let (status, body) = app
+        .post(
+            "/admin/feeds",
+            json!({ "url": "https://www.lefigaro.fr/rss/figaro_actualites.xml", "name": "é".repeat(201) }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("name"));
+}
+
+#[tokio::test]
+async fn reports_failing_feeds() {
+    let app = TestApp::new();
+
+    // Nothing listens on the discard port
+    let (status, _) = app
+        .post("/admin/feeds", json!({ "url": "http://127.0.0.1:9/feed" }))
+        .await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (_, body) = app.get("/admin/feeds/errors").await;
+    assert_eq!(body["feeds"], json!([]));
+
+    app.state.feed_service.poll_feeds().await;
+    app.state.feed_service.poll_feeds().await;
+
+    let (status, body) = app.get("/admin/feeds/errors").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["feeds"][0]["url"], "http://127.0.0.1:9/feed");
+    assert_eq!(body["feeds"][0]["consecutive_failures"], 2);
+    assert!(body["feeds"][0]["last_error"].is_string());
+}
@@ -0,0 +1,80 @@
+use super::TestApp;
+use http::StatusCode;
+use std::sync::Arc;
+
+use crate::services::health_service::{ExpectedCollection, HealthService};
+
+#[tokio::test]
+async fn reports_liveness() {
+    let app = TestApp::new();
+
+    let (status, body) = app.get("/health/live").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["status"], "alive");
+}
+
+#[tokio::test]
+async fn is_ready_once_required_collections_and_indexes_exist() {
+    let app = TestApp::new();
+
+    let (status, body) = app.get("/health/ready").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["ready"], false);
+
+    app.db.create_collection("articles", &["_id_"]);
+    let (status, body) = app.get("/health/ready").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(
+        body["checks"][1]["details"],
+        "missing indexes: articles_text_search"
+    );
+
+    app.db
+        .create_collection("articles", &["_id_", "articles_text_search"]);
+    let (status, body) = app.get("/health/ready").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ready"], true);
+    // Collections created on first write only need to exist eventually
+    assert_eq!(body["checks"][2]["status"], "ok");
+    assert_eq!(body["checks"][2]["details"], "collection not created yet");
+}
+
+#[tokio::test]
+async fn reports_missing_indexes_as_degraded_when_they_are_not_required() {
+    let mut app = TestApp::new();
+    app.state.health_service = HealthService::new(
+        Arc::new(app.db.clone()),
+        vec![
+            ExpectedCollection::new("articles", true)
+                .with_indexes(["articles_text_search"])
+                .with_indexes_required(false),
+        ],
+    );
+    app.db.create_collection("articles", &["_id_"]);
+
+    let (status, body) = app.get("/health/ready").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ready"], true);
+    assert_eq!(body["checks"][1]["status"], "degraded");
+    assert_eq!(
+        body["checks"][1]["details"],
+        "missing indexes: articles_text_search"
+    );
+}
+
+#[tokio::test]
+async fn is_not_ready_when_the_database_is_unreachable() {
+    let app = TestApp::new();
+    app.db
+        .create_collection("articles", &["_id_", "articles_text_search"]);
+    app.db.set_unavailable(true);
+
+    let (status, body) = app.get("/health/ready").await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let checks = body["checks"].as_array().unwrap();
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0]["name"], "mongodb_ping");
+    assert_eq!(checks[0]["status"], "failed");
+}
@@ -0,0 +1,266 @@
+use super::{TestApp, assert_error, metric, minutes_ago, oid};
+use axum::body::Body;
+use http::{Method, Request, StatusCode, header};
+use serde_json::json;
+
+/// Seeds latency values 1..=10 for v1, one minute apart, and 20 and 30 for v2.
+fn seed_metrics(app: &TestApp) {
+    for value in 1..=10 {
+        app.db
+            .insert_metric(metric("latency", value as f64, "1", minutes_ago(value)));
+    }
+    app.db
+        .insert_metric(metric("latency", 20.0, "2", minutes_ago(1)));
+    app.db
+        .insert_metric(metric("latency", 30.0, "2", minutes_ago(2)));
+}
+
+fn values(body: &serde_json::Value) -> Vec<f64> {
+    body["metrics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|metric| metric["metric_value"].as_f64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn lists_metrics_with_cursors() {
+    let app = TestApp::new();
+    seed_metrics(&app);
+
+    let (status, first_page) = app
+        .get("/metrics?metric_name=latency&predictor_version=1&limit=4")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(values(&first_page), [1.0, 2.0, 3.0, 4.0]);
+
+    let next_cursor = first_page["next_cursor"].as_str().unwrap();
+    let (_, second_page) = app
+        .get(&format!(
+            "/metrics?metric_name=latency&predictor_version=1&limit=4&cursor={}",
+            next_cursor
+        ))
+        .await;
+    assert_eq!(values(&second_page), [5.0, 6.0, 7.0, 8.0]);
+}
+
+#[tokio::test]
+async fn summarizes_a_metric() {
+    let app = TestApp::new();
+    seed_metrics(&app);
+
+    let (status, body) = app
+        .get("/metrics/summary?metric_name=latency&predictor_version=1&quantiles=0.25")
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["count"], 10);
+    assert_eq!(body["avg_value"], 5.5);
+    assert_eq!(body["min_value"], 1.0);
+    assert_eq!(body["max_value"], 10.0);
+    assert_eq!(body["p50_value"], 5.0);
+    assert_eq!(
+        body["quantiles"],
+        json!([{ "quantile": 0.25, "value": 3.0 }])
+    );
+    assert!((body["std_dev_value"].as_f64().unwrap() - 2.8723).abs() < 1e-3);
+}
+
+#[tokio::test]
+async fn reports_a_missing_summary_as_not_found() {
+    let app = TestApp::new();
+
+    let (status, body) = app.get("/metrics/summary?metric_name=latency").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_error(&body, "not_found", None);
+
+    let (status, body) = app.get("/metrics/summary").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("metric_name"));
+
+    let (status, body) = app
+        .get("/metrics/summary?metric_name=latency&quantiles=1.5")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("quantiles"));
+}
+
+#[tokio::test]
+async fn bins_metric_values() {
+    let app = TestApp::new();
+    seed_metrics(&app);
+
+    let (status, body) = app
+        .get("/metrics/bins?metric_name=latency&predictor_version=1&num_bins=3")
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let counts: Vec<i64> = body["metric_bins"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|bin| bin["count"].as_i64().unwrap())
+        .collect();
+    assert_eq!(counts, [3, 3, 4]);
+    assert_eq!(body["metric_bins"][0]["bin_start"], 1.0);
+    assert_eq!(body["metric_bins"][2]["bin_end"], 10.0);
+}
+
+#[tokio::test]
+async fn groups_a_timeseries_by_tag() {
+    let app = TestApp::new();
+    seed_metrics(&app);
+
+    let (status, body) = app
+        .get("/metrics/timeseries?metric_name=latency&interval=1d&group_by=predictor_version")
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let series = body["series"].as_array().unwrap();
+    assert_eq!(series.len(), 2);
+    assert_eq!(series[0]["group"], "1");
+    assert_eq!(series[1]["group"], "2");
+    let count: i64 = series[1]["buckets"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|bucket| bucket["count"].as_i64().unwrap())
+        .sum();
+    assert_eq!(count, 2);
+
+    let (status, body) = app
+        .get("/metrics/timeseries?metric_name=latency&interval=1y")
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("interval"));
+}
+
+#[tokio::test]
+async fn compares_predictor_versions() {
+    let app = TestApp::new();
+    seed_metrics(&app);
+
+    let (status, body) = app
+        .get("/metrics/compare?metric_name=latency&prediction_type=sentiment_analysis&a=1&b=2")
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["a"]["count"], 10);
+    assert_eq!(body["b"]["count"], 2);
+    assert_eq!(body["mean_difference"], -19.5);
+    assert_eq!(body["test"]["test"], "welch");
+}
+
+#[tokio::test]
+async fn creates_a_metric() {
+    let app = TestApp::new();
+
+    let (status, body) = app
+        .post(
+            "/metrics",
+            json!({
+                "metric_name": "latency",
+                "metric_value": 12.5,
+                "tags": { "predictor_version": "1" }
+            }),
+        )
+        .await;
+
+    assert_eq!(status, StatusCode::CREATED);
+    let metrics = app.db.metrics();
+    assert_eq!(metrics.len(), 1);
+    assert_eq!(oid(&body["id"]), metrics[0].id.unwrap().to_hex());
+    assert_eq!(metrics[0].metric_value, 12.5);
+
+    let (status, body) = app
+        .post(
+            "/metrics",
+            json!({ "metric_name": "bad name", "metric_value": 1.0 }),
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", None);
+}
+
+#[tokio::test]
+async fn ingests_a_partially_valid_ndjson_batch() {
+    let app = TestApp::new();
+    let body = [
+        r#"{"metric_name":"latency","metric_value":1.0}"#,
+        r#"{"metric_name":"latency"}"#,
+        r#"{"metric_name":"latency","metric_value":2.0,"tags":{"bad key":"x"}}"#,
+        r#"{"metric_name":"accuracy","metric_value":0.9}"#,
+    ]
+    .join("\n");
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/metrics/batch")
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .body(Body::from(body))
+        .unwrap();
+    let (status, body) = app.call(request).await;
+
+    assert_eq!(status, StatusCode::MULTI_STATUS);
+    assert_eq!(body["inserted_count"], 2);
+    assert_eq!(body["failed_count"], 2);
+    let failed: Vec<i64> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["index"].as_i64().unwrap())
+        .collect();
+    assert_eq!(failed, [1, 2]);
+    assert_eq!(app.db.metrics().len(), 2);
+}
+
+#[tokio::test]
+async fn rejects_an_empty_batch() {
+    let app = TestApp::new();
+
+    let (status, body) = app.post("/metrics/batch", json!([])).await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", None);
+}
+
+#[tokio::test]
+async fn reads_batches_as_told_by_their_content_type() {
+    let app = TestApp::new();
+    let metric = r#"{"metric_name": "latency", "metric_value": 1.0}"#;
+
+    for (content_type, body, status, code) in [
+        // A single object is not an array, nor NDJSON sent as JSON
+        (
+            "application/json",
+            metric.to_string(),
+            StatusCode::BAD_REQUEST,
+            "validation_error",
+        ),
+        (
+            "application/json",
+            format!("{}\n{}", metric, metric),
+            StatusCode::BAD_REQUEST,
+            "validation_error",
+        ),
+        (
+            "text/plain",
+            format!("[{}]", metric),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+        ),
+    ] {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/metrics/batch")
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap();
+        let (response_status, body) = app.call(request).await;
+
+        assert_eq!(response_status, status, "{}", content_type);
+        assert_error(&body, code, None);
+    }
+    assert!(app.db.metrics().is_empty());
+}
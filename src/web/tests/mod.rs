@@ -0,0 +1,300 @@
+//! Handler-level tests: requests go through the full router, backed by the
+//! in-memory repositories instead of MongoDB.
+
+mod api_key_tests;
+mod article_feed_tests;
+mod article_ingestion_tests;
+mod articles_tests;
+mod cache_tests;
+mod deployments_tests;
+mod feed_tests;
+mod health_tests;
+mod metrics_tests;
+mod openapi_tests;
+mod predictors_tests;
+mod rate_limit_tests;
+mod rollouts_tests;
+
+use axum::Router;
+use axum::body::{Body, to_bytes};
+use chrono::{DateTime, Duration, Utc};
+use http::{Method, Request, StatusCode, header};
+use mongodb::bson::oid::ObjectId;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use super::middleware::auth::API_KEY_HEADER;
+use super::middleware::rate_limit::RateLimiter;
+use super::routes::{self, AppState};
+use crate::config::{
+    AuthConfig, CacheConfig, FeedsConfig, LimitsConfig, RateLimitsConfig, ServerConfig,
+};
+use crate::database::repositories::in_memory::InMemoryDatabase;
+use crate::database::repositories::models::article_prediction_repository_models::{
+    ArticlePredictionsDocument, PredictionDocument,
+};
+use crate::database::repositories::models::article_repository_models::{
+    ArticleDocument, SourceDocument,
+};
+use crate::database::repositories::models::metrics_repository_models::MetricsDocument;
+use crate::database::repositories::models::predictor_repository_models::PredictorDocument;
+use crate::services::api_key_service::ApiKeyService;
+use crate::services::article_service::ArticleService;
+use crate::services::deployment_service::DeploymentService;
+use crate::services::feed_service::FeedService;
+use crate::services::health_service::{ExpectedCollection, HealthService};
+use crate::services::metrics_service::MetricsService;
+use crate::services::predictor_service::PredictorService;
+use crate::services::rollout_service::RolloutService;
+
+/// Bootstrap admin key of the test app, sent with every request by default.
+pub const ADMIN_KEY: &str = "test-admin-key-0123456789abcdef0123456789";
+
+pub struct TestApp {
+    pub db: InMemoryDatabase,
+    pub state: AppState,
+    api_key: Option<String>,
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        let db = InMemoryDatabase::new();
+        let repository = Arc::new(db.clone());
+
+        let cache = CacheConfig::default();
+        let predictor_service = PredictorService::new(repository.clone(), &cache);
+        let deployment_service = DeploymentService::new(
+            repository.clone(),
+            repository.clone(),
+            predictor_service.clone(),
+        );
+        let article_service = ArticleService::new(repository.clone(), repository.clone());
+        let state = AppState {
+            api_key_service: ApiKeyService::new(repository.clone(), Some(ADMIN_KEY)),
+            feed_service: FeedService::new(
+                repository.clone(),
+                article_service.clone(),
+                FeedsConfig::default(),
+            )
+            .unwrap(),
+            article_service,
+            health_service: HealthService::new(
+                repository.clone(),
+                vec![
+                    ExpectedCollection::new("articles", true)
+                        .with_indexes(["articles_text_search"]),
+                    ExpectedCollection::new("rollouts", false),
+                ],
+            ),
+            metrics_service: MetricsService::new(repository.clone(), &cache),
+            predictor_service,
+            rollout_service: RolloutService::new(
+                repository.clone(),
+                repository.clone(),
+                repository,
+                deployment_service.clone(),
+            ),
+            deployment_service,
+            limits: LimitsConfig::default(),
+            auth: AuthConfig::default(),
+            // Enabled by the rate limiting tests only
+            rate_limiter: RateLimiter::new(RateLimitsConfig {
+                enabled: false,
+                ..RateLimitsConfig::default()
+            }),
+        };
+
+        Self {
+            db,
+            state,
+            api_key: Some(ADMIN_KEY.to_string()),
+        }
+    }
+
+    /// Sends `key` instead of the admin key, or no key at all.
+    pub fn with_api_key(mut self, key: Option<&str>) -> Self {
+        self.api_key = key.map(str::to_string);
+        self
+    }
+
+    /// Another client of the same app, sending `key` instead.
+    pub fn client(&self, key: Option<&str>) -> Self {
+        Self {
+            db: self.db.clone(),
+            state: self.state.clone(),
+            api_key: key.map(str::to_string),
+        }
+    }
+
+    fn router(&self) -> Router {
+        routes::create_router(self.state.clone(), &ServerConfig::default().cors_origins)
+    }
+
+    pub async fn get(&self, uri: &str) -> (StatusCode, Value) {
+        self.send(Method::GET, uri, None).await
+    }
+
+    pub async fn post(&self, uri: &str, body: Value) -> (StatusCode, Value) {
+        self.send(Method::POST, uri, Some(body)).await
+    }
+
+    pub async fn put(&self, uri: &str, body: Value) -> (StatusCode, Value) {
+        self.send(Method::PUT, uri, Some(body)).await
+    }
+
+    pub async fn send(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder().method(method).uri(uri);
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            _none => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        self.call(request).await
+    }
+
+    pub async fn call(&self, mut request: Request<Body>) -> (StatusCode, Value) {
+        if let Some(key) = &self.api_key {
+            request
+                .headers_mut()
+                .entry(API_KEY_HEADER)
+                .or_insert(key.parse().unwrap());
+        }
+
+        let response = self.router().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+
+        (status, body)
+    }
+}
+
+/// Asserts that `body` is an error response with the given code and param.
+pub fn assert_error(body: &Value, code: &str, param: Option<&str>) {
+    assert_eq!(
+        body["error"]["code"], code,
+        "unexpected error body: {}",
+        body
+    );
+    assert_eq!(
+        body["error"]["param"].as_str(),
+        param,
+        "unexpected error body: {}",
+        body
+    );
+}
+
+/// Reads the hex string of an ObjectId serialized as extended JSON.
+pub fn oid(value: &Value) -> String {
+    value["$oid"].as_str().unwrap().to_string()
+}
+
+/// Timestamps rounded to milliseconds, the precision BSON dates keep.
+pub fn minutes_ago(minutes: i64) -> DateTime<Utc> {
+    let date = Utc::now() - Duration::minutes(minutes);
+    DateTime::from_timestamp_millis(date.timestamp_millis()).unwrap()
+}
+
+pub fn article(title: &str, published_at: DateTime<Utc>) -> ArticleDocument {
+    ArticleDocument {
+        id: None,
+        source: SourceDocument {
+            id: None,
+            name: "Test Source".to_string(),
+        },
+        author: None,
+        title: Some(title.to_string()),
+        description: None,
+        url: None,
+        url_to_image: None,
+        url_hash: None,
+        published_at,
+        content: None,
+        created_at: published_at,
+        updated_at: published_at,
+        predictions: None,
+        sentiment_analysis: None,
+        search_score: None,
+        search_highlights: None,
+    }
+}
+
+pub fn article_prediction(
+    article_id: ObjectId,
+    prediction_type: &str,
+    value: &str,
+    confidence: f64,
+) -> ArticlePredictionsDocument {
+    let predictor_id = ObjectId::new();
+    let prediction = PredictionDocument {
+        prediction_confidence: Some(confidence),
+        prediction_value: Value::String(value.to_string()),
+    };
+
+    ArticlePredictionsDocument {
+        id: None,
+        article_id,
+        prediction_type: prediction_type.to_string(),
+        selected_predictor_id: predictor_id,
+        selected_prediction: prediction.clone(),
+        predictions: HashMap::from([(predictor_id, prediction)]),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
+
+pub fn predictor(
+    prediction_type: &str,
+    version: i32,
+    traffic_percentage: i32,
+) -> PredictorDocument {
+    PredictorDocument {
+        id: None,
+        prediction_type: prediction_type.to_string(),
+        predictor_version: version,
+        predictor_description: format!("{} v{}", prediction_type, version),
+        traffic_percentage,
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
+
+pub fn metric(
+    name: &str,
+    value: f64,
+    predictor_version: &str,
+    created_at: DateTime<Utc>,
+) -> MetricsDocument {
+    MetricsDocument {
+        id: None,
+        metric_name: name.to_string(),
+        metric_value: value,
+        description: None,
+        tags: HashMap::from([
+            (
+                "prediction_type".to_string(),
+                "sentiment_analysis".to_string(),
+            ),
+            (
+                "predictor_version".to_string(),
+                predictor_version.to_string(),
+            ),
+        ]),
+        created_at,
+        updated_at: created_at,
+    }
+}
@@ -0,0 +1,99 @@
+use http::{Method, StatusCode};
+
+use super::TestApp;
+use crate::web::middleware::auth;
+
+/// Routes serving the documentation itself.
+const UNDOCUMENTED_ROUTES: &[&str] = &["/openapi.json"];
+
+/// Method and path of every `.route(..)` of `create_router`. axum cannot list
+/// the routes of a `Router`, so they are read from its source.
+fn router_routes() -> Vec<(String, String)> {
+    let source = include_str!("../routes.rs");
+    let mut routes = Vec::new();
+
+    for route in source.split(".route(").skip(1) {
+        let path = route.split('"').nth(1).unwrap();
+
+        for method in ["get", "post", "put", "patch", "delete"] {
+            let pattern = format!("{}(", method);
+            let is_method_call = route.match_indices(&pattern).any(|(index, _)| {
+                route[..index]
+                    .chars()
+                    .last()
+                    .is_none_or(|c| !(c.is_alphanumeric() || c == '_'))
+            });
+
+            if is_method_call {
+                routes.push((method.to_string(), path.to_string()));
+            }
+        }
+    }
+
+    routes
+}
+
+#[tokio::test]
+async fn serves_the_openapi_document() {
+    let app = TestApp::new();
+
+    let (status, body) = app.get("/openapi.json").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["openapi"].as_str().unwrap().starts_with("3."));
+    assert!(body["paths"]["/articles"]["get"].is_object());
+    assert!(body["components"]["schemas"]["ErrorResponse"].is_object());
+}
+
+#[tokio::test]
+async fn documents_every_route_of_the_router() {
+    let app = TestApp::new();
+    let (_, spec) = app.get("/openapi.json").await;
+
+    let routes = router_routes();
+    assert!(routes.len() > 20, "failed to read the routes: {:?}", routes);
+
+    let undocumented: Vec<String> = routes
+        .iter()
+        .filter(|(_, path)| !UNDOCUMENTED_ROUTES.contains(&path.as_str()))
+        .filter(|(method, path)| !spec["paths"][path][method].is_object())
+        .map(|(method, path)| format!("{} {}", method.to_uppercase(), path))
+        .collect();
+
+    assert!(
+        undocumented.is_empty(),
+        "routes missing from the OpenAPI document: {:?}",
+        undocumented
+    );
+}
+
+#[tokio::test]
+async fn documents_the_api_key_of_protected_routes() {
+    let app = TestApp::new();
+    let (_, spec) = app.get("/openapi.json").await;
+
+    // Reads are public unless configured otherwise, writes never are
+    let undocumented: Vec<String> = router_routes()
+        .iter()
+        .filter(|(method, path)| {
+            let method = Method::from_bytes(method.to_uppercase().as_bytes()).unwrap();
+            auth::required_role(&method, path, true).is_some()
+        })
+        .filter(|(method, path)| {
+            let operation = &spec["paths"][path][method];
+            let security = operation["security"].as_array();
+            !(security.is_some_and(|security| {
+                security.iter().any(|scheme| scheme["api_key"].is_array())
+                    && security.iter().any(|scheme| scheme["bearer"].is_array())
+            }) && operation["responses"]["401"].is_object()
+                && operation["responses"]["403"].is_object())
+        })
+        .map(|(method, path)| format!("{} {}", method.to_uppercase(), path))
+        .collect();
+
+    assert!(
+        undocumented.is_empty(),
+        "protected routes without security or 401/403 responses: {:?}",
+        undocumented
+    );
+}
@@ -0,0 +1,56 @@
+use super::{TestApp, assert_error, predictor};
+use http::StatusCode;
+
+#[tokio::test]
+async fn lists_prediction_types_and_versions() {
+    let app = TestApp::new();
+    app.db
+        .insert_predictor(predictor("sentiment_analysis", 2, 0));
+    app.db
+        .insert_predictor(predictor("sentiment_analysis", 1, 100));
+    app.db.insert_predictor(predictor("topic", 1, 100));
+
+    let (status, body) = app.get("/predictors/types").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["prediction_types"],
+        serde_json::json!(["sentiment_analysis", "topic"])
+    );
+
+    let (status, body) = app
+        .get("/predictors/versions?prediction_type=sentiment_analysis")
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["predictor_versions"], serde_json::json!([1, 2]));
+}
+
+#[tokio::test]
+async fn lists_predictors_with_a_minimum_traffic() {
+    let app = TestApp::new();
+    app.db
+        .insert_predictor(predictor("sentiment_analysis", 1, 80));
+    app.db
+        .insert_predictor(predictor("sentiment_analysis", 2, 20));
+
+    let (status, body) = app
+        .get("/predictors?prediction_type=sentiment_analysis&min_traffic=50")
+        .await;
+
+    assert_eq!(status, StatusCode::OK);
+    let predictors = body["predictors"].as_array().unwrap();
+    assert_eq!(predictors.len(), 1);
+    assert_eq!(predictors[0]["predictor_version"], 1);
+}
+
+#[tokio::test]
+async fn requires_a_prediction_type() {
+    let app = TestApp::new();
+
+    let (status, body) = app.get("/predictors").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("prediction_type"));
+
+    let (status, body) = app.get("/predictors?min_traffic=lots").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("min_traffic"));
+}
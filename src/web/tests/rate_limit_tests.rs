@@ -0,0 +1,133 @@
+use axum::body::{Body, to_bytes};
+use http::{HeaderMap, Request, StatusCode, header};
+use serde_json::{Value, json};
+use std::time::Duration;
+use tower::ServiceExt;
+
+use super::{ADMIN_KEY, TestApp, assert_error};
+use crate::config::{RateLimitConfig, RateLimitsConfig};
+use crate::web::middleware::auth::API_KEY_HEADER;
+use crate::web::middleware::rate_limit::RateLimiter;
+
+/// No metrics are stored, so requests let through get a 404.
+const AGGREGATION_URI: &str = "/metrics/summary?metric_name=latency";
+
+fn rate_limited_app() -> TestApp {
+    let mut app = TestApp::new();
+    app.state.rate_limiter = RateLimiter::new(RateLimitsConfig {
+        trust_forwarded_for: true,
+        aggregations: RateLimitConfig {
+            requests_per_minute: 6,
+            burst: 2,
+        },
+        ..RateLimitsConfig::default()
+    });
+    app
+}
+
+/// GETs `uri` as the client at `ip`, with `key` if any.
+async fn get_as(
+    app: &TestApp,
+    uri: &str,
+    ip: &str,
+    key: Option<&str>,
+) -> (StatusCode, HeaderMap, Value) {
+    let mut request = Request::get(uri).header("x-forwarded-for", format!("10.0.0.1, {}", ip));
+    if let Some(key) = key {
+        request = request.header(API_KEY_HEADER, key);
+    }
+
+    let response = app
+        .router()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    (status, headers, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test(start_paused = true)]
+async fn rejects_clients_over_the_budget_of_the_route_group() {
+    let app = rate_limited_app();
+
+    for _ in 0..2 {
+        let (status, _, _) = get_as(&app, AGGREGATION_URI, "192.0.2.1", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    let (status, headers, body) = get_as(&app, AGGREGATION_URI, "192.0.2.1", None).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_error(&body, "rate_limited", None);
+    assert_eq!(headers[header::RETRY_AFTER], "10");
+
+    // Other route groups have their own budget
+    let (status, _, _) = get_as(&app, "/health/live", "192.0.2.1", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    tokio::time::advance(Duration::from_secs(10)).await;
+    let (status, _, _) = get_as(&app, AGGREGATION_URI, "192.0.2.1", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(start_paused = true)]
+async fn limits_each_api_key_and_address_separately() {
+    let app = rate_limited_app();
+
+    for _ in 0..2 {
+        get_as(&app, AGGREGATION_URI, "192.0.2.1", None).await;
+    }
+    let (status, _, _) = get_as(&app, AGGREGATION_URI, "192.0.2.1", None).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, _, _) = get_as(&app, AGGREGATION_URI, "192.0.2.2", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // A valid key gets its own budget, whatever the address
+    let (status, _, _) = get_as(&app, AGGREGATION_URI, "192.0.2.1", Some(ADMIN_KEY)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // An invalid one counts against the address
+    let (status, _, _) = get_as(
+        &app,
+        AGGREGATION_URI,
+        "192.0.2.1",
+        Some("snk_not-a-real-key"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test(start_paused = true)]
+async fn throttles_invalid_keys_before_looking_them_up() {
+    let app = rate_limited_app();
+
+    for attempt in 0..5 {
+        let key = format!("snk_random-{}", attempt);
+        let (status, _, _) = get_as(&app, AGGREGATION_URI, "192.0.2.1", Some(&key)).await;
+        // Reads are public, so the keys themselves are not checked
+        let expected = if attempt < 2 {
+            StatusCode::NOT_FOUND
+        } else {
+            StatusCode::TOO_MANY_REQUESTS
+        };
+        assert_eq!(status, expected);
+    }
+    assert_eq!(app.db.api_key_lookups(), 2);
+
+    // Once looked up, a valid key no longer depends on the address
+    let (_, body) = app
+        .post(
+            "/admin/api-keys",
+            json!({ "name": "dashboard", "role": "reader" }),
+        )
+        .await;
+    let key = body["key"].as_str().unwrap();
+    let (status, _, _) = get_as(&app, AGGREGATION_URI, "192.0.2.2", Some(key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _, _) = get_as(&app, AGGREGATION_URI, "192.0.2.1", Some(key)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(app.db.api_key_lookups(), 3);
+}
@@ -0,0 +1,320 @@
+use super::{TestApp, assert_error, metric, oid, predictor};
+use chrono::{Duration, Utc};
+use http::{Method, StatusCode};
+use mongodb::bson::oid::ObjectId;
+use serde_json::json;
+
+use crate::database::repositories::models::deployment_repository_models::{
+    ActiveDeploymentDocument, DeploymentDocument,
+};
+
+/// Seeds v1 serving all traffic and an idle v2, returning v2's id.
+fn seed_deployment(app: &TestApp) -> ObjectId {
+    let v1 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 1, 100));
+    let v2 = app
+        .db
+        .insert_predictor(predictor("sentiment_analysis", 2, 0));
+    app.db.insert_deployment(DeploymentDocument {
+        id: None,
+        prediction_type: "sentiment_analysis".to_string(),
+        active_deployments: vec![ActiveDeploymentDocument {
+            predictor_id: v1,
+            traffic_percentage: 100.0,
+        }],
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    });
+
+    v2
+}
+
+fn rollout_request(predictor_id: ObjectId) -> serde_json::Value {
+    json!({
+        "prediction_type": "sentiment_analysis",
+        "predictor_id": predictor_id.to_hex(),
+        "steps": [10, 100],
+        "step_interval_hours": 1
+    })
+}
+
+#[tokio::test]
+async fn applies_the_first_step_of_a_rollout() {
+    let app = TestApp::new();
+    let v2 = seed_deployment(&app);
+
+    let (status, rollout) = app.post("/rollouts", rollout_request(v2)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(rollout["status"], "active");
+
+    app.state.rollout_service.process_due_rollouts().await;
+
+    let rollout_id = oid(&rollout["_id"]);
+    let (status, rollout) = app.get(&format!("/rollouts/{}", rollout_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rollout["current_step"], 0);
+
+    let (_, deployment) = app.get("/deployments/sentiment_analysis").await;
+    let traffic: Vec<(i64, f64)> = deployment["active_deployments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|deployment| {
+            (
+                deployment["predictor"]["predictor_version"]
+                    .as_i64()
+                    .unwrap(),
+                deployment["traffic_percentage"].as_f64().unwrap(),
+            )
+        })
+        .collect();
+    assert!(traffic.contains(&(1, 90.0)));
+    assert!(traffic.contains(&(2, 10.0)));
+
+    let (_, body) = app.get("/rollouts?status=active").await;
+    assert_eq!(body["rollouts"].as_array().unwrap().len(), 1);
+}
+
+/// Traffic percentage by predictor version of the sentiment deployment.
+async fn traffic(app: &TestApp) -> Vec<(i64, f64)> {
+    let (_, deployment) = app.get("/deployments/sentiment_analysis").await;
+    let mut traffic: Vec<(i64, f64)> = deployment["active_deployments"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|deployment| {
+            (
+                deployment["predictor"]["predictor_version"]
+                    .as_i64()
+                    .unwrap(),
+                deployment["traffic_percentage"].as_f64().unwrap(),
+            )
+        })
+        .collect();
+    traffic.sort_by_key(|(version, _)| *version);
+    traffic
+}
+
+#[tokio::test]
+async fn rolls_back_to_the_split_before_the_rollout() {
+    let app = TestApp::new();
+    let v2 = seed_deployment(&app);
+    let v1 = app.db.predictors()[0].id.unwrap();
+
+    let mut request = rollout_request(v2);
+    request["steps"] = json!([10, 50, 100]);
+    request["gate"] =
+        json!({ "metric_name": "error_rate", "max_avg_value": 0.1, "min_samples": 1 });
+    let (_, rollout) = app.post("/rollouts", request).await;
+    let rollout_uri = format!("/rollouts/{}", oid(&rollout["_id"]));
+
+    app.state.rollout_service.process_due_rollouts().await;
+    app.db
+        .insert_metric(metric("error_rate", 0.01, "2", Utc::now()));
+    app.db.make_rollouts_due();
+    app.state.rollout_service.process_due_rollouts().await;
+    assert_eq!(traffic(&app).await, [(1, 50.0), (2, 50.0)]);
+
+    // The gate fails while the baseline predictor cannot be deployed
+    app.db
+        .insert_metric(metric("error_rate", 0.9, "2", Utc::now()));
+    let removed = app.db.remove_predictor(v1).unwrap();
+    app.db.make_rollouts_due();
+    app.state.rollout_service.process_due_rollouts().await;
+
+    let (_, rollout) = app.get(&rollout_uri).await;
+    assert_eq!(rollout["status"], "active");
+    assert!(rollout["rollback_reason"].is_string(), "{}", rollout);
+    assert_eq!(rollout["current_step"], 1);
+
+    // The retry restores the split from before the first step, not 90/10
+    app.db.insert_predictor(removed);
+    app.db.make_rollouts_due();
+    app.state.rollout_service.process_due_rollouts().await;
+
+    let (_, rollout) = app.get(&rollout_uri).await;
+    assert_eq!(rollout["status"], "rolled_back");
+    assert_eq!(traffic(&app).await, [(1, 100.0)]);
+}
+
+#[tokio::test]
+async fn gates_steps_on_metrics_recorded_since_they_started() {
+    let app = TestApp::new();
+    let v2 = seed_deployment(&app);
+    app.db.insert_metric(metric(
+        "error_rate",
+        0.9,
+        "2",
+        Utc::now() - Duration::hours(1),
+    ));
+
+    let mut request = rollout_request(v2);
+    request["gate"] =
+        json!({ "metric_name": "error_rate", "max_avg_value": 0.1, "min_samples": 2 });
+    let (_, rollout) = app.post("/rollouts", request).await;
+    let rollout_uri = format!("/rollouts/{}", oid(&rollout["_id"]));
+
+    app.state.rollout_service.process_due_rollouts().await;
+    for _ in 0..2 {
+        app.db
+            .insert_metric(metric("error_rate", 0.01, "2", Utc::now()));
+    }
+    app.db.make_rollouts_due();
+    app.state.rollout_service.process_due_rollouts().await;
+
+    let (_, rollout) = app.get(&rollout_uri).await;
+    assert_eq!(rollout["current_step"], 1, "{}", rollout);
+    assert_eq!(traffic(&app).await, [(2, 100.0)]);
+}
+
+#[tokio::test]
+async fn rolls_back_steps_without_enough_samples() {
+    let app = TestApp::new();
+    let v2 = seed_deployment(&app);
+
+    let mut request = rollout_request(v2);
+    request["gate"] =
+        json!({ "metric_name": "error_rate", "max_avg_value": 0.1, "min_samples": 2 });
+    let (_, rollout) = app.post("/rollouts", request).await;
+    let rollout_uri = format!("/rollouts/{}", oid(&rollout["_id"]));
+
+    app.state.rollout_service.process_due_rollouts().await;
+    app.db
+        .insert_metric(metric("error_rate", 0.01, "2", Utc::now()));
+    app.db.make_rollouts_due();
+    app.state.rollout_service.process_due_rollouts().await;
+
+    let (_, rollout) = app.get(&rollout_uri).await;
+    assert_eq!(rollout["status"], "rolled_back");
+    assert_eq!(
+        rollout["status_reason"],
+        "only 1 values of 'error_rate' since the step started, 2 required"
+    );
+    assert_eq!(traffic(&app).await, [(1, 100.0)]);
+}
+
+#[tokio::test]
+async fn retries_steps_that_fail_to_apply() {
+    let app = TestApp::new();
+    let v2 = seed_deployment(&app);
+    let (_, rollout) = app.post("/rollouts", rollout_request(v2)).await;
+    let rollout_uri = format!("/rollouts/{}", oid(&rollout["_id"]));
+
+    app.db.set_unavailable(true);
+    app.state.rollout_service.process_due_rollouts().await;
+
+    let (_, rollout) = app.get(&rollout_uri).await;
+    assert_eq!(rollout["status"], "active");
+    assert!(rollout["current_step"].is_null(), "{}", rollout);
+
+    app.db.set_unavailable(false);
+    app.db.make_rollouts_due();
+    app.state.rollout_service.process_due_rollouts().await;
+
+    let (_, rollout) = app.get(&rollout_uri).await;
+    assert_eq!(rollout["current_step"], 0);
+    assert_eq!(traffic(&app).await, [(1, 90.0), (2, 10.0)]);
+}
+
+#[tokio::test]
+async fn allows_a_single_active_rollout_per_prediction_type() {
+    let app = TestApp::new();
+    let v2 = seed_deployment(&app);
+
+    let (status, _) = app.post("/rollouts", rollout_request(v2)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, body) = app.post("/rollouts", rollout_request(v2)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_error(&body, "conflict", None);
+}
+
+#[tokio::test]
+async fn refuses_manual_traffic_changes_during_a_rollout() {
+    let app = TestApp::new();
+    let v2 = seed_deployment(&app);
+    let (_, rollout) = app.post("/rollouts", rollout_request(v2)).await;
+    let rollout_id = oid(&rollout["_id"]);
+    let split = json!({
+        "splits": [{ "predictor_id": v2.to_hex(), "traffic_percentage": 100 }]
+    });
+
+    let (status, body) = app
+        .put("/deployments/sentiment_analysis/traffic", split.clone())
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_error(&body, "conflict", None);
+
+    app.send(
+        Method::POST,
+        &format!("/rollouts/{}/cancel", rollout_id),
+        None,
+    )
+    .await;
+    let (status, _) = app
+        .put("/deployments/sentiment_analysis/traffic", split)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(traffic(&app).await, [(2, 100.0)]);
+}
+
+#[tokio::test]
+async fn cancels_an_active_rollout_once() {
+    let app = TestApp::new();
+    let v2 = seed_deployment(&app);
+    let (_, rollout) = app.post("/rollouts", rollout_request(v2)).await;
+    let rollout_id = oid(&rollout["_id"]);
+
+    let (status, rollout) = app
+        .send(
+            Method::POST,
+            &format!("/rollouts/{}/cancel?reason=regression", rollout_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rollout["status"], "cancelled");
+    assert_eq!(rollout["status_reason"], "regression");
+
+    let (status, body) = app
+        .send(
+            Method::POST,
+            &format!("/rollouts/{}/cancel", rollout_id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_error(&body, "conflict", None);
+
+    let (status, _) = app
+        .send(
+            Method::POST,
+            &format!("/rollouts/{}/cancel", ObjectId::new()),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn rejects_invalid_rollout_plans() {
+    let app = TestApp::new();
+    let v2 = seed_deployment(&app);
+
+    let mut request = rollout_request(v2);
+    request["steps"] = json!([50, 10]);
+    let (status, body) = app.post("/rollouts", request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("steps"));
+
+    let (status, body) = app
+        .post("/rollouts", rollout_request(ObjectId::new()))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("predictor_id"));
+
+    let (status, body) = app.get("/rollouts/not-an-id").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_error(&body, "validation_error", Some("id"));
+}